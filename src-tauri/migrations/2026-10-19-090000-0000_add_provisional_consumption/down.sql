@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM electricity_consumption WHERE is_provisional;
+DELETE FROM gas_consumption WHERE is_provisional;
+
+ALTER TABLE electricity_consumption DROP COLUMN is_provisional;
+ALTER TABLE gas_consumption DROP COLUMN is_provisional;
@@ -0,0 +1,2 @@
+ALTER TABLE electricity_consumption ADD COLUMN is_provisional BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE gas_consumption ADD COLUMN is_provisional BOOLEAN NOT NULL DEFAULT FALSE;
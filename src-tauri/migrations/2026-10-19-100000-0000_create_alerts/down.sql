@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_triggered_alert_triggered_at;
+
+DROP TABLE IF EXISTS triggered_alert;
+
+DROP TABLE IF EXISTS alert_rule;
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS alert_rule (
+    alert_rule_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    threshold_watts DOUBLE NOT NULL,
+    duration_seconds INTEGER NOT NULL,
+    window_start TIME NULL,
+    window_end TIME NULL,
+    hysteresis_watts DOUBLE NOT NULL DEFAULT 0,
+    is_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    notify BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS triggered_alert (
+    triggered_alert_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    alert_rule_id INTEGER NOT NULL,
+    rule_name TEXT NOT NULL,
+    exceeded_since DATETIME NOT NULL,
+    triggered_at DATETIME NOT NULL,
+    power_watts DOUBLE NOT NULL,
+    threshold_watts DOUBLE NOT NULL
+);
+
+CREATE INDEX idx_triggered_alert_triggered_at ON triggered_alert(triggered_at);
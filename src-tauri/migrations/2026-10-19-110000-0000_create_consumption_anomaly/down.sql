@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_consumption_anomaly_london_date_id;
+
+DROP TABLE IF EXISTS consumption_anomaly;
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS consumption_anomaly (
+    consumption_anomaly_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    fuel TEXT NOT NULL,
+    granularity TEXT NOT NULL,
+    period_start DATETIME NOT NULL,
+    london_date_id INTEGER NOT NULL,
+    expected_wh DOUBLE NOT NULL,
+    actual_wh BIGINT NOT NULL,
+    z_score DOUBLE NOT NULL,
+    detected_at DATETIME NOT NULL,
+    UNIQUE (fuel, granularity, period_start)
+);
+
+CREATE INDEX idx_consumption_anomaly_london_date_id ON consumption_anomaly(london_date_id);
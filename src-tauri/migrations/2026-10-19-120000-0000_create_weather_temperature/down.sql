@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_weather_temperature_london_date_id;
+
+DROP TABLE IF EXISTS weather_temperature;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS weather_temperature (
+    weather_temperature_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    timestamp DATETIME NOT NULL UNIQUE,
+    temperature_celsius DOUBLE NOT NULL,
+    london_date_id INTEGER NOT NULL,
+    -- A daily reading is for a date rather than a time, so its london_date_id is kept when the
+    -- reporting timezone changes
+    is_daily BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE INDEX idx_weather_temperature_london_date_id ON weather_temperature(london_date_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE electricity_consumption DROP COLUMN quality_flag;
+ALTER TABLE gas_consumption DROP COLUMN quality_flag;
@@ -0,0 +1,29 @@
+-- 0 is a valid reading. Anything else is excluded from aggregates: 1 is the meter's error code,
+-- 2 is negative, 3 is implausibly large and 4 is off the half-hour grid and overlaps the previous
+-- reading.
+ALTER TABLE electricity_consumption ADD COLUMN quality_flag INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE gas_consumption ADD COLUMN quality_flag INTEGER NOT NULL DEFAULT 0;
+
+UPDATE electricity_consumption SET quality_flag = CASE
+    WHEN energy_consumption_wh = 16777215 THEN 1
+    WHEN energy_consumption_wh < 0 THEN 2
+    WHEN energy_consumption_wh > 25000 THEN 3
+    WHEN strftime('%M:%S', timestamp) NOT IN ('00:00', '30:00') AND EXISTS (
+        SELECT 1 FROM electricity_consumption AS previous
+        WHERE previous.timestamp < electricity_consumption.timestamp
+            AND previous.timestamp > datetime(electricity_consumption.timestamp, '-30 minutes')
+    ) THEN 4
+    ELSE 0
+END;
+
+UPDATE gas_consumption SET quality_flag = CASE
+    WHEN energy_consumption_wh = 16777215 THEN 1
+    WHEN energy_consumption_wh < 0 THEN 2
+    WHEN energy_consumption_wh > 50000 THEN 3
+    WHEN strftime('%M:%S', timestamp) NOT IN ('00:00', '30:00') AND EXISTS (
+        SELECT 1 FROM gas_consumption AS previous
+        WHERE previous.timestamp < gas_consumption.timestamp
+            AND previous.timestamp > datetime(gas_consumption.timestamp, '-30 minutes')
+    ) THEN 4
+    ELSE 0
+END;
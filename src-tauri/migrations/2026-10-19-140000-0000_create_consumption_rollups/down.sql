@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE gas_monthly_rollup;
+DROP TABLE gas_daily_rollup;
+DROP TABLE electricity_monthly_rollup;
+DROP TABLE electricity_daily_rollup;
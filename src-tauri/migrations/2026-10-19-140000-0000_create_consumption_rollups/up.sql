@@ -0,0 +1,44 @@
+-- Totals of the valid half-hourly readings, kept up to date as readings are inserted
+CREATE TABLE electricity_daily_rollup (
+    london_date_id INTEGER NOT NULL PRIMARY KEY,
+    energy_consumption_wh BIGINT NOT NULL,
+    is_provisional BOOLEAN NOT NULL
+);
+
+CREATE TABLE electricity_monthly_rollup (
+    london_month_id INTEGER NOT NULL PRIMARY KEY,
+    energy_consumption_wh BIGINT NOT NULL
+);
+
+CREATE TABLE gas_daily_rollup (
+    london_date_id INTEGER NOT NULL PRIMARY KEY,
+    energy_consumption_wh BIGINT NOT NULL,
+    is_provisional BOOLEAN NOT NULL
+);
+
+CREATE TABLE gas_monthly_rollup (
+    london_month_id INTEGER NOT NULL PRIMARY KEY,
+    energy_consumption_wh BIGINT NOT NULL
+);
+
+INSERT INTO electricity_daily_rollup (london_date_id, energy_consumption_wh, is_provisional)
+SELECT london_date_id, SUM(energy_consumption_wh), MAX(is_provisional)
+FROM electricity_consumption
+WHERE london_date_id IS NOT NULL AND quality_flag = 0
+GROUP BY london_date_id;
+
+INSERT INTO electricity_monthly_rollup (london_month_id, energy_consumption_wh)
+SELECT london_date_id - (london_date_id % 100) + 1, SUM(energy_consumption_wh)
+FROM electricity_daily_rollup
+GROUP BY london_date_id - (london_date_id % 100) + 1;
+
+INSERT INTO gas_daily_rollup (london_date_id, energy_consumption_wh, is_provisional)
+SELECT london_date_id, SUM(energy_consumption_wh), MAX(is_provisional)
+FROM gas_consumption
+WHERE london_date_id IS NOT NULL AND quality_flag = 0
+GROUP BY london_date_id;
+
+INSERT INTO gas_monthly_rollup (london_month_id, energy_consumption_wh)
+SELECT london_date_id - (london_date_id % 100) + 1, SUM(energy_consumption_wh)
+FROM gas_daily_rollup
+GROUP BY london_date_id - (london_date_id % 100) + 1;
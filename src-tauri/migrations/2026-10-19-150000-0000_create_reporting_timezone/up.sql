@@ -0,0 +1,6 @@
+-- The timezone that every date id is in, so that the app and the command line tool agree on it.
+-- The app records it when it next opens the database, from its setting at the time.
+CREATE TABLE reporting_timezone (
+    reporting_timezone_id INTEGER NOT NULL PRIMARY KEY CHECK (reporting_timezone_id = 1),
+    timezone TEXT NOT NULL
+);
@@ -0,0 +1,251 @@
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+
+use crate::data::alert::{AlertRule, NewTriggeredAlert};
+use crate::dates::reporting_timezone;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RuleState {
+    Clear,
+    Exceeding { since: NaiveDateTime },
+    Triggered,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvent {
+    pub alert_rule_id: i32,
+    pub rule_name: String,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub exceeded_since: NaiveDateTime,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub triggered_at: NaiveDateTime,
+    pub power_watts: f64,
+    pub threshold_watts: f64,
+    #[serde(skip)]
+    pub notify: bool,
+}
+
+impl From<&AlertEvent> for NewTriggeredAlert {
+    fn from(event: &AlertEvent) -> Self {
+        NewTriggeredAlert {
+            alert_rule_id: event.alert_rule_id,
+            rule_name: event.rule_name.clone(),
+            exceeded_since: event.exceeded_since,
+            triggered_at: event.triggered_at,
+            power_watts: event.power_watts,
+            threshold_watts: event.threshold_watts,
+        }
+    }
+}
+
+/// Evaluates live power readings against the configured alert rules.
+///
+/// A rule triggers once the power has stayed above its threshold for its duration within its
+/// time window. It does not trigger again until the power has dropped below the threshold by at
+/// least the hysteresis, or the time window has ended.
+pub struct PowerAlertEngine {
+    rules: Vec<(AlertRule, RuleState)>,
+}
+
+impl PowerAlertEngine {
+    pub fn new() -> Self {
+        Self { rules: vec![] }
+    }
+
+    /// Replaces the rules, keeping the state of rules that are unchanged.
+    pub fn set_rules(&mut self, rules: Vec<AlertRule>) {
+        let previous = std::mem::take(&mut self.rules);
+
+        self.rules = rules
+            .into_iter()
+            .map(|rule| {
+                let state = previous
+                    .iter()
+                    .find(|(r, _)| *r == rule)
+                    .map(|(_, s)| *s)
+                    .unwrap_or(RuleState::Clear);
+
+                (rule, state)
+            })
+            .collect();
+    }
+
+    /// Records a power reading taken at a UTC timestamp and returns any alerts it triggers.
+    pub fn evaluate(&mut self, timestamp: NaiveDateTime, power_watts: f64) -> Vec<AlertEvent> {
+        let local_time = timestamp
+            .and_utc()
+            .with_timezone(&reporting_timezone())
+            .time();
+
+        let mut events = vec![];
+
+        for (rule, state) in self.rules.iter_mut() {
+            if !rule.is_enabled {
+                *state = RuleState::Clear;
+                continue;
+            }
+
+            let in_window = is_in_window(rule.window_start, rule.window_end, local_time);
+            let is_above = power_watts > rule.threshold_watts;
+            let has_dropped = power_watts <= rule.threshold_watts - rule.hysteresis_watts;
+
+            *state = match *state {
+                _ if !in_window => RuleState::Clear,
+                RuleState::Clear if is_above => RuleState::Exceeding { since: timestamp },
+                RuleState::Clear => RuleState::Clear,
+                RuleState::Exceeding { .. } | RuleState::Triggered if has_dropped => {
+                    RuleState::Clear
+                }
+                other => other,
+            };
+
+            if let RuleState::Exceeding { since } = *state {
+                if timestamp - since >= Duration::seconds(rule.duration_seconds.into()) {
+                    *state = RuleState::Triggered;
+
+                    events.push(AlertEvent {
+                        alert_rule_id: rule.alert_rule_id,
+                        rule_name: rule.name.clone(),
+                        exceeded_since: since,
+                        triggered_at: timestamp,
+                        power_watts,
+                        threshold_watts: rule.threshold_watts,
+                        notify: rule.notify,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+}
+
+fn is_in_window(start: Option<NaiveTime>, end: Option<NaiveTime>, time: NaiveTime) -> bool {
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => start <= time && time < end,
+        // The window spans midnight, e.g. 23:00 to 06:00
+        (Some(start), Some(end)) => time >= start || time < end,
+        (Some(start), None) => time >= start,
+        (None, Some(end)) => time < end,
+        (None, None) => true,
+    }
+}
+
+/// Converts a power reading from the meter into watts.
+pub fn power_to_watts(value: f64, units: &str) -> Option<f64> {
+    match units.to_ascii_lowercase().as_str() {
+        "kw" => Some(value * 1000.0),
+        "w" => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, 15)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn rule() -> AlertRule {
+        AlertRule {
+            alert_rule_id: 1,
+            name: "High load".to_string(),
+            threshold_watts: 6000.0,
+            duration_seconds: 300,
+            window_start: None,
+            window_end: None,
+            hysteresis_watts: 500.0,
+            is_enabled: true,
+            notify: false,
+        }
+    }
+
+    fn engine(rule: AlertRule) -> PowerAlertEngine {
+        let mut engine = PowerAlertEngine::new();
+        engine.set_rules(vec![rule]);
+        engine
+    }
+
+    #[test]
+    fn test_triggers_after_duration() {
+        let mut engine = engine(rule());
+
+        assert!(engine.evaluate(at(18, 0), 6500.0).is_empty());
+        assert!(engine.evaluate(at(18, 4), 6500.0).is_empty());
+
+        let events = engine.evaluate(at(18, 5), 6200.0);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].exceeded_since, at(18, 0));
+        assert_eq!(events[0].power_watts, 6200.0);
+    }
+
+    #[test]
+    fn test_does_not_retrigger_until_dropped_by_hysteresis() {
+        let mut engine = engine(rule());
+
+        engine.evaluate(at(18, 0), 6500.0);
+        assert_eq!(engine.evaluate(at(18, 5), 6500.0).len(), 1);
+
+        // Dips below the threshold but within the hysteresis
+        assert!(engine.evaluate(at(18, 6), 5800.0).is_empty());
+        assert!(engine.evaluate(at(18, 12), 6500.0).is_empty());
+
+        engine.evaluate(at(18, 13), 5000.0);
+        engine.evaluate(at(18, 14), 6500.0);
+
+        assert_eq!(engine.evaluate(at(18, 19), 6500.0).len(), 1);
+    }
+
+    #[test]
+    fn test_ignores_readings_outside_window() {
+        let mut engine = engine(AlertRule {
+            threshold_watts: 300.0,
+            hysteresis_watts: 0.0,
+            window_start: NaiveTime::from_hms_opt(23, 0, 0),
+            window_end: NaiveTime::from_hms_opt(6, 0, 0),
+            ..rule()
+        });
+
+        engine.evaluate(at(12, 0), 1000.0);
+        assert!(engine.evaluate(at(12, 10), 1000.0).is_empty());
+
+        engine.evaluate(at(23, 30), 1000.0);
+        assert_eq!(engine.evaluate(at(23, 35), 1000.0).len(), 1);
+    }
+
+    #[test]
+    fn test_disabled_rule_never_triggers() {
+        let mut engine = engine(AlertRule {
+            is_enabled: false,
+            ..rule()
+        });
+
+        engine.evaluate(at(18, 0), 10000.0);
+        assert!(engine.evaluate(at(18, 10), 10000.0).is_empty());
+    }
+
+    #[test]
+    fn test_set_rules_keeps_state_of_unchanged_rules() {
+        let mut engine = engine(rule());
+
+        engine.evaluate(at(18, 0), 6500.0);
+        engine.set_rules(vec![rule()]);
+
+        assert_eq!(engine.evaluate(at(18, 5), 6500.0).len(), 1);
+    }
+
+    #[test]
+    fn test_power_to_watts() {
+        assert_eq!(power_to_watts(1.5, "kW"), Some(1500.0));
+        assert_eq!(power_to_watts(150.0, "W"), Some(150.0));
+        assert_eq!(power_to_watts(1.0, "kWh"), None);
+    }
+}
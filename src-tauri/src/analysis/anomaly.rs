@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+
+use crate::{
+    data::{
+        anomaly::{AnomalyRepository, NewAnomaly, SqliteAnomalyRepository},
+        consumption::{
+            ConsumptionRepository, SqliteElectricityConsumptionRepository,
+            SqliteGasConsumptionRepository, ENERGY_CONSUMPTION_WH_ERROR_CODE,
+        },
+        Fuel, RepositoryError,
+    },
+    dates::{
+        london_midnight_as_utc, london_slot_count, naive_date_to_london_date_id, reporting_timezone,
+    },
+    db::SqliteConnectionPool,
+};
+
+pub const DEFAULT_ANOMALY_Z_SCORE: f64 = 3.0;
+
+/// The baseline for a day is the same weekday in the preceding weeks, and around the same time a
+/// year earlier so that seasonal changes are taken into account.
+const RECENT_BASELINE_WEEKS: i64 = 8;
+const LAST_YEAR_BASELINE_WEEKS: i64 = 4;
+
+/// How far back data is needed to build a baseline.
+const BASELINE_HISTORY_DAYS: i64 = 7 * (52 + LAST_YEAR_BASELINE_WEEKS);
+
+const MIN_BASELINE_SAMPLES: usize = 4;
+
+/// Floors for the standard deviation, so that a very steady baseline doesn't flag small changes.
+const MIN_RELATIVE_STANDARD_DEVIATION: f64 = 0.05;
+const MIN_STANDARD_DEVIATION_WH: f64 = 10.0;
+
+pub const DAY_GRANULARITY: &str = "day";
+pub const INTERVAL_GRANULARITY: &str = "interval";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Anomaly<K> {
+    pub key: K,
+    pub expected_wh: f64,
+    pub actual_wh: i64,
+    pub z_score: f64,
+}
+
+fn baseline_dates(date: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    let recent = (1..=RECENT_BASELINE_WEEKS).map(move |weeks| date - Duration::weeks(weeks));
+    let last_year = (-LAST_YEAR_BASELINE_WEEKS..=LAST_YEAR_BASELINE_WEEKS)
+        .map(move |weeks| date - Duration::weeks(52 + weeks));
+
+    recent.chain(last_year)
+}
+
+/// Scores a value against its baseline, returning the expected value and the z-score.
+fn score(actual_wh: i64, baseline_wh: &[f64]) -> Option<(f64, f64)> {
+    if baseline_wh.len() < MIN_BASELINE_SAMPLES {
+        return None;
+    }
+
+    let count = baseline_wh.len() as f64;
+    let mean = baseline_wh.iter().sum::<f64>() / count;
+    let variance = baseline_wh.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1.0);
+
+    let standard_deviation = variance
+        .sqrt()
+        .max(mean.abs() * MIN_RELATIVE_STANDARD_DEVIATION)
+        .max(MIN_STANDARD_DEVIATION_WH);
+
+    Some((mean, (actual_wh as f64 - mean) / standard_deviation))
+}
+
+/// Finds London days from `start` up to `end` whose consumption is further from their baseline
+/// than `z_threshold` standard deviations.
+pub fn find_daily_anomalies(
+    daily_wh: &BTreeMap<NaiveDate, i64>,
+    start: NaiveDate,
+    end: NaiveDate,
+    z_threshold: f64,
+) -> Vec<Anomaly<NaiveDate>> {
+    daily_wh
+        .range(start..end)
+        .filter_map(|(date, actual_wh)| {
+            let baseline: Vec<f64> = baseline_dates(*date)
+                .filter_map(|d| daily_wh.get(&d))
+                .map(|wh| *wh as f64)
+                .collect();
+
+            let (expected_wh, z_score) = score(*actual_wh, &baseline)?;
+
+            (z_score.abs() >= z_threshold).then_some(Anomaly {
+                key: *date,
+                expected_wh,
+                actual_wh: *actual_wh,
+                z_score,
+            })
+        })
+        .collect()
+}
+
+/// Finds half hours, keyed by London date and half hour of the day, from `start` up to `end`
+/// whose consumption is further from the same half hour in the baseline days than
+/// `z_threshold` standard deviations.
+pub fn find_interval_anomalies(
+    interval_wh: &BTreeMap<(NaiveDate, u32), i64>,
+    start: NaiveDate,
+    end: NaiveDate,
+    z_threshold: f64,
+) -> Vec<Anomaly<(NaiveDate, u32)>> {
+    interval_wh
+        .range((start, 0)..(end, 0))
+        .filter_map(|((date, half_hour), actual_wh)| {
+            let baseline: Vec<f64> = baseline_dates(*date)
+                .filter_map(|d| interval_wh.get(&(d, *half_hour)))
+                .map(|wh| *wh as f64)
+                .collect();
+
+            let (expected_wh, z_score) = score(*actual_wh, &baseline)?;
+
+            (z_score.abs() >= z_threshold).then_some(Anomaly {
+                key: (*date, *half_hour),
+                expected_wh,
+                actual_wh: *actual_wh,
+                z_score,
+            })
+        })
+        .collect()
+}
+
+/// The date and half hour of the day, in the reporting timezone, of a UTC timestamp.
+pub fn london_half_hour(timestamp: &NaiveDateTime) -> (NaiveDate, u32) {
+    let local_timestamp = timestamp.and_utc().with_timezone(&reporting_timezone());
+
+    (
+        local_timestamp.date_naive(),
+        local_timestamp.hour() * 2 + local_timestamp.minute() / 30,
+    )
+}
+
+/// The London days with a reading for every half hour, counting both of the repeated half hours
+/// when the clocks go back.
+pub fn complete_days(timestamps: impl Iterator<Item = NaiveDateTime>) -> BTreeSet<NaiveDate> {
+    let mut slot_counts: BTreeMap<NaiveDate, u8> = BTreeMap::new();
+
+    for timestamp in timestamps {
+        let count = slot_counts
+            .entry(london_half_hour(&timestamp).0)
+            .or_insert(0);
+        *count = count.saturating_add(1);
+    }
+
+    slot_counts
+        .into_iter()
+        .filter(|(date, count)| *count == london_slot_count(date))
+        .map(|(date, _)| date)
+        .collect()
+}
+
+/// Detects anomalous days and half hours for a fuel on London days from `start` up to `end`,
+/// replacing any previously stored for those days. Only days with a reading for every half hour
+/// are scored, so that a day still being delivered isn't flagged as unusually low. Returns the
+/// number of anomalies found.
+pub fn detect_and_store_anomalies(
+    connection_pool: SqliteConnectionPool,
+    fuel: Fuel,
+    start: NaiveDate,
+    end: NaiveDate,
+    z_threshold: f64,
+) -> Result<usize, RepositoryError> {
+    let history_start = start - Duration::days(BASELINE_HISTORY_DAYS);
+
+    let (daily, raw): (Vec<(NaiveDate, i64)>, Vec<(NaiveDateTime, i64)>) = match fuel {
+        Fuel::Electricity => {
+            let repository = SqliteElectricityConsumptionRepository::new(connection_pool.clone());
+
+            (
+                repository
+                    .get_daily(history_start, end)?
+                    .into_iter()
+                    .map(|(date, wh, _)| (date, wh))
+                    .collect(),
+                repository
+                    .get_raw(history_start, end)?
+                    .into_iter()
+                    .map(|x| (x.timestamp, x.energy_consumption_wh))
+                    .collect(),
+            )
+        }
+        Fuel::Gas => {
+            let repository = SqliteGasConsumptionRepository::new(connection_pool.clone());
+
+            (
+                repository
+                    .get_daily(history_start, end)?
+                    .into_iter()
+                    .map(|(date, wh, _)| (date, wh))
+                    .collect(),
+                repository
+                    .get_raw(history_start, end)?
+                    .into_iter()
+                    .map(|x| (x.timestamp, x.energy_consumption_wh))
+                    .collect(),
+            )
+        }
+    };
+
+    let daily_wh: BTreeMap<NaiveDate, i64> = daily.into_iter().collect();
+
+    let complete_days = complete_days(raw.iter().map(|(timestamp, _)| *timestamp));
+
+    let mut interval_wh: BTreeMap<(NaiveDate, u32), i64> = BTreeMap::new();
+    let mut interval_timestamps: BTreeMap<(NaiveDate, u32), NaiveDateTime> = BTreeMap::new();
+
+    for (timestamp, wh) in raw {
+        if wh == ENERGY_CONSUMPTION_WH_ERROR_CODE {
+            continue;
+        }
+
+        // When the clocks go back, the repeated half hours are compared using the first of them
+        let key = london_half_hour(&timestamp);
+        interval_wh.entry(key).or_insert(wh);
+        interval_timestamps.entry(key).or_insert(timestamp);
+    }
+
+    let detected_at = Utc::now().naive_utc();
+
+    let daily_anomalies = find_daily_anomalies(&daily_wh, start, end, z_threshold)
+        .into_iter()
+        .filter(|anomaly| complete_days.contains(&anomaly.key))
+        .map(|anomaly| NewAnomaly {
+            fuel: fuel.as_str().to_string(),
+            granularity: DAY_GRANULARITY.to_string(),
+            period_start: london_midnight_as_utc(&anomaly.key),
+            london_date_id: naive_date_to_london_date_id(&anomaly.key),
+            expected_wh: anomaly.expected_wh,
+            actual_wh: anomaly.actual_wh,
+            z_score: anomaly.z_score,
+            detected_at,
+        });
+
+    let interval_anomalies = find_interval_anomalies(&interval_wh, start, end, z_threshold)
+        .into_iter()
+        .filter(|anomaly| complete_days.contains(&anomaly.key.0))
+        .map(|anomaly| NewAnomaly {
+            fuel: fuel.as_str().to_string(),
+            granularity: INTERVAL_GRANULARITY.to_string(),
+            period_start: interval_timestamps[&anomaly.key],
+            london_date_id: naive_date_to_london_date_id(&anomaly.key.0),
+            expected_wh: anomaly.expected_wh,
+            actual_wh: anomaly.actual_wh,
+            z_score: anomaly.z_score,
+            detected_at,
+        });
+
+    let anomalies: Vec<NewAnomaly> = daily_anomalies.chain(interval_anomalies).collect();
+    let anomaly_count = anomalies.len();
+
+    SqliteAnomalyRepository::new(connection_pool).replace_anomalies(fuel, start, end, anomalies)?;
+
+    Ok(anomaly_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// Eight weeks of Mondays alternating between 9 and 11 kWh, then one more Monday.
+    fn mondays(last_monday_wh: i64) -> BTreeMap<NaiveDate, i64> {
+        let last_monday = date(2026, 10, 19);
+
+        let mut daily_wh: BTreeMap<NaiveDate, i64> = (1..=8)
+            .map(|weeks| {
+                let wh = if weeks % 2 == 0 { 9_000 } else { 11_000 };
+                (last_monday - Duration::weeks(weeks), wh)
+            })
+            .collect();
+
+        daily_wh.insert(last_monday, last_monday_wh);
+
+        daily_wh
+    }
+
+    #[test]
+    fn test_flags_unusually_high_day() {
+        let anomalies = find_daily_anomalies(
+            &mondays(20_000),
+            date(2026, 10, 19),
+            date(2026, 10, 20),
+            3.0,
+        );
+
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].expected_wh, 10_000.0);
+        assert_eq!(anomalies[0].actual_wh, 20_000);
+        assert!(anomalies[0].z_score > 3.0);
+    }
+
+    #[test]
+    fn test_flags_unusually_low_day() {
+        let anomalies =
+            find_daily_anomalies(&mondays(1_000), date(2026, 10, 19), date(2026, 10, 20), 3.0);
+
+        assert_eq!(anomalies.len(), 1);
+        assert!(anomalies[0].z_score < -3.0);
+    }
+
+    #[test]
+    fn test_ignores_ordinary_day() {
+        let anomalies = find_daily_anomalies(
+            &mondays(10_500),
+            date(2026, 10, 19),
+            date(2026, 10, 20),
+            3.0,
+        );
+
+        assert!(anomalies.is_empty());
+    }
+
+    #[test]
+    fn test_needs_enough_baseline() {
+        let mut daily_wh = mondays(20_000);
+        daily_wh.retain(|d, _| *d > date(2026, 9, 25));
+
+        let anomalies =
+            find_daily_anomalies(&daily_wh, date(2026, 10, 19), date(2026, 10, 20), 3.0);
+
+        assert!(anomalies.is_empty());
+    }
+
+    #[test]
+    fn test_flags_unusual_interval() {
+        let mut interval_wh = BTreeMap::new();
+
+        for (d, wh) in mondays(10_000) {
+            interval_wh.insert((d, 3), wh / 100);
+            interval_wh.insert((d, 4), wh / 100);
+        }
+
+        interval_wh.insert((date(2026, 10, 19), 4), 1_000);
+
+        let anomalies =
+            find_interval_anomalies(&interval_wh, date(2026, 10, 19), date(2026, 10, 20), 3.0);
+
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].key, (date(2026, 10, 19), 4));
+    }
+
+    #[test]
+    fn test_complete_days_need_every_half_hour() {
+        let half_hours = |start: NaiveDateTime, count: i64| {
+            (0..count).map(move |i| start + Duration::minutes(30 * i))
+        };
+
+        // January 2nd is missing its last half hour, and the clocks go back on October 25th
+        let timestamps = half_hours(date(2026, 1, 1).and_hms_opt(0, 0, 0).unwrap(), 48)
+            .chain(half_hours(
+                date(2026, 1, 2).and_hms_opt(0, 0, 0).unwrap(),
+                47,
+            ))
+            .chain(half_hours(
+                date(2026, 10, 24).and_hms_opt(23, 0, 0).unwrap(),
+                50,
+            ));
+
+        assert_eq!(
+            complete_days(timestamps),
+            BTreeSet::from([date(2026, 1, 1), date(2026, 10, 25)])
+        );
+    }
+
+    #[test]
+    fn test_london_half_hour_in_british_summer_time() {
+        let timestamp = date(2026, 7, 14).and_hms_opt(23, 30, 0).unwrap();
+
+        assert_eq!(london_half_hour(&timestamp), (date(2026, 7, 15), 1));
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use serde::Serialize;
+
+use super::{linear_fit, median, percentile};
+use crate::dates::reporting_timezone;
+
+/// The overnight hours, in local time, in which the baseload is measured. Little other than
+/// always-on appliances should be running.
+const OVERNIGHT_START_HOUR: u32 = 0;
+const OVERNIGHT_END_HOUR: u32 = 5;
+
+/// A low percentile rather than the minimum, so that a single odd reading doesn't count.
+const BASELOAD_PERCENTILE: f64 = 0.1;
+
+/// The fewest overnight half hours, out of ten, needed to estimate a day's baseload.
+const MIN_OVERNIGHT_READINGS: usize = 6;
+
+const HOURS_PER_YEAR: f64 = 8760.0;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyBaseload {
+    pub date: NaiveDate,
+    pub watts: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseloadEstimate {
+    pub daily: Vec<DailyBaseload>,
+    /// The median of the daily baseloads
+    pub baseload_watts: Option<f64>,
+    pub annual_kwh: Option<f64>,
+    pub unit_price_pence: Option<f64>,
+    pub annual_cost_pence: Option<f64>,
+    /// The change in the daily baseload per day over the period, from a linear fit
+    pub trend_watts_per_day: Option<f64>,
+}
+
+/// Estimates the baseload for each London day from half-hourly readings, given as UTC
+/// timestamps and Wh.
+pub fn estimate_daily_baseload(readings: &[(NaiveDateTime, i64)]) -> Vec<DailyBaseload> {
+    let mut overnight_watts: BTreeMap<NaiveDate, Vec<f64>> = BTreeMap::new();
+
+    for (timestamp, energy_wh) in readings {
+        let local_timestamp = timestamp.and_utc().with_timezone(&reporting_timezone());
+
+        if (OVERNIGHT_START_HOUR..OVERNIGHT_END_HOUR).contains(&local_timestamp.hour()) {
+            // The energy used in half an hour in Wh is half the average power in W
+            overnight_watts
+                .entry(local_timestamp.date_naive())
+                .or_default()
+                .push(*energy_wh as f64 * 2.0);
+        }
+    }
+
+    overnight_watts
+        .into_iter()
+        .filter(|(_, watts)| watts.len() >= MIN_OVERNIGHT_READINGS)
+        .filter_map(|(date, mut watts)| {
+            watts.sort_by(f64::total_cmp);
+
+            percentile(&watts, BASELOAD_PERCENTILE).map(|watts| DailyBaseload { date, watts })
+        })
+        .collect()
+}
+
+/// Summarises daily baseloads, costing them at a unit price in pence per kWh.
+pub fn summarise_baseload(
+    daily: Vec<DailyBaseload>,
+    unit_price_pence: Option<f64>,
+) -> BaseloadEstimate {
+    let watts: Vec<f64> = daily.iter().map(|d| d.watts).collect();
+
+    let baseload_watts = median(&watts);
+    let annual_kwh = baseload_watts.map(|watts| watts * HOURS_PER_YEAR / 1000.0);
+    let annual_cost_pence = annual_kwh
+        .zip(unit_price_pence)
+        .map(|(kwh, price)| kwh * price);
+
+    let trend_watts_per_day = daily.first().and_then(|first| {
+        let points: Vec<(f64, f64)> = daily
+            .iter()
+            .map(|d| ((d.date - first.date).num_days() as f64, d.watts))
+            .collect();
+
+        linear_fit(&points).map(|(slope, _)| slope)
+    });
+
+    BaseloadEstimate {
+        daily,
+        baseload_watts,
+        annual_kwh,
+        unit_price_pence,
+        annual_cost_pence,
+        trend_watts_per_day,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readings(date: NaiveDate, energy_wh: impl Fn(u32) -> i64) -> Vec<(NaiveDateTime, i64)> {
+        (0..48)
+            .map(|half_hour| {
+                let timestamp = date
+                    .and_hms_opt(half_hour / 2, (half_hour % 2) * 30, 0)
+                    .unwrap();
+
+                (timestamp, energy_wh(half_hour))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_baseload_uses_overnight_readings() {
+        let date = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+
+        // 100 Wh per half hour overnight, with one lower reading, and much more during the day
+        let readings = readings(date, |half_hour| match half_hour {
+            3 => 20,
+            0..=9 => 100,
+            _ => 1000,
+        });
+
+        let daily = estimate_daily_baseload(&readings);
+
+        assert_eq!(daily, vec![DailyBaseload { date, watts: 40.0 }]);
+    }
+
+    #[test]
+    fn test_baseload_in_british_summer_time() {
+        let date = NaiveDate::from_ymd_opt(2026, 7, 15).unwrap();
+
+        // 23:00 UTC the day before is midnight in London
+        let readings: Vec<_> = readings(date.pred_opt().unwrap(), |_| 500)
+            .into_iter()
+            .chain(readings(
+                date,
+                |half_hour| if half_hour < 8 { 50 } else { 500 },
+            ))
+            .collect();
+
+        let daily = estimate_daily_baseload(&readings);
+        let baseload = daily.iter().find(|d| d.date == date).unwrap();
+
+        assert_eq!(baseload.watts, 100.0);
+    }
+
+    #[test]
+    fn test_baseload_skips_days_with_too_few_readings() {
+        let date = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+
+        let readings: Vec<_> = readings(date, |_| 100).into_iter().skip(5).collect();
+
+        assert!(estimate_daily_baseload(&readings).is_empty());
+    }
+
+    #[test]
+    fn test_summarise_baseload() {
+        let start = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+
+        let daily: Vec<_> = (0..3)
+            .map(|day| DailyBaseload {
+                date: start + chrono::Duration::days(day),
+                watts: 100.0 + 10.0 * day as f64,
+            })
+            .collect();
+
+        let estimate = summarise_baseload(daily, Some(25.0));
+
+        assert_eq!(estimate.baseload_watts, Some(110.0));
+        assert_eq!(estimate.annual_kwh, Some(963.6));
+        assert!((estimate.annual_cost_pence.unwrap() - 24090.0).abs() < 1e-6);
+        assert!((estimate.trend_watts_per_day.unwrap() - 10.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Which earlier period to compare a date range with.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ComparisonOffset {
+    /// The period of the same length immediately before
+    PreviousPeriod,
+    /// The same period a year earlier
+    SamePeriodLastYear,
+}
+
+/// How the days of the two periods are paired up.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ComparisonAlignment {
+    /// Pairs each day with the same day of the week, e.g. Monday with Monday
+    #[default]
+    DayOfWeek,
+    /// Pairs each day with the same calendar date
+    CalendarDate,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ComparisonGranularity {
+    #[default]
+    Day,
+    Month,
+}
+
+/// The consumption, in Wh, and the cost, in pence, for each day or month of a period starting
+/// on `start` and ending before `end`.
+pub struct ComparisonPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub values: BTreeMap<NaiveDate, i64>,
+    pub costs: BTreeMap<NaiveDate, f64>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonBucket {
+    pub timestamp: NaiveDate,
+    pub comparison_timestamp: NaiveDate,
+    pub value: Option<i64>,
+    pub comparison_value: Option<i64>,
+    pub delta: Option<i64>,
+    pub percent_change: Option<f64>,
+    pub cost_pence: Option<f64>,
+    pub comparison_cost_pence: Option<f64>,
+    pub cost_difference_pence: Option<f64>,
+}
+
+/// The totals over the buckets that have values in both periods, so that missing data in one
+/// period doesn't look like a saving.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonTotals {
+    pub bucket_count: usize,
+    pub value: i64,
+    pub comparison_value: i64,
+    pub delta: i64,
+    pub percent_change: Option<f64>,
+    pub cost_pence: f64,
+    pub comparison_cost_pence: f64,
+    pub cost_difference_pence: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodComparison {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub comparison_start: NaiveDate,
+    pub comparison_end: NaiveDate,
+    pub buckets: Vec<ComparisonBucket>,
+    pub totals: ComparisonTotals,
+}
+
+/// Returns the range to compare `start` to `end` with. Aligning by day of the week shifts by
+/// whole weeks, so that weekdays line up with weekdays.
+pub fn offset_range(
+    start: NaiveDate,
+    end: NaiveDate,
+    offset: ComparisonOffset,
+    alignment: ComparisonAlignment,
+) -> (NaiveDate, NaiveDate) {
+    let day_count = (end - start).num_days();
+
+    match (offset, alignment) {
+        (ComparisonOffset::SamePeriodLastYear, ComparisonAlignment::DayOfWeek) => {
+            (start - Duration::weeks(52), end - Duration::weeks(52))
+        }
+        // Clamps February 29th to the 28th
+        (ComparisonOffset::SamePeriodLastYear, ComparisonAlignment::CalendarDate) => {
+            (start - Months::new(12), end - Months::new(12))
+        }
+        (ComparisonOffset::PreviousPeriod, ComparisonAlignment::DayOfWeek) => {
+            let shift = Duration::weeks((day_count + 6) / 7);
+
+            (start - shift, end - shift)
+        }
+        (ComparisonOffset::PreviousPeriod, ComparisonAlignment::CalendarDate) => {
+            match whole_months(start, end) {
+                Some(month_count) => (start - Months::new(month_count), start),
+                None => (start - Duration::days(day_count), start),
+            }
+        }
+    }
+}
+
+/// The number of months between two dates that are both the first of a month.
+fn whole_months(start: NaiveDate, end: NaiveDate) -> Option<u32> {
+    if start.day() != 1 || end.day() != 1 || end <= start {
+        return None;
+    }
+
+    u32::try_from(month_index(start, end)).ok()
+}
+
+fn month_index(start: NaiveDate, date: NaiveDate) -> i64 {
+    (date.year() as i64 - start.year() as i64) * 12 + date.month() as i64 - start.month() as i64
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("The first of the month to exist")
+}
+
+/// Returns the date each bucket of a period starts on.
+fn bucket_dates(period: &ComparisonPeriod, granularity: ComparisonGranularity) -> Vec<NaiveDate> {
+    match granularity {
+        ComparisonGranularity::Day => period
+            .start
+            .iter_days()
+            .take_while(|date| *date < period.end)
+            .collect(),
+        ComparisonGranularity::Month => {
+            let first_month = first_of_month(period.start);
+
+            (0..)
+                .map(|i| first_month + Months::new(i))
+                .take_while(|month| *month < period.end)
+                .collect()
+        }
+    }
+}
+
+/// Sums the daily costs of a period into its buckets.
+fn bucket_costs(
+    period: &ComparisonPeriod,
+    granularity: ComparisonGranularity,
+) -> BTreeMap<NaiveDate, f64> {
+    let mut costs = BTreeMap::new();
+
+    for (date, cost) in period.costs.range(period.start..period.end) {
+        let bucket = match granularity {
+            ComparisonGranularity::Day => *date,
+            ComparisonGranularity::Month => first_of_month(*date),
+        };
+
+        *costs.entry(bucket).or_insert(0.0) += cost;
+    }
+
+    costs
+}
+
+fn percent_change(value: i64, comparison_value: i64) -> Option<f64> {
+    if comparison_value == 0 {
+        return None;
+    }
+
+    Some((value - comparison_value) as f64 * 100.0 / comparison_value as f64)
+}
+
+/// Pairs the buckets of two periods in order and returns the differences between them. The
+/// periods may have different lengths, in which case the extra buckets have no counterpart.
+pub fn compare_periods(
+    current: &ComparisonPeriod,
+    comparison: &ComparisonPeriod,
+    granularity: ComparisonGranularity,
+) -> PeriodComparison {
+    let current_dates = bucket_dates(current, granularity);
+    let comparison_dates = bucket_dates(comparison, granularity);
+
+    let current_costs = bucket_costs(current, granularity);
+    let comparison_costs = bucket_costs(comparison, granularity);
+
+    let bucket_count = current_dates.len().max(comparison_dates.len());
+
+    let buckets: Vec<ComparisonBucket> = (0..bucket_count)
+        .filter_map(|i| {
+            let date = current_dates.get(i).copied();
+            let comparison_date = comparison_dates.get(i).copied();
+
+            let value = date.and_then(|d| current.values.get(&d).copied());
+            let comparison_value = comparison_date.and_then(|d| comparison.values.get(&d).copied());
+
+            let cost_pence = date.and_then(|d| current_costs.get(&d).copied());
+            let comparison_cost_pence =
+                comparison_date.and_then(|d| comparison_costs.get(&d).copied());
+
+            let both_values = value.zip(comparison_value);
+            let both_costs = cost_pence.zip(comparison_cost_pence);
+
+            Some(ComparisonBucket {
+                timestamp: date.or(comparison_date)?,
+                comparison_timestamp: comparison_date.or(date)?,
+                value,
+                comparison_value,
+                delta: both_values.map(|(v, c)| v - c),
+                percent_change: both_values.and_then(|(v, c)| percent_change(v, c)),
+                cost_pence,
+                comparison_cost_pence,
+                cost_difference_pence: both_costs.map(|(v, c)| v - c),
+            })
+        })
+        .collect();
+
+    let paired: Vec<&ComparisonBucket> = buckets
+        .iter()
+        .filter(|bucket| bucket.value.is_some() && bucket.comparison_value.is_some())
+        .collect();
+
+    let value: i64 = paired.iter().filter_map(|bucket| bucket.value).sum();
+    let comparison_value: i64 = paired
+        .iter()
+        .filter_map(|bucket| bucket.comparison_value)
+        .sum();
+
+    let (cost_pence, comparison_cost_pence) = paired
+        .iter()
+        .filter_map(|bucket| bucket.cost_pence.zip(bucket.comparison_cost_pence))
+        .fold(
+            (0.0, 0.0),
+            |(total, comparison_total), (cost, comparison_cost)| {
+                (total + cost, comparison_total + comparison_cost)
+            },
+        );
+
+    PeriodComparison {
+        start: current.start,
+        end: current.end,
+        comparison_start: comparison.start,
+        comparison_end: comparison.end,
+        totals: ComparisonTotals {
+            bucket_count: paired.len(),
+            value,
+            comparison_value,
+            delta: value - comparison_value,
+            percent_change: percent_change(value, comparison_value),
+            cost_pence,
+            comparison_cost_pence,
+            cost_difference_pence: cost_pence - comparison_cost_pence,
+        },
+        buckets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn period(
+        start: NaiveDate,
+        end: NaiveDate,
+        values: &[(NaiveDate, i64)],
+        costs: &[(NaiveDate, f64)],
+    ) -> ComparisonPeriod {
+        ComparisonPeriod {
+            start,
+            end,
+            values: values.iter().copied().collect(),
+            costs: costs.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn test_same_period_last_year_by_day_of_week() {
+        let (start, end) = offset_range(
+            date(2026, 10, 19),
+            date(2026, 10, 26),
+            ComparisonOffset::SamePeriodLastYear,
+            ComparisonAlignment::DayOfWeek,
+        );
+
+        assert_eq!(start, date(2025, 10, 20));
+        assert_eq!(end, date(2025, 10, 27));
+        assert_eq!(start.weekday(), date(2026, 10, 19).weekday());
+    }
+
+    #[test]
+    fn test_same_period_last_year_by_calendar_date() {
+        assert_eq!(
+            offset_range(
+                date(2028, 2, 29),
+                date(2028, 3, 1),
+                ComparisonOffset::SamePeriodLastYear,
+                ComparisonAlignment::CalendarDate,
+            ),
+            (date(2027, 2, 28), date(2027, 3, 1))
+        );
+    }
+
+    #[test]
+    fn test_previous_period() {
+        // A 10 day period shifts back two whole weeks to keep the weekdays aligned
+        assert_eq!(
+            offset_range(
+                date(2026, 10, 19),
+                date(2026, 10, 29),
+                ComparisonOffset::PreviousPeriod,
+                ComparisonAlignment::DayOfWeek,
+            ),
+            (date(2026, 10, 5), date(2026, 10, 15))
+        );
+
+        assert_eq!(
+            offset_range(
+                date(2026, 10, 19),
+                date(2026, 10, 29),
+                ComparisonOffset::PreviousPeriod,
+                ComparisonAlignment::CalendarDate,
+            ),
+            (date(2026, 10, 9), date(2026, 10, 19))
+        );
+
+        // Whole months compare with the previous months
+        assert_eq!(
+            offset_range(
+                date(2026, 3, 1),
+                date(2026, 4, 1),
+                ComparisonOffset::PreviousPeriod,
+                ComparisonAlignment::CalendarDate,
+            ),
+            (date(2026, 2, 1), date(2026, 3, 1))
+        );
+    }
+
+    #[test]
+    fn test_compare_daily() {
+        let current = period(
+            date(2026, 10, 19),
+            date(2026, 10, 22),
+            &[(date(2026, 10, 19), 9_000), (date(2026, 10, 20), 12_000)],
+            &[(date(2026, 10, 19), 300.0), (date(2026, 10, 20), 400.0)],
+        );
+        let comparison = period(
+            date(2025, 10, 20),
+            date(2025, 10, 23),
+            &[
+                (date(2025, 10, 20), 10_000),
+                (date(2025, 10, 21), 12_000),
+                (date(2025, 10, 22), 11_000),
+            ],
+            &[(date(2025, 10, 20), 280.0), (date(2025, 10, 21), 330.0)],
+        );
+
+        let result = compare_periods(&current, &comparison, ComparisonGranularity::Day);
+
+        assert_eq!(result.buckets.len(), 3);
+        assert_eq!(result.buckets[0].delta, Some(-1_000));
+        assert_eq!(result.buckets[0].percent_change, Some(-10.0));
+        assert_eq!(result.buckets[0].cost_difference_pence, Some(20.0));
+        assert_eq!(result.buckets[1].percent_change, Some(0.0));
+        assert_eq!(result.buckets[2].value, None);
+        assert_eq!(result.buckets[2].delta, None);
+
+        // The day missing from the current period is left out of the totals
+        assert_eq!(result.totals.bucket_count, 2);
+        assert_eq!(result.totals.value, 21_000);
+        assert_eq!(result.totals.comparison_value, 22_000);
+        assert_eq!(result.totals.delta, -1_000);
+        assert_eq!(result.totals.cost_difference_pence, 90.0);
+    }
+
+    #[test]
+    fn test_compare_monthly() {
+        let current = period(
+            date(2026, 1, 1),
+            date(2026, 3, 1),
+            &[(date(2026, 1, 1), 300_000), (date(2026, 2, 1), 250_000)],
+            &[
+                (date(2026, 1, 1), 100.0),
+                (date(2026, 1, 31), 100.0),
+                (date(2026, 2, 1), 150.0),
+            ],
+        );
+        let comparison = period(
+            date(2025, 1, 1),
+            date(2025, 3, 1),
+            &[(date(2025, 1, 1), 400_000), (date(2025, 2, 1), 250_000)],
+            &[(date(2025, 1, 15), 250.0), (date(2025, 2, 1), 150.0)],
+        );
+
+        let result = compare_periods(&current, &comparison, ComparisonGranularity::Month);
+
+        assert_eq!(result.buckets.len(), 2);
+        assert_eq!(result.buckets[0].timestamp, date(2026, 1, 1));
+        assert_eq!(result.buckets[0].comparison_timestamp, date(2025, 1, 1));
+        assert_eq!(result.buckets[0].percent_change, Some(-25.0));
+        assert_eq!(result.buckets[0].cost_difference_pence, Some(-50.0));
+        assert_eq!(
+            result.totals.percent_change,
+            Some(-100_000.0 * 100.0 / 650_000.0)
+        );
+    }
+}
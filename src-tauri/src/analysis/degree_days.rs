@@ -0,0 +1,95 @@
+use serde::Serialize;
+
+use super::linear_fit;
+
+/// The base temperature commonly used for heating degree days in the UK.
+pub const DEFAULT_BASE_TEMPERATURE_CELSIUS: f64 = 15.5;
+
+/// The heating degree days for a day with the given mean temperature.
+pub fn heating_degree_days(mean_temperature_celsius: f64, base_temperature_celsius: f64) -> f64 {
+    (base_temperature_celsius - mean_temperature_celsius).max(0.0)
+}
+
+/// A straight line fit of daily gas consumption against heating degree days. The slope is the
+/// heating load and the intercept is the load that doesn't depend on the weather, which is
+/// mostly hot water and cooking.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DegreeDayRegression {
+    pub day_count: usize,
+    pub base_temperature_celsius: f64,
+    pub kwh_per_degree_day: f64,
+    pub hot_water_kwh_per_day: f64,
+    /// How much of the variation in daily consumption the fit explains, from 0 to 1
+    pub r_squared: f64,
+    pub heating_kwh: f64,
+    pub hot_water_kwh: f64,
+}
+
+/// Fits daily consumption in kWh against heating degree days, given as `(hdd, kwh)` pairs.
+pub fn degree_day_regression(
+    days: &[(f64, f64)],
+    base_temperature_celsius: f64,
+) -> Option<DegreeDayRegression> {
+    let (slope, intercept) = linear_fit(days)?;
+
+    let mean_kwh = days.iter().map(|(_, kwh)| kwh).sum::<f64>() / days.len() as f64;
+
+    let total_sum_of_squares: f64 = days.iter().map(|(_, kwh)| (kwh - mean_kwh).powi(2)).sum();
+    let residual_sum_of_squares: f64 = days
+        .iter()
+        .map(|(hdd, kwh)| (kwh - (slope * hdd + intercept)).powi(2))
+        .sum();
+
+    let r_squared = if total_sum_of_squares > 0.0 {
+        1.0 - residual_sum_of_squares / total_sum_of_squares
+    } else {
+        1.0
+    };
+
+    let total_kwh: f64 = days.iter().map(|(_, kwh)| kwh).sum();
+    let hot_water_kwh = (intercept.max(0.0) * days.len() as f64).min(total_kwh);
+
+    Some(DegreeDayRegression {
+        day_count: days.len(),
+        base_temperature_celsius,
+        kwh_per_degree_day: slope,
+        hot_water_kwh_per_day: intercept,
+        r_squared,
+        heating_kwh: total_kwh - hot_water_kwh,
+        hot_water_kwh,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heating_degree_days() {
+        assert_eq!(heating_degree_days(5.5, 15.5), 10.0);
+        assert_eq!(heating_degree_days(20.0, 15.5), 0.0);
+    }
+
+    #[test]
+    fn test_degree_day_regression() {
+        // 8 kWh a day of hot water, plus 3 kWh per degree day
+        let days: Vec<(f64, f64)> = [0.0, 2.0, 5.0, 10.0]
+            .iter()
+            .map(|hdd| (*hdd, 8.0 + 3.0 * hdd))
+            .collect();
+
+        let regression = degree_day_regression(&days, 15.5).unwrap();
+
+        assert!((regression.kwh_per_degree_day - 3.0).abs() < 1e-9);
+        assert!((regression.hot_water_kwh_per_day - 8.0).abs() < 1e-9);
+        assert!((regression.r_squared - 1.0).abs() < 1e-9);
+        assert!((regression.hot_water_kwh - 32.0).abs() < 1e-9);
+        assert!((regression.heating_kwh - 51.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_degree_day_regression_needs_varied_weather() {
+        assert_eq!(degree_day_regression(&[(0.0, 8.0), (0.0, 9.0)], 15.5), None);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+use super::anomaly::london_half_hour;
+use crate::dates::london_slot_count;
+
+/// The number of columns, one for each half hour of the London wall clock.
+pub const HEATMAP_SLOT_COUNT: usize = 48;
+
+/// A dense day by half-hour matrix of consumption in Wh, with a row for each London date and a
+/// column for each half hour of the wall clock.
+///
+/// Days when the clocks change don't have 48 half hours. On the 46 half hour day in spring the
+/// skipped hour is null, and on the 50 half hour day in autumn both occurrences of the repeated
+/// hour are added together, so each column always means the same time of day. `slot_counts`
+/// gives the real number of half hours in each day.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumptionHeatmap {
+    pub dates: Vec<NaiveDate>,
+    pub slot_counts: Vec<u8>,
+    /// A row for each date, with null for missing or invalid readings
+    pub values: Vec<Vec<Option<i64>>>,
+    pub max_value: Option<i64>,
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Missing,
+    Valid(i64),
+    Invalid,
+}
+
+/// Builds the heatmap for London days from `start` up to `end` from half-hourly readings at UTC
+/// timestamps. `is_valid` decides which readings are shown.
+pub fn consumption_heatmap(
+    start: NaiveDate,
+    end: NaiveDate,
+    readings: &[(NaiveDateTime, i64)],
+    is_valid: impl Fn(i64) -> bool,
+) -> ConsumptionHeatmap {
+    let mut slots: BTreeMap<NaiveDate, [Slot; HEATMAP_SLOT_COUNT]> = BTreeMap::new();
+
+    for (timestamp, value) in readings {
+        let (date, half_hour) = london_half_hour(timestamp);
+
+        if date < start || date >= end {
+            continue;
+        }
+
+        let row = slots
+            .entry(date)
+            .or_insert([Slot::Missing; HEATMAP_SLOT_COUNT]);
+        let slot = &mut row[half_hour as usize];
+
+        *slot = match (*slot, is_valid(*value)) {
+            (Slot::Invalid, _) | (_, false) => Slot::Invalid,
+            (Slot::Missing, true) => Slot::Valid(*value),
+            (Slot::Valid(previous), true) => Slot::Valid(previous + value),
+        };
+    }
+
+    let dates: Vec<NaiveDate> = start.iter_days().take_while(|date| *date < end).collect();
+
+    let values: Vec<Vec<Option<i64>>> = dates
+        .iter()
+        .map(|date| match slots.get(date) {
+            Some(row) => row
+                .iter()
+                .map(|slot| match slot {
+                    Slot::Valid(value) => Some(*value),
+                    Slot::Missing | Slot::Invalid => None,
+                })
+                .collect(),
+            None => vec![None; HEATMAP_SLOT_COUNT],
+        })
+        .collect();
+
+    ConsumptionHeatmap {
+        slot_counts: dates.iter().map(london_slot_count).collect(),
+        max_value: values.iter().flatten().flatten().max().copied(),
+        dates,
+        values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::consumption::ENERGY_CONSUMPTION_WH_ERROR_CODE;
+    use chrono::Duration;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn utc(date: NaiveDate, hour: u32, minute: u32) -> NaiveDateTime {
+        date.and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn is_valid(value: i64) -> bool {
+        value >= 0 && value != ENERGY_CONSUMPTION_WH_ERROR_CODE
+    }
+
+    #[test]
+    fn test_missing_and_invalid_readings_are_null() {
+        let day = date(2026, 1, 15);
+
+        let heatmap = consumption_heatmap(
+            day,
+            date(2026, 1, 17),
+            &[
+                (utc(day, 0, 0), 100),
+                (utc(day, 0, 30), ENERGY_CONSUMPTION_WH_ERROR_CODE),
+                (utc(day, 23, 30), 300),
+            ],
+            is_valid,
+        );
+
+        assert_eq!(heatmap.dates, vec![day, date(2026, 1, 16)]);
+        assert_eq!(heatmap.values[0][0], Some(100));
+        assert_eq!(heatmap.values[0][1], None);
+        assert_eq!(heatmap.values[0][2], None);
+        assert_eq!(heatmap.values[0][47], Some(300));
+        assert_eq!(heatmap.values[1], vec![None; HEATMAP_SLOT_COUNT]);
+        assert_eq!(heatmap.max_value, Some(300));
+    }
+
+    #[test]
+    fn test_british_summer_time_uses_the_wall_clock() {
+        let day = date(2026, 7, 15);
+
+        // 23:00 UTC the day before is midnight in London
+        let heatmap = consumption_heatmap(
+            day,
+            date(2026, 7, 16),
+            &[(utc(date(2026, 7, 14), 23, 0), 100), (utc(day, 12, 0), 200)],
+            is_valid,
+        );
+
+        assert_eq!(heatmap.values[0][0], Some(100));
+        assert_eq!(heatmap.values[0][26], Some(200));
+    }
+
+    #[test]
+    fn test_clocks_going_forward() {
+        let day = date(2026, 3, 29);
+
+        let readings: Vec<_> = (0..46)
+            .map(|i| (utc(day, 0, 0) + Duration::minutes(30 * i), 10))
+            .collect();
+
+        let heatmap = consumption_heatmap(day, date(2026, 3, 30), &readings, is_valid);
+
+        assert_eq!(heatmap.slot_counts, vec![46]);
+        assert_eq!(heatmap.values[0][1], Some(10));
+        // 01:00 to 02:00 doesn't exist
+        assert_eq!(heatmap.values[0][2], None);
+        assert_eq!(heatmap.values[0][3], None);
+        assert_eq!(heatmap.values[0][4], Some(10));
+        assert_eq!(heatmap.values[0].iter().flatten().count(), 46);
+    }
+
+    #[test]
+    fn test_clocks_going_back() {
+        let day = date(2026, 10, 25);
+
+        // Midnight in London is 23:00 UTC the day before
+        let readings: Vec<_> = (0..50)
+            .map(|i| {
+                (
+                    utc(date(2026, 10, 24), 23, 0) + Duration::minutes(30 * i),
+                    10,
+                )
+            })
+            .collect();
+
+        let heatmap = consumption_heatmap(day, date(2026, 10, 26), &readings, is_valid);
+
+        assert_eq!(heatmap.slot_counts, vec![50]);
+        assert_eq!(heatmap.values[0][1], Some(10));
+        // 01:00 to 02:00 happens twice
+        assert_eq!(heatmap.values[0][2], Some(20));
+        assert_eq!(heatmap.values[0][3], Some(20));
+        assert_eq!(heatmap.values[0][4], Some(10));
+        assert_eq!(heatmap.values[0].iter().flatten().sum::<i64>(), 500);
+    }
+}
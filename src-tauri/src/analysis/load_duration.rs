@@ -0,0 +1,164 @@
+use serde::Serialize;
+
+pub const DEFAULT_LOAD_DURATION_POINTS: usize = 101;
+
+const HOURS_PER_READING: f64 = 0.5;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadDurationPoint {
+    /// The percentage of the time that demand is at or above `kw`
+    pub percent_of_time: f64,
+    pub kw: f64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HoursAboveThreshold {
+    pub threshold_kw: f64,
+    pub hours: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadDurationCurve {
+    pub points: Vec<LoadDurationPoint>,
+    pub reading_count: usize,
+    pub peak_kw: Option<f64>,
+    pub average_kw: Option<f64>,
+    /// The average demand as a fraction of the peak demand
+    pub load_factor: Option<f64>,
+    pub hours_above: Vec<HoursAboveThreshold>,
+}
+
+/// Builds a load duration curve from half-hourly readings in Wh, downsampled to at most
+/// `point_count` points evenly spaced in time from the peak to the lowest demand.
+pub fn load_duration_curve(
+    readings_wh: &[i64],
+    point_count: usize,
+    thresholds_kw: &[f64],
+) -> LoadDurationCurve {
+    // The energy used in half an hour in Wh is half the average power in W
+    let mut demand_kw: Vec<f64> = readings_wh
+        .iter()
+        .map(|wh| *wh as f64 * 2.0 / 1000.0)
+        .collect();
+
+    demand_kw.sort_by(|a, b| b.total_cmp(a));
+
+    let points = downsample(&demand_kw, point_count);
+
+    let peak_kw = demand_kw.first().copied();
+
+    let average_kw = if demand_kw.is_empty() {
+        None
+    } else {
+        Some(demand_kw.iter().sum::<f64>() / demand_kw.len() as f64)
+    };
+
+    let load_factor = average_kw
+        .zip(peak_kw)
+        .filter(|(_, peak)| *peak > 0.0)
+        .map(|(average, peak)| average / peak);
+
+    let hours_above = thresholds_kw
+        .iter()
+        .map(|threshold_kw| HoursAboveThreshold {
+            threshold_kw: *threshold_kw,
+            hours: demand_kw.iter().filter(|kw| *kw > threshold_kw).count() as f64
+                * HOURS_PER_READING,
+        })
+        .collect();
+
+    LoadDurationCurve {
+        points,
+        reading_count: demand_kw.len(),
+        peak_kw,
+        average_kw,
+        load_factor,
+        hours_above,
+    }
+}
+
+fn downsample(descending_kw: &[f64], point_count: usize) -> Vec<LoadDurationPoint> {
+    match descending_kw.len() {
+        0 => vec![],
+        1 => vec![LoadDurationPoint {
+            percent_of_time: 100.0,
+            kw: descending_kw[0],
+        }],
+        len => {
+            let point_count = point_count.clamp(2, len);
+            let last_index = (len - 1) as f64;
+
+            (0..point_count)
+                .map(|i| {
+                    let fraction = i as f64 / (point_count - 1) as f64;
+                    let index = (fraction * last_index).round() as usize;
+
+                    LoadDurationPoint {
+                        percent_of_time: (index + 1) as f64 / len as f64 * 100.0,
+                        kw: descending_kw[index],
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_duration_curve() {
+        // 1, 2, 3 and 4 kW for half an hour each
+        let curve = load_duration_curve(&[500, 2000, 1000, 1500], 10, &[1.5, 5.0]);
+
+        assert_eq!(curve.reading_count, 4);
+        assert_eq!(curve.peak_kw, Some(4.0));
+        assert_eq!(curve.average_kw, Some(2.5));
+        assert_eq!(curve.load_factor, Some(0.625));
+        assert_eq!(
+            curve.points.iter().map(|p| p.kw).collect::<Vec<_>>(),
+            vec![4.0, 3.0, 2.0, 1.0]
+        );
+        assert_eq!(curve.points[0].percent_of_time, 25.0);
+        assert_eq!(curve.points[3].percent_of_time, 100.0);
+        assert_eq!(
+            curve.hours_above,
+            vec![
+                HoursAboveThreshold {
+                    threshold_kw: 1.5,
+                    hours: 1.5
+                },
+                HoursAboveThreshold {
+                    threshold_kw: 5.0,
+                    hours: 0.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_duration_curve_is_downsampled() {
+        let readings: Vec<i64> = (1..=1000).collect();
+
+        let curve = load_duration_curve(&readings, 11, &[]);
+
+        assert_eq!(curve.points.len(), 11);
+        assert_eq!(curve.points[0].kw, 2.0);
+        assert_eq!(curve.points[10].kw, 0.002);
+        assert_eq!(curve.points[10].percent_of_time, 100.0);
+    }
+
+    #[test]
+    fn test_load_duration_curve_without_readings() {
+        let curve = load_duration_curve(&[], 10, &[1.0]);
+
+        assert!(curve.points.is_empty());
+        assert_eq!(curve.peak_kw, None);
+        assert_eq!(curve.load_factor, None);
+        assert_eq!(curve.hours_above[0].hours, 0.0);
+    }
+}
@@ -0,0 +1,94 @@
+pub mod anomaly;
+pub mod baseload;
+pub mod comparison;
+pub mod degree_days;
+pub mod heatmap;
+pub mod load_duration;
+pub mod projection;
+
+/// Returns the value at the given percentile (0.0 to 1.0) of values that are already sorted in
+/// ascending order, using the nearest-rank method.
+pub fn percentile(sorted_values: &[f64], percentile: f64) -> Option<f64> {
+    if sorted_values.is_empty() {
+        return None;
+    }
+
+    let rank = (percentile.clamp(0.0, 1.0) * sorted_values.len() as f64).ceil() as usize;
+
+    Some(sorted_values[rank.max(1) - 1])
+}
+
+/// Returns the median of some values, in any order.
+pub fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted_values = values.to_vec();
+    sorted_values.sort_by(f64::total_cmp);
+
+    percentile(&sorted_values, 0.5)
+}
+
+/// Fits a straight line through some points by least squares, returning the slope and
+/// intercept. Returns `None` if there are fewer than two distinct x values.
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let count = points.len() as f64;
+
+    if points.len() < 2 {
+        return None;
+    }
+
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    if variance == 0.0 {
+        return None;
+    }
+
+    let slope = covariance / variance;
+
+    Some((slope, mean_y - slope * mean_x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let values = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+
+        assert_eq!(percentile(&values, 0.1), Some(1.0));
+        assert_eq!(percentile(&values, 0.5), Some(5.0));
+        assert_eq!(percentile(&values, 0.9), Some(9.0));
+        assert_eq!(percentile(&values, 1.0), Some(10.0));
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+    }
+
+    #[test]
+    fn test_percentile_of_nothing() {
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+
+    #[test]
+    fn test_median_of_unsorted_values() {
+        assert_eq!(median(&[5.0, 1.0, 3.0]), Some(3.0));
+    }
+
+    #[test]
+    fn test_linear_fit() {
+        let (slope, intercept) = linear_fit(&[(0.0, 1.0), (1.0, 3.0), (2.0, 5.0)]).unwrap();
+
+        assert!((slope - 2.0).abs() < 1e-9);
+        assert!((intercept - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_linear_fit_needs_distinct_x_values() {
+        assert_eq!(linear_fit(&[(1.0, 1.0)]), None);
+        assert_eq!(linear_fit(&[(1.0, 1.0), (1.0, 2.0)]), None);
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, Months, NaiveDate};
+use serde::Serialize;
+
+/// The number of days before today whose average is used as the recent daily consumption.
+const RECENT_DAYS: i64 = 14;
+
+/// The z-score for the 90% confidence band around the day-to-day variation.
+const CONFIDENCE_Z_SCORE: f64 = 1.645;
+
+/// The range for adjusting last year's consumption by how this period compares so far, so that a
+/// few unusual days early in a period don't distort the projection.
+const MIN_YEAR_ON_YEAR_RATIO: f64 = 0.5;
+const MAX_YEAR_ON_YEAR_RATIO: f64 = 2.0;
+
+/// The fewest days of a period needed before this period is compared with last year.
+const MIN_DAYS_FOR_YEAR_ON_YEAR: i64 = 7;
+
+/// The tariff used to cost the remainder of a period.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActiveTariff {
+    pub standing_charge_pence: f64,
+    pub unit_price_pence: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodProjection {
+    pub period_start: NaiveDate,
+    /// The first day after the period
+    pub period_end: NaiveDate,
+    pub days_elapsed: i64,
+    /// Includes today, as its consumption is not yet complete
+    pub days_remaining: i64,
+    /// Days before today with no consumption yet, e.g. while the DCC catches up, which are
+    /// projected like the days remaining
+    pub days_without_data: i64,
+    pub actual_kwh: f64,
+    pub recent_daily_average_kwh: Option<f64>,
+    pub last_year_kwh: Option<f64>,
+    pub projected_kwh: f64,
+    pub low_kwh: f64,
+    pub high_kwh: f64,
+    pub actual_cost_pence: Option<f64>,
+    pub projected_cost_pence: Option<f64>,
+    pub low_cost_pence: Option<f64>,
+    pub high_cost_pence: Option<f64>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumptionProjection {
+    pub month: PeriodProjection,
+    pub year: PeriodProjection,
+}
+
+pub fn month_bounds(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = today.with_day(1).expect("The first of the month to exist");
+
+    (start, start + Months::new(1))
+}
+
+pub fn year_bounds(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let start = NaiveDate::from_ymd_opt(today.year(), 1, 1).expect("January 1st to exist");
+
+    (start, start + Months::new(12))
+}
+
+fn one_year_earlier(date: NaiveDate) -> NaiveDate {
+    // Clamps February 29th to the 28th
+    date - Months::new(12)
+}
+
+fn days_between(start: NaiveDate, end: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    start.iter_days().take_while(move |date| *date < end)
+}
+
+fn total_kwh(daily_wh: &BTreeMap<NaiveDate, i64>, start: NaiveDate, end: NaiveDate) -> f64 {
+    daily_wh
+        .range(start..end)
+        .map(|(_, wh)| *wh as f64)
+        .sum::<f64>()
+        / 1000.0
+}
+
+/// The total for a range of days, provided that there is data for most of them.
+fn total_kwh_if_complete(
+    daily_wh: &BTreeMap<NaiveDate, i64>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Option<f64> {
+    let day_count = days_between(start, end).count();
+    let days_with_data = daily_wh.range(start..end).count();
+
+    if day_count == 0 || (days_with_data as f64) < 0.8 * day_count as f64 {
+        return None;
+    }
+
+    // Scale up for the few missing days
+    Some(total_kwh(daily_wh, start, end) * day_count as f64 / days_with_data as f64)
+}
+
+/// Projects the consumption and cost for the period from `period_start` up to `period_end`,
+/// from daily consumption in Wh. Days before `today` with no consumption are projected like
+/// the days remaining, as the DCC can be a few days behind.
+///
+/// The daily consumption for the rest of the period is estimated from the recent daily average
+/// and from the same days last year, adjusted for how this period compares with last year so
+/// far. The band spans both estimates, widened by the recent day-to-day variation.
+pub fn project_period(
+    daily_wh: &BTreeMap<NaiveDate, i64>,
+    today: NaiveDate,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    actual_cost_pence: Option<f64>,
+    tariff: Option<ActiveTariff>,
+) -> PeriodProjection {
+    let days_elapsed = (today - period_start).num_days().max(0);
+    let days_remaining = (period_end - today).num_days().max(0);
+
+    let actual_kwh = total_kwh(daily_wh, period_start, today);
+    let days_with_data = daily_wh.range(period_start..today).count() as i64;
+    let days_without_data = days_elapsed - days_with_data;
+
+    let recent_kwh: Vec<f64> = daily_wh
+        .range(today - Duration::days(RECENT_DAYS)..today)
+        .map(|(_, wh)| *wh as f64 / 1000.0)
+        .collect();
+
+    let recent_daily_average_kwh = if recent_kwh.is_empty() {
+        None
+    } else {
+        Some(recent_kwh.iter().sum::<f64>() / recent_kwh.len() as f64)
+    };
+
+    let recent_standard_deviation_kwh = recent_daily_average_kwh
+        .filter(|_| recent_kwh.len() > 1)
+        .map(|mean| {
+            let variance = recent_kwh.iter().map(|x| (x - mean).powi(2)).sum::<f64>()
+                / (recent_kwh.len() - 1) as f64;
+
+            variance.sqrt()
+        })
+        .unwrap_or(0.0);
+
+    let last_year_kwh = total_kwh_if_complete(
+        daily_wh,
+        one_year_earlier(period_start),
+        one_year_earlier(period_end),
+    );
+
+    let year_on_year_ratio = if days_with_data >= MIN_DAYS_FOR_YEAR_ON_YEAR {
+        total_kwh_if_complete(
+            daily_wh,
+            one_year_earlier(period_start),
+            one_year_earlier(today),
+        )
+        .filter(|kwh| *kwh > 0.0)
+        .map(|last_year_to_date_kwh| {
+            // Scaled up for the days without data, as the total last year is for every day
+            let actual_to_date_kwh = actual_kwh * days_elapsed as f64 / days_with_data as f64;
+
+            (actual_to_date_kwh / last_year_to_date_kwh)
+                .clamp(MIN_YEAR_ON_YEAR_RATIO, MAX_YEAR_ON_YEAR_RATIO)
+        })
+    } else {
+        None
+    };
+
+    let last_year_daily_kwh = total_kwh_if_complete(
+        daily_wh,
+        one_year_earlier(today),
+        one_year_earlier(period_end),
+    )
+    .filter(|_| days_remaining > 0)
+    .map(|kwh| kwh / days_remaining as f64 * year_on_year_ratio.unwrap_or(1.0));
+
+    let mut daily_estimates: Vec<f64> = [recent_daily_average_kwh, last_year_daily_kwh]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if daily_estimates.is_empty() && days_with_data > 0 {
+        daily_estimates.push(actual_kwh / days_with_data as f64);
+    }
+
+    let remaining_days = (days_remaining + days_without_data) as f64;
+
+    let (projected_remaining_kwh, low_remaining_kwh, high_remaining_kwh) =
+        if daily_estimates.is_empty() {
+            (0.0, 0.0, 0.0)
+        } else {
+            let central = daily_estimates.iter().sum::<f64>() / daily_estimates.len() as f64;
+            let lowest = daily_estimates
+                .iter()
+                .copied()
+                .fold(f64::INFINITY, f64::min);
+            let highest = daily_estimates
+                .iter()
+                .copied()
+                .fold(f64::NEG_INFINITY, f64::max);
+
+            let spread = CONFIDENCE_Z_SCORE * recent_standard_deviation_kwh * remaining_days.sqrt();
+
+            (
+                central * remaining_days,
+                (lowest * remaining_days - spread).max(0.0),
+                highest * remaining_days + spread,
+            )
+        };
+
+    let remaining_cost_pence = |remaining_kwh: f64| {
+        actual_cost_pence.zip(tariff).map(|(actual, tariff)| {
+            actual
+                + tariff.standing_charge_pence * remaining_days
+                + tariff.unit_price_pence * remaining_kwh
+        })
+    };
+
+    PeriodProjection {
+        period_start,
+        period_end,
+        days_elapsed,
+        days_remaining,
+        days_without_data,
+        actual_kwh,
+        recent_daily_average_kwh,
+        last_year_kwh,
+        projected_kwh: actual_kwh + projected_remaining_kwh,
+        low_kwh: actual_kwh + low_remaining_kwh,
+        high_kwh: actual_kwh + high_remaining_kwh,
+        actual_cost_pence,
+        projected_cost_pence: remaining_cost_pence(projected_remaining_kwh),
+        low_cost_pence: remaining_cost_pence(low_remaining_kwh),
+        high_cost_pence: remaining_cost_pence(high_remaining_kwh),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn constant_daily_wh(start: NaiveDate, end: NaiveDate, wh: i64) -> BTreeMap<NaiveDate, i64> {
+        days_between(start, end).map(|day| (day, wh)).collect()
+    }
+
+    #[test]
+    fn test_month_and_year_bounds() {
+        assert_eq!(
+            month_bounds(date(2026, 12, 15)),
+            (date(2026, 12, 1), date(2027, 1, 1))
+        );
+        assert_eq!(
+            year_bounds(date(2026, 10, 19)),
+            (date(2026, 1, 1), date(2027, 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_steady_consumption_projects_exactly() {
+        let today = date(2026, 10, 11);
+        let (start, end) = month_bounds(today);
+
+        let daily_wh = constant_daily_wh(date(2025, 9, 1), today, 10_000);
+
+        let tariff = ActiveTariff {
+            standing_charge_pence: 50.0,
+            unit_price_pence: 25.0,
+        };
+
+        let projection = project_period(&daily_wh, today, start, end, Some(2_750.0), Some(tariff));
+
+        assert_eq!(projection.days_elapsed, 10);
+        assert_eq!(projection.days_remaining, 21);
+        assert_eq!(projection.actual_kwh, 100.0);
+        assert_eq!(projection.last_year_kwh, Some(310.0));
+        assert!((projection.projected_kwh - 310.0).abs() < 1e-9);
+        assert!((projection.low_kwh - 310.0).abs() < 1e-9);
+        assert!((projection.high_kwh - 310.0).abs() < 1e-9);
+        assert!((projection.projected_cost_pence.unwrap() - 9_050.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_band_spans_recent_and_last_year() {
+        let today = date(2026, 10, 11);
+        let (start, end) = month_bounds(today);
+
+        // Half as much last October, but this month has been adjusted for by the ratio so far
+        let mut daily_wh = constant_daily_wh(date(2025, 9, 1), date(2025, 10, 1), 10_000);
+        daily_wh.extend(constant_daily_wh(
+            date(2025, 10, 1),
+            date(2025, 11, 1),
+            5_000,
+        ));
+        daily_wh.extend(constant_daily_wh(
+            date(2025, 11, 1),
+            date(2026, 10, 4),
+            10_000,
+        ));
+        // A higher recent week
+        daily_wh.extend(constant_daily_wh(date(2026, 10, 4), today, 20_000));
+
+        let projection = project_period(&daily_wh, today, start, end, None, None);
+
+        assert!(projection.low_kwh < projection.projected_kwh);
+        assert!(projection.projected_kwh < projection.high_kwh);
+        assert_eq!(projection.projected_cost_pence, None);
+    }
+
+    #[test]
+    fn test_without_history_uses_period_average() {
+        let today = date(2026, 10, 25);
+        let (start, end) = month_bounds(today);
+
+        // Ten days at the start of the month, then nothing in the last two weeks
+        let daily_wh = constant_daily_wh(start, today - Duration::days(RECENT_DAYS), 10_000);
+
+        let projection = project_period(&daily_wh, today, start, end, None, None);
+
+        assert_eq!(projection.recent_daily_average_kwh, None);
+        assert_eq!(projection.last_year_kwh, None);
+        assert_eq!(projection.actual_kwh, 100.0);
+        assert_eq!(projection.days_without_data, 14);
+        assert_eq!(projection.days_remaining, 7);
+        // The days without data and the days remaining at 10 kWh a day
+        assert!((projection.projected_kwh - 310.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_days_without_data_are_projected() {
+        let today = date(2026, 10, 11);
+        let (start, end) = month_bounds(today);
+
+        // The last three days haven't been delivered yet
+        let daily_wh = constant_daily_wh(date(2025, 9, 1), today - Duration::days(3), 10_000);
+
+        let tariff = ActiveTariff {
+            standing_charge_pence: 50.0,
+            unit_price_pence: 25.0,
+        };
+
+        let projection = project_period(&daily_wh, today, start, end, Some(2_100.0), Some(tariff));
+
+        assert_eq!(projection.actual_kwh, 70.0);
+        assert_eq!(projection.days_without_data, 3);
+        assert!((projection.projected_kwh - 310.0).abs() < 1e-9);
+        assert!((projection.low_kwh - 310.0).abs() < 1e-9);
+        assert!((projection.high_kwh - 310.0).abs() < 1e-9);
+        assert!((projection.projected_cost_pence.unwrap() - 9_300.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,389 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::NaiveDate;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tauri::{async_runtime, AppHandle, Manager};
+use tokio::net::TcpListener;
+
+use crate::{
+    commands::{
+        app::{get_app_status, get_app_version, get_startup_error, StatusResponse},
+        electricity::{
+            get_daily_electricity_consumption, get_electricity_cost_history,
+            get_electricity_tariff_history, get_monthly_electricity_consumption,
+            get_raw_electricity_consumption,
+        },
+        gas::{
+            get_daily_gas_consumption, get_gas_cost_history, get_gas_tariff_history,
+            get_monthly_gas_consumption, get_raw_gas_consumption,
+        },
+        profiles::get_energy_profiles,
+        ApiError,
+    },
+    data::{energy_profile::EnergyProfile, Fuel},
+    metrics::load_stored_metrics,
+    startup::StartupError,
+    utils::{get_or_create_api_token, ApiServerSettings},
+    AppError, AppState,
+};
+
+pub const DEFAULT_API_SERVER_PORT: u16 = 8787;
+
+#[derive(Clone)]
+struct ServerState {
+    app_handle: AppHandle,
+    token: String,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+enum Granularity {
+    Raw,
+    Daily,
+    Monthly,
+}
+
+#[derive(Deserialize)]
+struct DateRange {
+    start: String,
+    end: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerStatus {
+    version: String,
+    #[serde(flatten)]
+    status: StatusResponse,
+    energy_profiles: Vec<EnergyProfile>,
+    startup_error: Option<StartupError>,
+}
+
+/// Stops the API server if it's running, then starts it again if it's enabled, e.g. after its
+/// settings or token have changed.
+pub async fn restart_api_server(app_handle: &AppHandle) -> Result<(), AppError> {
+    let app_state = app_handle.state::<AppState>();
+
+    let running = app_state
+        .api_server
+        .lock()
+        .map_err(|_| AppError::MutexPoisonedError {
+            name: "api_server".into(),
+        })?
+        .take();
+
+    if let Some(task) = running {
+        task.abort();
+        // Waits for the listener to be dropped, so that the port can be bound again
+        let _ = task.await;
+
+        info!("Stopped the API server");
+    }
+
+    let settings = {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| AppError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        ApiServerSettings::from_app_settings(&app_settings)?
+    };
+
+    if !settings.enabled && !settings.metrics_enabled {
+        return Ok(());
+    }
+
+    let token = get_or_create_api_token()?;
+
+    // Only ever bound to localhost, as the token is sent in the clear
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, settings.port));
+
+    let listener = TcpListener::bind(address).await.map_err(|e| {
+        AppError::CustomError(format!(
+            "Failed to start the API server on {}: {}",
+            address, e
+        ))
+    })?;
+
+    let router = router(
+        ServerState {
+            app_handle: app_handle.clone(),
+            token,
+        },
+        &settings,
+    );
+
+    let task = async_runtime::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("API server stopped: {}", e);
+        }
+    });
+
+    *app_state
+        .api_server
+        .lock()
+        .map_err(|_| AppError::MutexPoisonedError {
+            name: "api_server".into(),
+        })? = Some(task);
+
+    info!("API server listening on http://{}", address);
+
+    Ok(())
+}
+
+fn router(state: ServerState, settings: &ApiServerSettings) -> Router {
+    let mut router = Router::new();
+
+    if settings.enabled {
+        router = router
+            .route("/api/status", get(status))
+            .route("/api/{fuel}/consumption/{granularity}", get(consumption))
+            .route("/api/{fuel}/tariffs", get(tariffs))
+            .route("/api/{fuel}/costs", get(costs));
+    }
+
+    if settings.metrics_enabled {
+        router = router.route("/metrics", get(metrics));
+    }
+
+    router
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state)
+}
+
+async fn authorize(State(state): State<ServerState>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token);
+
+    match token {
+        Some(token) if tokens_match(token, &state.token) => next.run(request).await,
+        _ => (StatusCode::UNAUTHORIZED, "A valid API token is required").into_response(),
+    }
+}
+
+/// Extracts the token from an `Authorization: Bearer <token>` header.
+fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+
+    match scheme.eq_ignore_ascii_case("bearer") {
+        true => Some(token.trim()),
+        false => None,
+    }
+}
+
+/// Compares every byte, so the time taken doesn't reveal how much of the token was right.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Validates the dates, which the commands otherwise assume are well formed, and returns them
+/// as the ISO strings the commands take.
+fn parse_date_range(range: &DateRange) -> Result<(String, String), Response> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Dates must be YYYY-MM-DD: {}", e),
+            )
+                .into_response()
+        })
+    };
+
+    let start = parse(&range.start)?;
+    let end = parse(&range.end)?;
+
+    if start >= end {
+        return Err((StatusCode::BAD_REQUEST, "start must be before end").into_response());
+    }
+
+    Ok((start.to_string(), end.to_string()))
+}
+
+fn respond<T: Serialize>(result: Result<T, ApiError>) -> Response {
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => {
+            error!("API request failed: {}", e);
+
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn status(State(state): State<ServerState>) -> Response {
+    let app_state = state.app_handle.state::<AppState>();
+
+    let result = get_app_status(app_state.clone()).and_then(|status| {
+        Ok(ServerStatus {
+            version: get_app_version(),
+            status,
+            energy_profiles: get_energy_profiles(app_state.clone())?,
+            startup_error: get_startup_error(app_state),
+        })
+    });
+
+    respond(result)
+}
+
+async fn consumption(
+    State(state): State<ServerState>,
+    Path((fuel, granularity)): Path<(Fuel, Granularity)>,
+    Query(range): Query<DateRange>,
+) -> Response {
+    let (start, end) = match parse_date_range(&range) {
+        Ok(range) => range,
+        Err(response) => return response,
+    };
+
+    let app_state = state.app_handle.state::<AppState>();
+
+    match (fuel, granularity) {
+        (Fuel::Electricity, Granularity::Raw) => {
+            respond(get_raw_electricity_consumption(app_state, start, end).await)
+        }
+        (Fuel::Electricity, Granularity::Daily) => {
+            respond(get_daily_electricity_consumption(app_state, start, end).await)
+        }
+        (Fuel::Electricity, Granularity::Monthly) => {
+            respond(get_monthly_electricity_consumption(app_state, start, end).await)
+        }
+        (Fuel::Gas, Granularity::Raw) => {
+            respond(get_raw_gas_consumption(app_state, start, end).await)
+        }
+        (Fuel::Gas, Granularity::Daily) => {
+            respond(get_daily_gas_consumption(app_state, start, end).await)
+        }
+        (Fuel::Gas, Granularity::Monthly) => {
+            respond(get_monthly_gas_consumption(app_state, start, end).await)
+        }
+    }
+}
+
+async fn tariffs(State(state): State<ServerState>, Path(fuel): Path<Fuel>) -> Response {
+    let app_state = state.app_handle.state::<AppState>();
+
+    match fuel {
+        Fuel::Electricity => respond(get_electricity_tariff_history(app_state).await),
+        Fuel::Gas => respond(get_gas_tariff_history(app_state).await),
+    }
+}
+
+async fn costs(
+    State(state): State<ServerState>,
+    Path(fuel): Path<Fuel>,
+    Query(range): Query<DateRange>,
+) -> Response {
+    let (start, end) = match parse_date_range(&range) {
+        Ok(range) => range,
+        Err(response) => return response,
+    };
+
+    let app_state = state.app_handle.state::<AppState>();
+
+    match fuel {
+        Fuel::Electricity => respond(get_electricity_cost_history(app_state, start, end).await),
+        Fuel::Gas => respond(get_gas_cost_history(app_state, start, end).await),
+    }
+}
+
+async fn metrics(State(state): State<ServerState>) -> Response {
+    let app_state = state.app_handle.state::<AppState>();
+
+    let connection_pool = app_state.db_pool.clone();
+
+    let stored = match tokio::task::spawn_blocking(move || load_stored_metrics(connection_pool))
+        .await
+        .map_err(AppError::from)
+        .and_then(|result| result)
+    {
+        Ok(stored) => stored,
+        Err(e) => return respond::<()>(Err(e.into())),
+    };
+
+    let body = match app_state.metrics.lock() {
+        Ok(metrics) => metrics.render(&stored),
+        Err(_) => {
+            return respond::<()>(Err(ApiError::MutexPoisonedError {
+                name: "metrics".into(),
+            }))
+        }
+    };
+
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer abc123"), Some("abc123"));
+        assert_eq!(bearer_token("bearer  abc123 "), Some("abc123"));
+        assert_eq!(bearer_token("Basic abc123"), None);
+        assert_eq!(bearer_token("abc123"), None);
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc124", "abc123"));
+        assert!(!tokens_match("abc12", "abc123"));
+        assert!(!tokens_match("", "abc123"));
+    }
+
+    #[test]
+    fn test_parse_date_range() {
+        let range = DateRange {
+            start: "2025-01-01".into(),
+            end: "2025-02-01".into(),
+        };
+
+        assert_eq!(
+            parse_date_range(&range).unwrap(),
+            ("2025-01-01".to_string(), "2025-02-01".to_string())
+        );
+
+        // The commands slice the first ten characters, so short dates must be rejected first
+        let short = DateRange {
+            start: "2025".into(),
+            end: "2025-02-01".into(),
+        };
+
+        assert!(parse_date_range(&short).is_err());
+
+        let backwards = DateRange {
+            start: "2025-02-01".into(),
+            end: "2025-01-01".into(),
+        };
+
+        assert!(parse_date_range(&backwards).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+use std::{
+    ffi::{CStr, CString},
+    fs,
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use chrono::Utc;
+use diesel::connection::SimpleConnection;
+use diesel::migration::MigrationSource;
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Text};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
+use libsqlite3_sys as ffi;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{db::MIGRATIONS, AppError};
+
+/// Identifies our backups, in the application id field of the SQLite header ("SEEX").
+pub const BACKUP_APPLICATION_ID: i32 = 0x5345_4558;
+
+/// The prefix of automatic backups, which are rotated. Backups made by the user are never
+/// deleted.
+const AUTOMATIC_BACKUP_PREFIX: &str = "auto-";
+
+/// How many automatic backups are kept by default.
+pub const DEFAULT_AUTOMATIC_BACKUP_COUNT: u32 = 5;
+
+/// How long the online backup waits before retrying when another connection holds a lock, and
+/// how many times it retries.
+const BACKUP_RETRY_MILLISECONDS: i32 = 100;
+const BACKUP_RETRIES: u32 = 50;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub path: String,
+    /// The number of migrations applied to the database
+    pub schema_version: i32,
+}
+
+#[derive(QueryableByName)]
+struct BackupHeader {
+    #[diesel(sql_type = Integer)]
+    application_id: i32,
+    #[diesel(sql_type = Integer)]
+    user_version: i32,
+}
+
+#[derive(QueryableByName)]
+struct DatabaseFile {
+    #[diesel(sql_type = Text)]
+    file: String,
+}
+
+#[derive(QueryableByName)]
+struct QuickCheck {
+    #[diesel(sql_type = Text)]
+    quick_check: String,
+}
+
+fn database_error(action: &str, e: impl std::fmt::Display) -> AppError {
+    AppError::CustomError(format!("Failed to {}: {}", action, e))
+}
+
+fn open(path: &Path) -> Result<SqliteConnection, AppError> {
+    SqliteConnection::establish(&path.to_string_lossy())
+        .map_err(|e| database_error(&format!("open {}", path.display()), e))
+}
+
+/// A connection opened directly with SQLite, for the online backup API that Diesel doesn't
+/// expose. Closed when dropped.
+struct RawConnection(*mut ffi::sqlite3);
+
+impl RawConnection {
+    fn open(path: &Path, flags: i32) -> Result<Self, AppError> {
+        let c_path = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|e| database_error(&format!("open {}", path.display()), e))?;
+
+        let mut handle = ptr::null_mut();
+        let result =
+            unsafe { ffi::sqlite3_open_v2(c_path.as_ptr(), &mut handle, flags, ptr::null()) };
+        let connection = RawConnection(handle);
+
+        if result != ffi::SQLITE_OK {
+            return Err(database_error(
+                &format!("open {}", path.display()),
+                connection.error_message(),
+            ));
+        }
+
+        Ok(connection)
+    }
+
+    fn error_message(&self) -> String {
+        if self.0.is_null() {
+            return "out of memory".to_string();
+        }
+
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) }
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sqlite3_close(self.0);
+        }
+    }
+}
+
+/// Copies the main database of `source` to `destination` with SQLite's online backup API, which
+/// reads a consistent snapshot while other connections keep writing.
+fn online_backup(source: &Path, destination: &Path) -> Result<(), AppError> {
+    let source_connection = RawConnection::open(source, ffi::SQLITE_OPEN_READONLY)?;
+    let destination_connection = RawConnection::open(
+        destination,
+        ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+    )?;
+
+    let main = c"main";
+
+    let backup = unsafe {
+        ffi::sqlite3_backup_init(
+            destination_connection.0,
+            main.as_ptr(),
+            source_connection.0,
+            main.as_ptr(),
+        )
+    };
+
+    if backup.is_null() {
+        return Err(database_error(
+            "back up the database",
+            destination_connection.error_message(),
+        ));
+    }
+
+    let mut retries = 0;
+
+    let result = loop {
+        // Copies every page in one step, so the snapshot can't be restarted by a later write
+        match unsafe { ffi::sqlite3_backup_step(backup, -1) } {
+            ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED if retries < BACKUP_RETRIES => {
+                retries += 1;
+
+                unsafe {
+                    ffi::sqlite3_sleep(BACKUP_RETRY_MILLISECONDS);
+                }
+            }
+            result => break result,
+        }
+    };
+
+    unsafe {
+        ffi::sqlite3_backup_finish(backup);
+    }
+
+    if result != ffi::SQLITE_DONE {
+        return Err(database_error(
+            "back up the database",
+            destination_connection.error_message(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The path a restored database is staged at, until it replaces the database on the next start.
+pub fn restore_staging_path(db_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.restore", db_path.display()))
+}
+
+/// The directory automatic backups are written to, next to the database.
+pub fn automatic_backup_directory(db_path: &Path) -> PathBuf {
+    db_path.with_file_name("backups")
+}
+
+fn schema_version(conn: &mut SqliteConnection) -> Result<i32, AppError> {
+    let applied = conn
+        .applied_migrations()
+        .map_err(|e| database_error("read the applied migrations", e))?;
+
+    Ok(applied.len() as i32)
+}
+
+/// Writes the header fields that identify a backup and its schema version, and switches the
+/// file out of write-ahead logging so that it's self-contained.
+fn write_header(conn: &mut SqliteConnection, schema_version: i32) -> Result<(), AppError> {
+    conn.batch_execute(&format!(
+        r#"
+            PRAGMA application_id = {BACKUP_APPLICATION_ID};
+            PRAGMA user_version = {schema_version};
+            PRAGMA journal_mode = DELETE;
+        "#
+    ))
+    .map_err(|e| database_error("write the backup header", e))
+}
+
+/// Copies the database to `destination`, replacing any file already there. The online backup
+/// takes a consistent snapshot, so readings can keep being written while it runs.
+pub fn backup_database(
+    conn: &mut SqliteConnection,
+    destination: &Path,
+) -> Result<BackupInfo, AppError> {
+    let schema_version = schema_version(conn)?;
+
+    // Written alongside, and only moved into place once complete
+    let partial_path = PathBuf::from(format!("{}.partial", destination.display()));
+
+    if partial_path.exists() {
+        fs::remove_file(&partial_path).map_err(|e| database_error("remove a partial backup", e))?;
+    }
+
+    let source = sql_query("SELECT file FROM pragma_database_list WHERE name = 'main'")
+        .get_result::<DatabaseFile>(conn)
+        .map_err(|e| database_error("find the database file", e))?;
+
+    online_backup(Path::new(&source.file), &partial_path)?;
+
+    {
+        let mut backup_conn = open(&partial_path)?;
+        write_header(&mut backup_conn, schema_version)?;
+    }
+
+    fs::rename(&partial_path, destination)
+        .map_err(|e| database_error(&format!("write {}", destination.display()), e))?;
+
+    info!(
+        "Backed up the database, at schema version {}, to {}",
+        schema_version,
+        destination.display()
+    );
+
+    Ok(BackupInfo {
+        path: destination.to_string_lossy().to_string(),
+        schema_version,
+    })
+}
+
+/// Checks that a file is an intact database of ours, from this or an older version of the app.
+fn validate_backup(conn: &mut SqliteConnection) -> Result<(), AppError> {
+    let quick_check = sql_query("SELECT quick_check FROM pragma_quick_check()")
+        .load::<QuickCheck>(conn)
+        .map_err(|e| database_error("read the backup", e))?;
+
+    if quick_check.len() != 1 || quick_check[0].quick_check != "ok" {
+        return Err(AppError::CustomError(
+            "The backup is corrupt and can't be restored".into(),
+        ));
+    }
+
+    let header = sql_query(
+        r#"
+            SELECT
+                (SELECT application_id FROM pragma_application_id()) AS application_id,
+                (SELECT user_version FROM pragma_user_version()) AS user_version
+        "#,
+    )
+    .get_result::<BackupHeader>(conn)
+    .map_err(|e| database_error("read the backup header", e))?;
+
+    // A copy of db.sqlite made by hand has no header
+    if header.application_id != BACKUP_APPLICATION_ID && header.application_id != 0 {
+        return Err(AppError::CustomError(
+            "The file isn't a Smart Energy Explorer backup".into(),
+        ));
+    }
+
+    let known_versions: Vec<String> =
+        <EmbeddedMigrations as MigrationSource<Sqlite>>::migrations(&MIGRATIONS)
+            .map_err(|e| database_error("load the migrations", e))?
+            .iter()
+            .map(|migration| migration.name().version().to_string())
+            .collect();
+
+    let applied_versions = conn.applied_migrations().map_err(|_| {
+        AppError::CustomError("The file isn't a Smart Energy Explorer backup".into())
+    })?;
+
+    if applied_versions.is_empty() {
+        return Err(AppError::CustomError("The backup is empty".into()));
+    }
+
+    if let Some(unknown) = applied_versions
+        .iter()
+        .find(|version| !known_versions.contains(&version.to_string()))
+    {
+        return Err(AppError::CustomError(format!(
+            "The backup is from a newer version of the app, with migration {}",
+            unknown
+        )));
+    }
+
+    if header.user_version > applied_versions.len() as i32 {
+        warn!(
+            "Backup header has schema version {} but {} migrations are applied",
+            header.user_version,
+            applied_versions.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Copies a backup to the staging path next to the database, validates it and migrates it to
+/// the current schema. The staged database replaces the current one the next time the app
+/// starts, before any connections are opened.
+pub fn stage_restore(source: &Path, db_path: &Path) -> Result<BackupInfo, AppError> {
+    stage(source, db_path, true)
+}
+
+/// Stages the snapshot taken before the migrations failed, as it was. The migrations run again
+/// when the app next starts.
+pub fn stage_snapshot_restore(snapshot: &Path, db_path: &Path) -> Result<BackupInfo, AppError> {
+    stage(snapshot, db_path, false)
+}
+
+fn stage(source: &Path, db_path: &Path, migrate: bool) -> Result<BackupInfo, AppError> {
+    let staging_path = restore_staging_path(db_path);
+
+    fs::copy(source, &staging_path)
+        .map_err(|e| database_error(&format!("copy {}", source.display()), e))?;
+
+    let staged = (|| {
+        let mut conn = open(&staging_path)?;
+
+        validate_backup(&mut conn)?;
+
+        if migrate {
+            conn.run_pending_migrations(MIGRATIONS)
+                .map_err(|e| database_error("migrate the backup", e))?;
+        }
+
+        let schema_version = schema_version(&mut conn)?;
+        write_header(&mut conn, schema_version)?;
+
+        Ok(BackupInfo {
+            path: source.to_string_lossy().to_string(),
+            schema_version,
+        })
+    })();
+
+    if staged.is_err() {
+        let _ = fs::remove_file(&staging_path);
+    }
+
+    staged
+}
+
+/// Replaces the database with a staged restore, if there is one. Call this before opening the
+/// database.
+pub fn apply_staged_restore(db_path: &Path) -> Result<bool, AppError> {
+    let staging_path = restore_staging_path(db_path);
+
+    if !staging_path.exists() {
+        return Ok(false);
+    }
+
+    // Empties the old write-ahead log into the database being replaced, so that if the app stops
+    // after the rename, nothing from the log can be applied to the restored database
+    if db_path.exists() {
+        open(db_path)?
+            .batch_execute("PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| database_error("checkpoint the database", e))?;
+    }
+
+    // Renaming within a directory is atomic, so the database is either the old or the restored one
+    fs::rename(&staging_path, db_path).map_err(|e| database_error("restore the database", e))?;
+
+    // The empty write-ahead log belonged to the database that was replaced
+    for suffix in ["-wal", "-shm"] {
+        let path = PathBuf::from(format!("{}{}", db_path.display(), suffix));
+
+        if path.exists() {
+            fs::remove_file(&path)
+                .map_err(|e| database_error(&format!("remove {}", path.display()), e))?;
+        }
+    }
+
+    info!("Restored the database from a backup");
+
+    Ok(true)
+}
+
+/// Writes an automatic backup before an operation named by `reason`, then deletes the oldest
+/// automatic backups so that only `keep` remain.
+pub fn create_automatic_backup(
+    conn: &mut SqliteConnection,
+    backup_directory: &Path,
+    reason: &str,
+    keep: u32,
+) -> Result<BackupInfo, AppError> {
+    fs::create_dir_all(backup_directory)
+        .map_err(|e| database_error("create the backup directory", e))?;
+
+    // The timestamp comes first, so the names sort oldest first
+    let file_name = format!(
+        "{}{}-{}.sqlite",
+        AUTOMATIC_BACKUP_PREFIX,
+        Utc::now().format("%Y%m%dT%H%M%S%3fZ"),
+        reason
+    );
+
+    let backup = backup_database(conn, &backup_directory.join(file_name))?;
+
+    rotate_automatic_backups(backup_directory, keep)?;
+
+    Ok(backup)
+}
+
+fn rotate_automatic_backups(backup_directory: &Path, keep: u32) -> Result<(), AppError> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_directory)
+        .map_err(|e| database_error("list the backups", e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| {
+                    name.starts_with(AUTOMATIC_BACKUP_PREFIX) && name.ends_with(".sqlite")
+                })
+        })
+        .collect();
+
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep as usize);
+
+    for path in backups.iter().take(excess) {
+        info!("Deleting old automatic backup {}", path.display());
+
+        fs::remove_file(path).map_err(|e| database_error("delete an old backup", e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDatabase;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "smart-energy-explorer-{}-{}",
+            name,
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
+
+        sql_query(
+            "INSERT INTO electricity_consumption (timestamp, energy_consumption_wh) VALUES ('2026-10-19 10:00:00', 123)",
+        )
+        .execute(&mut *conn)
+        .unwrap();
+
+        let backup_path = temp_path("backup.sqlite");
+        let backup = backup_database(&mut conn, &backup_path).unwrap();
+
+        assert!(backup.schema_version > 0);
+
+        let restored_path = temp_path("restored.sqlite");
+        let staged = stage_restore(&backup_path, &restored_path).unwrap();
+
+        assert_eq!(staged.schema_version, backup.schema_version);
+        assert!(apply_staged_restore(&restored_path).unwrap());
+        assert!(!apply_staged_restore(&restored_path).unwrap());
+
+        let count: i64 = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "(SELECT COUNT(*) FROM electricity_consumption)",
+        ))
+        .get_result(&mut open(&restored_path).unwrap())
+        .unwrap();
+
+        assert_eq!(count, 1);
+
+        fs::remove_file(&backup_path).unwrap();
+        fs::remove_file(&restored_path).unwrap();
+    }
+
+    #[test]
+    fn test_restore_rejects_a_file_that_isnt_a_database() {
+        let source = temp_path("not-a-database.sqlite");
+        fs::write(&source, "not a database".repeat(100)).unwrap();
+
+        let db_path = temp_path("db.sqlite");
+
+        assert!(stage_restore(&source, &db_path).is_err());
+        assert!(!restore_staging_path(&db_path).exists());
+
+        fs::remove_file(&source).unwrap();
+    }
+
+    #[test]
+    fn test_automatic_backups_are_rotated() {
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
+
+        let backup_directory = temp_path("backups");
+
+        for _ in 0..4 {
+            create_automatic_backup(&mut conn, &backup_directory, "test", 2).unwrap();
+        }
+
+        assert_eq!(fs::read_dir(&backup_directory).unwrap().count(), 2);
+
+        fs::remove_dir_all(&backup_directory).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use smart_energy_explorer_lib::{analysis::anomaly::DEFAULT_ANOMALY_Z_SCORE, error::AppError};
+
+/// The app's identifier, which names its data directory.
+const APP_IDENTIFIER: &str = "io.github.rars.smart-energy-explorer";
+
+/// The settings file the app writes next to its database.
+const APP_SETTINGS_FILE: &str = "app_settings.bin";
+
+const DATABASE_ENV: &str = "SMART_ENERGY_DATABASE";
+const GLOWMARKT_USERNAME_ENV: &str = "SMART_ENERGY_GLOWMARKT_USERNAME";
+const GLOWMARKT_PASSWORD_ENV: &str = "SMART_ENERGY_GLOWMARKT_PASSWORD";
+
+/// A JSON config file. Every field is optional, and environment variables take precedence.
+#[derive(Deserialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigFile {
+    pub database: Option<PathBuf>,
+    pub glowmarkt_username: Option<String>,
+    pub glowmarkt_password: Option<String>,
+    pub anomaly_z_score: Option<f64>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            AppError::CustomError(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+
+        serde_json::from_str(&contents).map_err(|e| {
+            AppError::CustomError(format!("Failed to parse {}: {}", path.display(), e))
+        })
+    }
+}
+
+/// The subset of the app's settings that affect what's stored, so that the command line tool
+/// stores readings the same way when it shares the app's database.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AppSettingsFile {
+    anomaly_z_score: Option<f64>,
+}
+
+pub struct GlowmarktCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+pub struct Config {
+    pub database: PathBuf,
+    pub glowmarkt_credentials: Option<GlowmarktCredentials>,
+    pub anomaly_z_score: f64,
+}
+
+impl Config {
+    /// Resolves each setting from, in order: the command line, the environment, the config file,
+    /// the app's settings next to the database, and then the app's defaults.
+    pub fn resolve(
+        database: Option<PathBuf>,
+        config_file: Option<&Path>,
+    ) -> Result<Self, AppError> {
+        let file = config_file
+            .map(ConfigFile::load)
+            .transpose()?
+            .unwrap_or_default();
+
+        let database = database
+            .or_else(|| env::var_os(DATABASE_ENV).map(PathBuf::from))
+            .or(file.database)
+            .or_else(default_database_path)
+            .ok_or_else(|| {
+                AppError::CustomError(format!(
+                    "No database given. Use --database or set {}",
+                    DATABASE_ENV
+                ))
+            })?;
+
+        let app_settings = load_app_settings(&database);
+
+        let username = env::var(GLOWMARKT_USERNAME_ENV)
+            .ok()
+            .or(file.glowmarkt_username);
+        let password = env::var(GLOWMARKT_PASSWORD_ENV)
+            .ok()
+            .or(file.glowmarkt_password);
+
+        let glowmarkt_credentials = match (username, password) {
+            (Some(username), Some(password)) => Some(GlowmarktCredentials { username, password }),
+            _ => None,
+        };
+
+        Ok(Config {
+            database,
+            glowmarkt_credentials,
+            anomaly_z_score: file
+                .anomaly_z_score
+                .or(app_settings.anomaly_z_score)
+                .unwrap_or(DEFAULT_ANOMALY_Z_SCORE),
+        })
+    }
+}
+
+/// Reads the app's settings, if it has been run with this database. They're only used as
+/// defaults, so a missing or unreadable file is ignored.
+fn load_app_settings(database: &Path) -> AppSettingsFile {
+    fs::read_to_string(database.with_file_name(APP_SETTINGS_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// The database the app uses, in its data directory.
+fn default_database_path() -> Option<PathBuf> {
+    let data_directory = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    }?;
+
+    Some(data_directory.join(APP_IDENTIFIER).join("db.sqlite"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file_fields_are_optional() {
+        let file: ConfigFile = serde_json::from_str(
+            r#"{ "database": "/srv/energy/db.sqlite", "glowmarktUsername": "user" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            file,
+            ConfigFile {
+                database: Some(PathBuf::from("/srv/energy/db.sqlite")),
+                glowmarkt_username: Some("user".into()),
+                ..ConfigFile::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_app_settings_are_read_from_next_to_the_database() {
+        let directory = env::temp_dir().join(format!(
+            "smart-energy-explorer-cli-{}",
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+
+        fs::write(
+            directory.join(APP_SETTINGS_FILE),
+            r#"{ "anomalyZScore": 2.5, "termsAccepted": true }"#,
+        )
+        .unwrap();
+
+        let settings = load_app_settings(&directory.join("db.sqlite"));
+
+        assert_eq!(settings.anomaly_z_score, Some(2.5));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
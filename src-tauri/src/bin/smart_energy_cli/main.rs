@@ -0,0 +1,545 @@
+//! A command line tool for syncing and querying the app's database without a desktop session,
+//! e.g. on a home server. It uses the app's library for downloads, data and clients, but doesn't
+//! start Tauri.
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::{Parser, Subcommand, ValueEnum};
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Nullable, Timestamp};
+use log::{error, info};
+use serde::Serialize;
+
+use crate::config::Config;
+use smart_energy_explorer_lib::{
+    backup::{automatic_backup_directory, create_automatic_backup, DEFAULT_AUTOMATIC_BACKUP_COUNT},
+    clients::glowmarkt::GlowmarktDataProvider,
+    data::{
+        consumption::{
+            ConsumptionRepository, SqliteElectricityConsumptionRepository,
+            SqliteGasConsumptionRepository,
+        },
+        energy_profile::{EnergyProfileRepository, SqliteEnergyProfileRepository},
+        tariff::{SqliteElectricityTariffRepository, SqliteGasTariffRepository, TariffRepository},
+        Fuel, RepositoryError,
+    },
+    dates::{reporting_timezone, set_reporting_timezone, DEFAULT_REPORTING_TIMEZONE},
+    db::{
+        self, load_reporting_timezone, populate_missing_london_date_ids, store_reporting_timezone,
+        SqliteConnectionPool,
+    },
+    download::{download_new_data, DownloadProgress},
+    error::AppError,
+    export::{load_rows, load_tariff_timeline, write_export, ExportFormat, ExportGranularity},
+};
+
+mod config;
+
+/// Syncs and queries Smart Energy Explorer's database.
+///
+/// Glowmarkt credentials are read from SMART_ENERGY_GLOWMARKT_USERNAME and
+/// SMART_ENERGY_GLOWMARKT_PASSWORD, or from the config file.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// The database, by default the app's own
+    #[arg(long, global = true)]
+    database: Option<PathBuf>,
+
+    /// A JSON config file with database, glowmarktUsername, glowmarktPassword and anomalyZScore
+    /// fields
+    #[arg(long, global = true, env = "SMART_ENERGY_CONFIG")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Downloads consumption and tariffs since the last sync
+    Sync,
+    /// Shows when each fuel was last synced and how many readings are stored
+    Status,
+    /// Prints stored consumption as JSON
+    Query {
+        fuel: FuelArg,
+        granularity: QueryGranularity,
+        /// The first date, inclusive
+        #[arg(long)]
+        start: NaiveDate,
+        /// The last date, exclusive
+        #[arg(long)]
+        end: NaiveDate,
+    },
+    /// Exports consumption and costs to a file
+    Export {
+        /// The fuels to export, by default both
+        #[arg(long, value_delimiter = ',')]
+        fuel: Vec<FuelArg>,
+        #[arg(long, default_value = "daily")]
+        granularity: QueryGranularity,
+        #[arg(long, default_value = "csv")]
+        format: FormatArg,
+        /// The first date, inclusive
+        #[arg(long)]
+        start: NaiveDate,
+        /// The last date, exclusive
+        #[arg(long)]
+        end: NaiveDate,
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Prints the standing charge and unit price history as JSON
+    Tariffs { fuel: FuelArg },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FuelArg {
+    Electricity,
+    Gas,
+}
+
+impl From<FuelArg> for Fuel {
+    fn from(fuel: FuelArg) -> Self {
+        match fuel {
+            FuelArg::Electricity => Fuel::Electricity,
+            FuelArg::Gas => Fuel::Gas,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum QueryGranularity {
+    Raw,
+    Daily,
+    Monthly,
+}
+
+impl From<QueryGranularity> for ExportGranularity {
+    fn from(granularity: QueryGranularity) -> Self {
+        match granularity {
+            QueryGranularity::Raw => ExportGranularity::Raw,
+            QueryGranularity::Daily => ExportGranularity::Daily,
+            QueryGranularity::Monthly => ExportGranularity::Monthly,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+impl From<FormatArg> for ExportFormat {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Csv => ExportFormat::Csv,
+            FormatArg::JsonLines => ExportFormat::JsonLines,
+            FormatArg::Parquet => ExportFormat::Parquet,
+        }
+    }
+}
+
+/// A reading, day or month of consumption, shaped as the app's commands return them.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConsumptionRow {
+    timestamp: String,
+    value: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_provisional: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StandingCharge {
+    #[serde(serialize_with = "smart_energy_explorer_lib::serde_utils::serialize_naive_as_utc")]
+    start_date: NaiveDateTime,
+    standing_charge_pence: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UnitPrice {
+    #[serde(serialize_with = "smart_energy_explorer_lib::serde_utils::serialize_naive_as_utc")]
+    price_effective_time: NaiveDateTime,
+    unit_price_pence: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TariffHistory {
+    standing_charges: Vec<StandingCharge>,
+    unit_prices: Vec<UnitPrice>,
+}
+
+#[derive(QueryableByName)]
+struct ReadingSummary {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    latest: Option<NaiveDateTime>,
+}
+
+/// Logs download progress in steps of a quarter, rather than after every request.
+struct LogProgress;
+
+impl DownloadProgress for LogProgress {
+    fn update(&self, percentage: f64, download_name: &str) -> Result<(), AppError> {
+        if percentage.round() as u32 % 25 == 0 {
+            info!("Downloading {}: {:.0}%", download_name, percentage);
+        }
+
+        Ok(())
+    }
+
+    fn finished(&self, fuel: Fuel, duration: Duration, succeeded: bool) {
+        if succeeded {
+            info!("Synced {} in {:.1}s", fuel, duration.as_secs_f64());
+        }
+    }
+}
+
+fn repository_error(e: RepositoryError) -> AppError {
+    AppError::CustomError(e.to_string())
+}
+
+/// Opens the database and brings it up to date, backing it up first if it needs migrating, as
+/// the app does when it starts.
+fn open_database(database: &Path) -> Result<SqliteConnectionPool, AppError> {
+    let database_url = database.to_string_lossy().to_string();
+    let is_new = !database.exists();
+
+    let connection_pool = db::create_connection_pool(&database_url)
+        .map_err(|e| AppError::CustomError(format!("Failed to open {}: {}", database_url, e)))?;
+
+    let mut conn = connection_pool
+        .get()
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    if db::has_pending_migrations(&mut conn) {
+        create_automatic_backup(
+            &mut conn,
+            &automatic_backup_directory(database),
+            "migration",
+            DEFAULT_AUTOMATIC_BACKUP_COUNT,
+        )?;
+    }
+
+    db::run_migrations(&mut conn)?;
+
+    // Date ids are stored in the database's reporting timezone, which only the app can work out
+    // for a database from before it was recorded, so nothing is written without it
+    let timezone = match load_reporting_timezone(&mut conn)? {
+        Some(timezone) => timezone,
+        None if is_new => {
+            store_reporting_timezone(&mut conn, DEFAULT_REPORTING_TIMEZONE)
+                .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+            DEFAULT_REPORTING_TIMEZONE
+        }
+        None => {
+            return Err(AppError::CustomError(format!(
+                "{} has no reporting timezone yet. Open it in Smart Energy Explorer first",
+                database_url
+            )))
+        }
+    };
+
+    set_reporting_timezone(timezone);
+    populate_missing_london_date_ids(&mut conn).map_err(repository_error)?;
+
+    Ok(connection_pool)
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| AppError::CustomError(format!("Failed to serialize output: {}", e)))?;
+
+    println!("{}", json);
+
+    Ok(())
+}
+
+async fn sync(config: &Config, connection_pool: SqliteConnectionPool) -> Result<(), AppError> {
+    let Some(credentials) = &config.glowmarkt_credentials else {
+        return Err(AppError::CustomError(
+            "No Glowmarkt credentials. Set SMART_ENERGY_GLOWMARKT_USERNAME and SMART_ENERGY_GLOWMARKT_PASSWORD".into(),
+        ));
+    };
+
+    let data_provider =
+        GlowmarktDataProvider::new(&credentials.username, &credentials.password).await?;
+
+    download_new_data(
+        connection_pool,
+        Arc::new(data_provider),
+        &LogProgress,
+        config.anomaly_z_score,
+    )
+    .await?;
+
+    info!("Sync complete");
+
+    Ok(())
+}
+
+fn status(config: &Config, connection_pool: SqliteConnectionPool) -> Result<(), AppError> {
+    println!("Database: {}", config.database.display());
+    println!("Timezone: {}", reporting_timezone());
+
+    let profiles = SqliteEnergyProfileRepository::new(connection_pool.clone())
+        .get_all_energy_profiles()
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    let mut conn = connection_pool
+        .get()
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    for fuel in Fuel::ALL {
+        let summary = sql_query(format!(
+            "SELECT COUNT(*) AS count, MAX(timestamp) AS latest FROM {} WHERE is_provisional = 0",
+            fuel.consumption_table()
+        ))
+        .get_result::<ReadingSummary>(&mut conn)
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+        let last_synced = profiles
+            .iter()
+            .find(|profile| profile.name == fuel.as_str())
+            .map(
+                |profile| match (profile.is_active, profile.last_date_retrieved) {
+                    (false, _) => "inactive".to_string(),
+                    (true, Some(date)) => date.date().to_string(),
+                    (true, None) => "never".to_string(),
+                },
+            )
+            .unwrap_or_else(|| "never".to_string());
+
+        println!(
+            "{}: last synced {}, {} readings, latest {}",
+            fuel,
+            last_synced,
+            summary.count,
+            summary
+                .latest
+                .map(|latest| latest.and_utc().to_rfc3339())
+                .unwrap_or_else(|| "none".to_string())
+        );
+    }
+
+    Ok(())
+}
+
+fn query(
+    connection_pool: SqliteConnectionPool,
+    fuel: Fuel,
+    granularity: QueryGranularity,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<(), AppError> {
+    let rows: Vec<ConsumptionRow> = match (fuel, granularity) {
+        (Fuel::Electricity, QueryGranularity::Raw) => {
+            SqliteElectricityConsumptionRepository::new(connection_pool)
+                .get_raw(start, end)
+                .map_err(repository_error)?
+                .into_iter()
+                .map(|x| ConsumptionRow {
+                    timestamp: x.timestamp.and_utc().to_rfc3339(),
+                    value: x.energy_consumption_wh,
+                    is_provisional: Some(x.is_provisional),
+                })
+                .collect()
+        }
+        (Fuel::Gas, QueryGranularity::Raw) => SqliteGasConsumptionRepository::new(connection_pool)
+            .get_raw(start, end)
+            .map_err(repository_error)?
+            .into_iter()
+            .map(|x| ConsumptionRow {
+                timestamp: x.timestamp.and_utc().to_rfc3339(),
+                value: x.energy_consumption_wh,
+                is_provisional: Some(x.is_provisional),
+            })
+            .collect(),
+        (fuel, QueryGranularity::Daily) => {
+            let daily = match fuel {
+                Fuel::Electricity => SqliteElectricityConsumptionRepository::new(connection_pool)
+                    .get_daily(start, end),
+                Fuel::Gas => {
+                    SqliteGasConsumptionRepository::new(connection_pool).get_daily(start, end)
+                }
+            }
+            .map_err(repository_error)?;
+
+            daily
+                .into_iter()
+                .map(|(date, value, is_provisional)| ConsumptionRow {
+                    timestamp: date.to_string(),
+                    value,
+                    is_provisional: Some(is_provisional),
+                })
+                .collect()
+        }
+        (fuel, QueryGranularity::Monthly) => {
+            let monthly = match fuel {
+                Fuel::Electricity => SqliteElectricityConsumptionRepository::new(connection_pool)
+                    .get_monthly(start, end),
+                Fuel::Gas => {
+                    SqliteGasConsumptionRepository::new(connection_pool).get_monthly(start, end)
+                }
+            }
+            .map_err(repository_error)?;
+
+            monthly
+                .into_iter()
+                .map(|(date, value)| ConsumptionRow {
+                    timestamp: date.to_string(),
+                    value,
+                    is_provisional: None,
+                })
+                .collect()
+        }
+    };
+
+    print_json(&rows)
+}
+
+fn export(
+    connection_pool: SqliteConnectionPool,
+    fuels: Vec<Fuel>,
+    granularity: ExportGranularity,
+    format: ExportFormat,
+    start: NaiveDate,
+    end: NaiveDate,
+    output: PathBuf,
+) -> Result<(), AppError> {
+    let mut rows = vec![];
+
+    for fuel in fuels {
+        let tariffs =
+            load_tariff_timeline(connection_pool.clone(), fuel).map_err(repository_error)?;
+
+        rows.extend(
+            load_rows(
+                connection_pool.clone(),
+                fuel,
+                start,
+                end,
+                granularity,
+                &tariffs,
+            )
+            .map_err(repository_error)?,
+        );
+    }
+
+    write_export(&output, format, &rows)?;
+
+    info!("Exported {} rows to {}", rows.len(), output.display());
+
+    Ok(())
+}
+
+fn tariffs(connection_pool: SqliteConnectionPool, fuel: Fuel) -> Result<(), AppError> {
+    let (standing_charges, unit_prices) = match fuel {
+        Fuel::Electricity => {
+            let repository = SqliteElectricityTariffRepository::new(connection_pool);
+
+            (
+                repository.get_standing_charge_history(),
+                repository.get_unit_price_history(),
+            )
+        }
+        Fuel::Gas => {
+            let repository = SqliteGasTariffRepository::new(connection_pool);
+
+            (
+                repository.get_standing_charge_history(),
+                repository.get_unit_price_history(),
+            )
+        }
+    };
+
+    print_json(&TariffHistory {
+        standing_charges: standing_charges
+            .map_err(repository_error)?
+            .into_iter()
+            .map(|x| StandingCharge {
+                start_date: x.start_date,
+                standing_charge_pence: x.standing_charge_pence,
+            })
+            .collect(),
+        unit_prices: unit_prices
+            .map_err(repository_error)?
+            .into_iter()
+            .map(|x| UnitPrice {
+                price_effective_time: x.price_effective_time,
+                unit_price_pence: x.unit_price_pence,
+            })
+            .collect(),
+    })
+}
+
+async fn run(cli: Cli) -> Result<(), AppError> {
+    let config = Config::resolve(cli.database, cli.config.as_deref())?;
+
+    let database = config.database.clone();
+    let connection_pool = tokio::task::spawn_blocking(move || open_database(&database)).await??;
+
+    match cli.command {
+        Command::Sync => sync(&config, connection_pool).await,
+        Command::Status => status(&config, connection_pool),
+        Command::Query {
+            fuel,
+            granularity,
+            start,
+            end,
+        } => query(connection_pool, fuel.into(), granularity, start, end),
+        Command::Export {
+            fuel,
+            granularity,
+            format,
+            start,
+            end,
+            output,
+        } => {
+            let fuels = match fuel.is_empty() {
+                true => Fuel::ALL.to_vec(),
+                false => fuel.into_iter().map(Fuel::from).collect(),
+            };
+
+            export(
+                connection_pool,
+                fuels,
+                granularity.into(),
+                format.into(),
+                start,
+                end,
+                output,
+            )
+        }
+        Command::Tariffs { fuel } => tariffs(connection_pool, fuel.into()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
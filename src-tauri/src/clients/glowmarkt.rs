@@ -1,6 +1,6 @@
 use rust_decimal::{prelude::FromPrimitive, Decimal};
 use std::sync::Arc;
-use tauri::async_runtime::Mutex;
+use tokio::sync::Mutex;
 
 use chrono::{Datelike, NaiveDate, NaiveDateTime};
 use glowmarkt::{ErrorKind, GlowmarktApi, ReadingPeriod};
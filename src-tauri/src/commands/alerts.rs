@@ -0,0 +1,102 @@
+use log::debug;
+use tauri::State;
+
+use crate::{
+    data::alert::{
+        AlertRepository, AlertRule, NewAlertRule, SqliteAlertRepository, TriggeredAlertRecord,
+    },
+    dates::london_midnight_as_utc,
+    utils::parse_iso_string_to_naive_date,
+    AppState, MqttMessage,
+};
+
+use super::ApiError;
+
+#[tauri::command]
+pub async fn get_alert_rules(app_state: State<'_, AppState>) -> Result<Vec<AlertRule>, ApiError> {
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let rules = tokio::task::spawn_blocking(move || {
+        SqliteAlertRepository::new(connection_pool_clone).get_rules()
+    })
+    .await??;
+
+    Ok(rules)
+}
+
+#[tauri::command]
+pub async fn store_alert_rule(
+    app_state: State<'_, AppState>,
+    alert_rule_id: Option<i32>,
+    rule: NewAlertRule,
+) -> Result<AlertRule, ApiError> {
+    debug!("store_alert_rule({:?}, {:?}) called", alert_rule_id, rule);
+
+    if rule.threshold_watts <= 0.0 || rule.duration_seconds < 0 || rule.hysteresis_watts < 0.0 {
+        return Err(ApiError::Custom(
+            "Alert rules need a positive threshold, and a non-negative duration and hysteresis"
+                .into(),
+        ));
+    }
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let stored_rule = tokio::task::spawn_blocking(move || {
+        let repository = SqliteAlertRepository::new(connection_pool_clone);
+
+        match alert_rule_id {
+            Some(id) => repository.update_rule(id, rule),
+            None => repository.insert_rule(rule),
+        }
+    })
+    .await??;
+
+    notify_alert_rules_updated(&app_state).await?;
+
+    Ok(stored_rule)
+}
+
+#[tauri::command]
+pub async fn delete_alert_rule(
+    app_state: State<'_, AppState>,
+    alert_rule_id: i32,
+) -> Result<(), ApiError> {
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        SqliteAlertRepository::new(connection_pool_clone).delete_rule(alert_rule_id)
+    })
+    .await??;
+
+    notify_alert_rules_updated(&app_state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_triggered_alerts(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<TriggeredAlertRecord>, ApiError> {
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let alerts = tokio::task::spawn_blocking(move || {
+        SqliteAlertRepository::new(connection_pool_clone)
+            .get_triggered_alerts(london_midnight_as_utc(&start), london_midnight_as_utc(&end))
+    })
+    .await??;
+
+    Ok(alerts)
+}
+
+async fn notify_alert_rules_updated(app_state: &AppState) -> Result<(), ApiError> {
+    app_state
+        .mqtt_message_sender
+        .send(MqttMessage::AlertRulesUpdated)
+        .await
+        .map_err(|e| ApiError::Custom(e.to_string()))
+}
@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+
+use chrono::{Months, NaiveDate, NaiveDateTime};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{
+    analysis::{
+        anomaly::detect_and_store_anomalies,
+        baseload::{estimate_daily_baseload, summarise_baseload, BaseloadEstimate},
+        comparison::{
+            compare_periods, offset_range, ComparisonAlignment, ComparisonGranularity,
+            ComparisonOffset, ComparisonPeriod, PeriodComparison,
+        },
+        heatmap::{consumption_heatmap, ConsumptionHeatmap},
+        load_duration::{load_duration_curve, LoadDurationCurve, DEFAULT_LOAD_DURATION_POINTS},
+        projection::{
+            month_bounds, project_period, year_bounds, ActiveTariff, ConsumptionProjection,
+        },
+    },
+    data::{
+        anomaly::{AnomalyRepository, SqliteAnomalyRepository},
+        consumption::{
+            ConsumptionRepository, SqliteElectricityConsumptionRepository,
+            SqliteGasConsumptionRepository, ENERGY_CONSUMPTION_WH_ERROR_CODE,
+        },
+        tariff::{SqliteElectricityTariffRepository, TariffRepository},
+        Fuel, RepositoryError,
+    },
+    dates::{london_date_id_to_naive_date, london_today},
+    db::SqliteConnectionPool,
+    utils::{parse_iso_string_to_naive_date, AnomalySettings},
+    AppState,
+};
+
+use super::{
+    electricity::{get_electricity_cost_history, get_electricity_tariff_history},
+    gas::{get_gas_cost_history, get_gas_tariff_history},
+    tariff::{DailyCost, TariffHistoryResponse},
+    ApiError,
+};
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumptionAnomaly {
+    pub fuel: String,
+    pub granularity: String,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub period_start: NaiveDateTime,
+    pub date: NaiveDate,
+    pub expected_kwh: f64,
+    pub actual_kwh: f64,
+    pub z_score: f64,
+}
+
+#[tauri::command]
+pub async fn get_electricity_baseload(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<BaseloadEstimate, ApiError> {
+    debug!(
+        "get_electricity_baseload({}, {}) called",
+        start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let (consumption, unit_prices) =
+        tokio::task::spawn_blocking(move || -> Result<_, RepositoryError> {
+            let consumption =
+                SqliteElectricityConsumptionRepository::new(connection_pool_clone.clone())
+                    .get_raw(start, end)?;
+
+            let unit_prices = SqliteElectricityTariffRepository::new(connection_pool_clone)
+                .get_unit_price_history()?;
+
+            Ok((consumption, unit_prices))
+        })
+        .await??;
+
+    let readings: Vec<_> = consumption
+        .iter()
+        .filter(|x| x.energy_consumption_wh != ENERGY_CONSUMPTION_WH_ERROR_CODE)
+        .map(|x| (x.timestamp, x.energy_consumption_wh))
+        .collect();
+
+    // The unit price history is ordered, so the last entry is the current price
+    let unit_price_pence = unit_prices.last().map(|x| x.unit_price_pence);
+
+    Ok(summarise_baseload(
+        estimate_daily_baseload(&readings),
+        unit_price_pence,
+    ))
+}
+
+#[tauri::command]
+pub async fn get_electricity_load_duration_curve(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    points: Option<usize>,
+    thresholds_kw: Option<Vec<f64>>,
+) -> Result<LoadDurationCurve, ApiError> {
+    debug!(
+        "get_electricity_load_duration_curve({}, {}, {:?}, {:?}) called",
+        start_date, end_date, points, thresholds_kw
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let consumption = tokio::task::spawn_blocking(move || {
+        SqliteElectricityConsumptionRepository::new(connection_pool_clone).get_raw(start, end)
+    })
+    .await??;
+
+    let readings_wh: Vec<i64> = consumption
+        .iter()
+        .map(|x| x.energy_consumption_wh)
+        .filter(|wh| *wh != ENERGY_CONSUMPTION_WH_ERROR_CODE)
+        .collect();
+
+    Ok(load_duration_curve(
+        &readings_wh,
+        points.unwrap_or(DEFAULT_LOAD_DURATION_POINTS),
+        &thresholds_kw.unwrap_or_default(),
+    ))
+}
+
+#[tauri::command]
+pub async fn get_electricity_consumption_projection(
+    app_state: State<'_, AppState>,
+) -> Result<ConsumptionProjection, ApiError> {
+    debug!("get_electricity_consumption_projection called");
+
+    let today = london_today();
+    let (year_start, year_end) = year_bounds(today);
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    // Include last year, to compare with the same periods
+    let daily_consumption = tokio::task::spawn_blocking(move || {
+        SqliteElectricityConsumptionRepository::new(connection_pool_clone)
+            .get_daily(year_start - Months::new(12), year_end)
+    })
+    .await??;
+
+    let daily_costs =
+        get_electricity_cost_history(app_state.clone(), year_start.to_string(), today.to_string())
+            .await?;
+
+    let tariff_history = get_electricity_tariff_history(app_state.clone()).await?;
+
+    Ok(project_consumption(
+        today,
+        &daily_consumption,
+        &daily_costs,
+        &tariff_history,
+    ))
+}
+
+#[tauri::command]
+pub async fn get_gas_consumption_projection(
+    app_state: State<'_, AppState>,
+) -> Result<ConsumptionProjection, ApiError> {
+    debug!("get_gas_consumption_projection called");
+
+    let today = london_today();
+    let (year_start, year_end) = year_bounds(today);
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    // Include last year, to compare with the same periods
+    let daily_consumption = tokio::task::spawn_blocking(move || {
+        SqliteGasConsumptionRepository::new(connection_pool_clone)
+            .get_daily(year_start - Months::new(12), year_end)
+    })
+    .await??;
+
+    let daily_costs =
+        get_gas_cost_history(app_state.clone(), year_start.to_string(), today.to_string()).await?;
+
+    let tariff_history = get_gas_tariff_history(app_state.clone()).await?;
+
+    Ok(project_consumption(
+        today,
+        &daily_consumption,
+        &daily_costs,
+        &tariff_history,
+    ))
+}
+
+/// Projects the current month and year, costing the remaining days at the latest tariff.
+fn project_consumption(
+    today: NaiveDate,
+    daily_consumption: &[(NaiveDate, i64, bool)],
+    daily_costs: &[DailyCost],
+    tariff_history: &TariffHistoryResponse,
+) -> ConsumptionProjection {
+    let daily_wh: BTreeMap<NaiveDate, i64> = daily_consumption
+        .iter()
+        .map(|(date, wh, _)| (*date, *wh))
+        .collect();
+
+    let tariff = tariff_history
+        .standing_charges
+        .last()
+        .zip(tariff_history.unit_prices.last())
+        .map(|(standing_charge, unit_price)| ActiveTariff {
+            standing_charge_pence: standing_charge.standing_charge_pence,
+            unit_price_pence: unit_price.unit_price_pence,
+        });
+
+    let project = |(start, end): (NaiveDate, NaiveDate)| {
+        let costs: Vec<f64> = daily_costs
+            .iter()
+            .filter(|cost| cost.date >= start && cost.date < today)
+            .map(|cost| cost.cost_pence)
+            .collect();
+
+        let actual_cost_pence = if costs.is_empty() && today > start {
+            None
+        } else {
+            Some(costs.iter().sum())
+        };
+
+        project_period(&daily_wh, today, start, end, actual_cost_pence, tariff)
+    };
+
+    ConsumptionProjection {
+        month: project(month_bounds(today)),
+        year: project(year_bounds(today)),
+    }
+}
+
+#[tauri::command]
+pub fn get_anomaly_settings(app_state: State<'_, AppState>) -> Result<AnomalySettings, ApiError> {
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(AnomalySettings::from_app_settings(&app_settings)?)
+}
+
+#[tauri::command]
+pub fn store_anomaly_settings(
+    app_state: State<'_, AppState>,
+    settings: AnomalySettings,
+) -> Result<(), ApiError> {
+    if settings.z_score.is_nan() || settings.z_score <= 0.0 {
+        return Err(ApiError::Custom(
+            "The anomaly z-score must be greater than zero".into(),
+        ));
+    }
+
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(settings.save(&app_settings)?)
+}
+
+/// Detects anomalies again for a range of days, e.g. after changing the z-score. Returns the
+/// number found.
+#[tauri::command]
+pub async fn detect_anomalies(
+    app_state: State<'_, AppState>,
+    fuel: Fuel,
+    start_date: String,
+    end_date: String,
+) -> Result<usize, ApiError> {
+    debug!(
+        "detect_anomalies({}, {}, {}) called",
+        fuel, start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let z_score = get_anomaly_settings(app_state.clone())?.z_score;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let anomaly_count = tokio::task::spawn_blocking(move || {
+        detect_and_store_anomalies(connection_pool_clone, fuel, start, end, z_score)
+    })
+    .await??;
+
+    Ok(anomaly_count)
+}
+
+#[tauri::command]
+pub async fn get_anomalies(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    fuel: Option<Fuel>,
+) -> Result<Vec<ConsumptionAnomaly>, ApiError> {
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let anomalies = tokio::task::spawn_blocking(move || {
+        SqliteAnomalyRepository::new(connection_pool_clone).get_anomalies(fuel, start, end)
+    })
+    .await??;
+
+    Ok(anomalies
+        .into_iter()
+        .map(|x| ConsumptionAnomaly {
+            fuel: x.fuel,
+            granularity: x.granularity,
+            period_start: x.period_start,
+            date: london_date_id_to_naive_date(x.london_date_id),
+            expected_kwh: x.expected_wh / 1000.0,
+            actual_kwh: x.actual_wh as f64 / 1000.0,
+            z_score: x.z_score,
+        })
+        .collect())
+}
+
+/// What to compare a date range with: either a second date range, which defaults to the same
+/// length, or an earlier period given by an offset.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonOptions {
+    pub comparison_start_date: Option<String>,
+    pub comparison_end_date: Option<String>,
+    pub offset: Option<ComparisonOffset>,
+    pub alignment: Option<ComparisonAlignment>,
+    pub granularity: Option<ComparisonGranularity>,
+}
+
+/// Compares consumption and cost over a date range with another period. Monthly buckets always
+/// align by calendar month.
+#[tauri::command]
+pub async fn compare_consumption(
+    app_state: State<'_, AppState>,
+    fuel: Fuel,
+    start_date: String,
+    end_date: String,
+    options: ComparisonOptions,
+) -> Result<PeriodComparison, ApiError> {
+    debug!(
+        "compare_consumption({}, {}, {}, {:?}) called",
+        fuel, start_date, end_date, options
+    );
+
+    let granularity = options.granularity.unwrap_or_default();
+    let alignment = match granularity {
+        ComparisonGranularity::Day => options.alignment.unwrap_or_default(),
+        ComparisonGranularity::Month => ComparisonAlignment::CalendarDate,
+    };
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    if end <= start {
+        return Err(ApiError::Custom(
+            "The end date must be after the start date".into(),
+        ));
+    }
+
+    let (comparison_start, comparison_end) = match (options.comparison_start_date, options.offset) {
+        (Some(comparison_start_date), _) => {
+            let comparison_start = parse_iso_string_to_naive_date(&comparison_start_date)?;
+            let comparison_end = match options.comparison_end_date {
+                Some(comparison_end_date) => parse_iso_string_to_naive_date(&comparison_end_date)?,
+                None => comparison_start + (end - start),
+            };
+
+            (comparison_start, comparison_end)
+        }
+        (None, Some(offset)) => offset_range(start, end, offset, alignment),
+        (None, None) => {
+            return Err(ApiError::Custom(
+                "Either a comparison date range or an offset is needed".into(),
+            ))
+        }
+    };
+
+    let current = load_comparison_period(&app_state, fuel, start, end, granularity).await?;
+    let comparison = load_comparison_period(
+        &app_state,
+        fuel,
+        comparison_start,
+        comparison_end,
+        granularity,
+    )
+    .await?;
+
+    Ok(compare_periods(&current, &comparison, granularity))
+}
+
+async fn load_comparison_period(
+    app_state: &State<'_, AppState>,
+    fuel: Fuel,
+    start: NaiveDate,
+    end: NaiveDate,
+    granularity: ComparisonGranularity,
+) -> Result<ComparisonPeriod, ApiError> {
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let values = tokio::task::spawn_blocking(move || {
+        get_consumption_buckets(connection_pool_clone, fuel, start, end, granularity)
+    })
+    .await??;
+
+    let costs = match fuel {
+        Fuel::Electricity => {
+            get_electricity_cost_history(app_state.clone(), start.to_string(), end.to_string())
+                .await?
+        }
+        Fuel::Gas => {
+            get_gas_cost_history(app_state.clone(), start.to_string(), end.to_string()).await?
+        }
+    };
+
+    Ok(ComparisonPeriod {
+        start,
+        end,
+        values,
+        costs: costs.iter().map(|x| (x.date, x.cost_pence)).collect(),
+    })
+}
+
+fn get_consumption_buckets(
+    connection_pool: SqliteConnectionPool,
+    fuel: Fuel,
+    start: NaiveDate,
+    end: NaiveDate,
+    granularity: ComparisonGranularity,
+) -> Result<BTreeMap<NaiveDate, i64>, RepositoryError> {
+    let daily = |records: Vec<(NaiveDate, i64, bool)>| {
+        records
+            .into_iter()
+            .map(|(date, value, _)| (date, value))
+            .collect()
+    };
+
+    Ok(match (fuel, granularity) {
+        (Fuel::Electricity, ComparisonGranularity::Day) => daily(
+            SqliteElectricityConsumptionRepository::new(connection_pool).get_daily(start, end)?,
+        ),
+        (Fuel::Electricity, ComparisonGranularity::Month) => {
+            SqliteElectricityConsumptionRepository::new(connection_pool)
+                .get_monthly(start, end)?
+                .into_iter()
+                .collect()
+        }
+        (Fuel::Gas, ComparisonGranularity::Day) => {
+            daily(SqliteGasConsumptionRepository::new(connection_pool).get_daily(start, end)?)
+        }
+        (Fuel::Gas, ComparisonGranularity::Month) => {
+            SqliteGasConsumptionRepository::new(connection_pool)
+                .get_monthly(start, end)?
+                .into_iter()
+                .collect()
+        }
+    })
+}
+
+/// Returns a day by half-hour matrix of consumption for a heatmap.
+#[tauri::command]
+pub async fn get_consumption_heatmap(
+    app_state: State<'_, AppState>,
+    fuel: Fuel,
+    start_date: String,
+    end_date: String,
+) -> Result<ConsumptionHeatmap, ApiError> {
+    debug!(
+        "get_consumption_heatmap({}, {}, {}) called",
+        fuel, start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let readings: Vec<(NaiveDateTime, i64)> = tokio::task::spawn_blocking(move || {
+        Ok::<_, RepositoryError>(match fuel {
+            Fuel::Electricity => SqliteElectricityConsumptionRepository::new(connection_pool_clone)
+                .get_raw(start, end)?
+                .into_iter()
+                .map(|x| (x.timestamp, x.energy_consumption_wh))
+                .collect(),
+            Fuel::Gas => SqliteGasConsumptionRepository::new(connection_pool_clone)
+                .get_raw(start, end)?
+                .into_iter()
+                .map(|x| (x.timestamp, x.energy_consumption_wh))
+                .collect(),
+        })
+    })
+    .await??;
+
+    Ok(consumption_heatmap(start, end, &readings, |wh| {
+        wh >= 0 && wh != ENERGY_CONSUMPTION_WH_ERROR_CODE
+    }))
+}
@@ -0,0 +1,72 @@
+use log::debug;
+use tauri::{AppHandle, State};
+
+use crate::{
+    api_server::restart_api_server,
+    utils::{self, get_or_create_api_token, ApiServerSettings},
+    AppState,
+};
+
+use super::ApiError;
+
+#[tauri::command]
+pub fn get_api_server_settings(
+    app_state: State<'_, AppState>,
+) -> Result<ApiServerSettings, ApiError> {
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(ApiServerSettings::from_app_settings(&app_settings)?)
+}
+
+/// Saves the API server settings and starts or stops the server to match.
+#[tauri::command]
+pub async fn store_api_server_settings(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    settings: ApiServerSettings,
+) -> Result<(), ApiError> {
+    debug!("store_api_server_settings({:?}) called", settings);
+
+    if settings.port == 0 {
+        return Err(ApiError::Custom("The API server needs a port".into()));
+    }
+
+    {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| ApiError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        settings.save(&app_settings)?;
+    }
+
+    restart_api_server(&app_handle).await?;
+
+    Ok(())
+}
+
+/// The token that API requests must send as `Authorization: Bearer <token>`.
+#[tauri::command]
+pub async fn get_api_token() -> Result<String, ApiError> {
+    Ok(tokio::task::spawn_blocking(get_or_create_api_token).await??)
+}
+
+/// Replaces the API token, and restarts the server so that only the new one is accepted.
+#[tauri::command]
+pub async fn regenerate_api_token(app_handle: AppHandle) -> Result<String, ApiError> {
+    debug!("regenerate_api_token called");
+
+    let token = tokio::task::spawn_blocking(utils::regenerate_api_token).await??;
+
+    restart_api_server(&app_handle).await?;
+
+    Ok(token)
+}
@@ -6,16 +6,26 @@ use serde::Serialize;
 use tauri::{async_runtime, AppHandle, State};
 
 use crate::{
-    db::{self, revert_all_migrations},
-    download::check_and_download_new_data,
+    api_server::restart_api_server,
+    data::{
+        deletion::{
+            DataDeletionRepository, DataKind, DeletionSummary, SqliteDataDeletionRepository,
+        },
+        Fuel,
+    },
+    dates::{reporting_timezone, set_reporting_timezone},
+    db::{self, recalculate_london_date_ids, revert_all_migrations},
+    download_tasks::check_and_download_new_data,
+    startup::StartupError,
     utils::{
-        delete_credential, get_glowmarkt_data_provider, reset_mqtt_settings,
-        switch_main_to_splashscreen, switch_splashscreen_to_main,
+        delete_credential, get_glowmarkt_data_provider, parse_iso_string_to_naive_date,
+        reset_mqtt_settings, switch_main_to_splashscreen, switch_splashscreen_to_main,
+        TimezoneSettings,
     },
     AppState, MqttMessage,
 };
 
-use super::ApiError;
+use super::{backup::backup_before_destructive_operation, ApiError};
 
 const GIT_VERSION: &str = git_version!();
 
@@ -75,15 +85,97 @@ pub fn get_app_status(app_state: State<'_, AppState>) -> Result<StatusResponse,
     })
 }
 
+/// Why the database couldn't be migrated when the app started, if it couldn't.
+#[tauri::command]
+pub fn get_startup_error(app_state: State<'_, AppState>) -> Option<StartupError> {
+    app_state.startup_error.clone()
+}
+
+#[tauri::command]
+pub fn get_timezone_settings() -> Result<TimezoneSettings, ApiError> {
+    Ok(TimezoneSettings {
+        timezone: reporting_timezone().name().to_string(),
+    })
+}
+
+/// Changes the reporting timezone, recalculating the date of every stored reading so that daily,
+/// monthly and cost totals follow the new timezone's days.
+#[tauri::command]
+pub async fn store_timezone_settings(
+    app_state: State<'_, AppState>,
+    settings: TimezoneSettings,
+) -> Result<(), ApiError> {
+    debug!("store_timezone_settings({:?}) called", settings);
+
+    let timezone = settings.parse()?;
+
+    // The timezone is stored in the database in the same transaction as the recalculated date
+    // ids, so that a failure leaves the old timezone and the stored dates consistent. Writers
+    // read it from there too, so anything written once this commits uses the new timezone, even
+    // before it's set here
+    if timezone != reporting_timezone() {
+        let connection_pool_clone = app_state.db_pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            recalculate_london_date_ids(&mut connection_pool_clone.get()?, timezone)
+        })
+        .await??;
+    }
+
+    set_reporting_timezone(timezone);
+
+    Ok(())
+}
+
 #[tauri::command]
-pub fn clear_all_data(app_state: State<'_, AppState>) -> Result<(), ApiError> {
+pub async fn clear_all_data(app_state: State<'_, AppState>) -> Result<(), ApiError> {
+    backup_before_destructive_operation(app_state.inner(), "clear").await?;
+
     reset_database(app_state.inner())?;
 
     Ok(())
 }
 
+/// Deletes some kinds of data for a fuel over a date range, e.g. a corrupted week, so that it can
+/// be downloaded again.
+#[tauri::command]
+pub async fn delete_data(
+    app_state: State<'_, AppState>,
+    fuel: Fuel,
+    start_date: String,
+    end_date: String,
+    kinds: Vec<DataKind>,
+) -> Result<DeletionSummary, ApiError> {
+    debug!(
+        "delete_data({}, {}, {}, {:?}) called",
+        fuel, start_date, end_date, kinds
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    if start >= end || kinds.is_empty() {
+        return Err(ApiError::Custom(
+            "Choose at least one kind of data and a date range to delete".into(),
+        ));
+    }
+
+    backup_before_destructive_operation(app_state.inner(), "delete").await?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let summary = tokio::task::spawn_blocking(move || {
+        SqliteDataDeletionRepository::new(connection_pool_clone).delete(fuel, start, end, &kinds)
+    })
+    .await??;
+
+    Ok(summary)
+}
+
 #[tauri::command]
 pub async fn reset(app_handle: AppHandle, app_state: State<'_, AppState>) -> Result<(), ApiError> {
+    backup_before_destructive_operation(app_state.inner(), "reset").await?;
+
     reset_database(app_state.inner())?;
 
     {
@@ -98,6 +190,14 @@ pub async fn reset(app_handle: AppHandle, app_state: State<'_, AppState>) -> Res
         app_settings
             .safe_set("termsAccepted", false)
             .map_err(|e| ApiError::Custom(format!("{}", e)))?;
+
+        app_settings
+            .safe_set("apiServerEnabled", false)
+            .map_err(|e| ApiError::Custom(format!("{}", e)))?;
+
+        app_settings
+            .safe_set("metricsEnabled", false)
+            .map_err(|e| ApiError::Custom(format!("{}", e)))?;
     }
 
     tokio::task::spawn_blocking(|| {
@@ -105,6 +205,7 @@ pub async fn reset(app_handle: AppHandle, app_state: State<'_, AppState>) -> Res
             "glowmarkt_credentials",
             "glowmarkt_username",
             "glowmarkt_password",
+            "api_token",
         ];
 
         for c in credentials {
@@ -115,6 +216,8 @@ pub async fn reset(app_handle: AppHandle, app_state: State<'_, AppState>) -> Res
     })
     .await??;
 
+    restart_api_server(&app_handle).await?;
+
     reset_mqtt_settings(&app_handle).await?;
 
     app_state
@@ -132,7 +235,10 @@ fn reset_database(app_state: &AppState) -> Result<(), ApiError> {
     let mut conn = app_state.db_pool.get()?;
 
     revert_all_migrations(&mut conn);
-    db::run_migrations(&mut conn);
+    db::run_migrations(&mut conn)?;
+
+    // The database is empty, so it can keep the current timezone without recalculating anything
+    db::store_reporting_timezone(&mut conn, reporting_timezone())?;
 
     Ok(())
 }
@@ -142,6 +248,12 @@ pub async fn fetch_data(
     app_handle: AppHandle,
     app_state: State<'_, AppState>,
 ) -> Result<(), ApiError> {
+    if app_state.startup_error.is_some() {
+        return Err(ApiError::Custom(
+            "Data can't be downloaded while the database is open read-only".into(),
+        ));
+    }
+
     let app_state_clone = (*app_state).clone();
 
     if let Some(data_provider) = get_glowmarkt_data_provider()
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+
+use log::{debug, info};
+use tauri::{AppHandle, State};
+
+use crate::{
+    backup::{self, automatic_backup_directory, create_automatic_backup, BackupInfo},
+    utils::BackupSettings,
+    AppState,
+};
+
+use super::ApiError;
+
+#[tauri::command]
+pub fn get_backup_settings(app_state: State<'_, AppState>) -> Result<BackupSettings, ApiError> {
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(BackupSettings::from_app_settings(&app_settings)?)
+}
+
+#[tauri::command]
+pub fn store_backup_settings(
+    app_state: State<'_, AppState>,
+    settings: BackupSettings,
+) -> Result<(), ApiError> {
+    if settings.automatic_backup_count == 0 {
+        return Err(ApiError::Custom(
+            "At least one automatic backup must be kept".into(),
+        ));
+    }
+
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(settings.save(&app_settings)?)
+}
+
+/// Writes a backup of the database to a file chosen by the user.
+#[tauri::command]
+pub async fn backup_database(
+    app_state: State<'_, AppState>,
+    path: String,
+) -> Result<BackupInfo, ApiError> {
+    debug!("backup_database({}) called", path);
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let backup = tokio::task::spawn_blocking(move || {
+        backup::backup_database(&mut connection_pool_clone.get()?, &PathBuf::from(path))
+            .map_err(ApiError::from)
+    })
+    .await??;
+
+    Ok(backup)
+}
+
+/// Validates a backup and migrates it to the current schema, then restarts the app so that it
+/// replaces the database before any connections are opened.
+#[tauri::command]
+pub async fn restore_database(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    path: String,
+) -> Result<(), ApiError> {
+    debug!("restore_database({}) called", path);
+
+    backup_before_destructive_operation(app_state.inner(), "restore").await?;
+
+    let db_path = app_state.db_path.clone();
+
+    let restore =
+        tokio::task::spawn_blocking(move || backup::stage_restore(&PathBuf::from(path), &db_path))
+            .await??;
+
+    info!(
+        "Restarting to restore {} at schema version {}",
+        restore.path, restore.schema_version
+    );
+
+    app_handle.restart()
+}
+
+/// Takes an automatic backup before an operation that deletes data, if they're enabled.
+pub(crate) async fn backup_before_destructive_operation(
+    app_state: &AppState,
+    reason: &'static str,
+) -> Result<(), ApiError> {
+    let settings = {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| ApiError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        BackupSettings::from_app_settings(&app_settings)?
+    };
+
+    if !settings.automatic_backups {
+        return Ok(());
+    }
+
+    let connection_pool_clone = app_state.db_pool.clone();
+    let backup_directory = automatic_backup_directory(&app_state.db_path);
+
+    tokio::task::spawn_blocking(move || {
+        create_automatic_backup(
+            &mut connection_pool_clone.get()?,
+            &backup_directory,
+            reason,
+            settings.automatic_backup_count,
+        )
+        .map_err(ApiError::from)
+    })
+    .await??;
+
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
 use log::{debug, warn};
 use serde::Serialize;
 use tauri::{async_runtime, State};
@@ -8,9 +8,12 @@ use tauri::{async_runtime, State};
 use super::tariff::{DailyCost, StandingCharge, TariffHistoryResponse, UnitPrice};
 use crate::{
     data::{
-        consumption::{ConsumptionRepository, SqliteElectricityConsumptionRepository},
+        consumption::{
+            ConsumptionRepository, ProfileSplit, SqliteElectricityConsumptionRepository,
+        },
         tariff::{SqliteElectricityTariffRepository, TariffRepository},
     },
+    dates::is_partial_period,
     utils::parse_iso_string_to_naive_date,
     AppState,
 };
@@ -18,16 +21,20 @@ use crate::{
 use super::ApiError;
 
 #[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct ElectricityConsumption {
     #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
     pub timestamp: NaiveDateTime,
     pub value: i64,
+    pub is_provisional: bool,
 }
 
 #[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct DailyElectricityConsumption {
     pub timestamp: NaiveDate,
     pub value: i64,
+    pub is_provisional: bool,
 }
 
 #[derive(Serialize, Debug)]
@@ -36,6 +43,37 @@ pub struct MonthlyElectricityConsumption {
     pub value: i64,
 }
 
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyElectricityConsumption {
+    /// The Monday the week starts on
+    pub timestamp: NaiveDate,
+    pub iso_year: i32,
+    pub iso_week: u32,
+    pub value: i64,
+    pub is_partial: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct YearlyElectricityConsumption {
+    pub timestamp: NaiveDate,
+    pub value: i64,
+    pub is_partial: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ElectricityConsumptionProfile {
+    pub group: String,
+    pub half_hour: i32,
+    pub sample_count: i64,
+    pub average: f64,
+    pub p10: i64,
+    pub p50: i64,
+    pub p90: i64,
+}
+
 #[tauri::command]
 pub async fn get_raw_electricity_consumption(
     app_state: State<'_, AppState>,
@@ -67,6 +105,7 @@ pub async fn get_raw_electricity_consumption(
                     .map(|x| ElectricityConsumption {
                         timestamp: x.timestamp,
                         value: x.energy_consumption_wh,
+                        is_provisional: x.is_provisional,
                     })
                     .collect());
             }
@@ -109,6 +148,7 @@ pub async fn get_daily_electricity_consumption(
         .map(|x| DailyElectricityConsumption {
             timestamp: x.0,
             value: x.1,
+            is_provisional: x.2,
         })
         .collect())
 }
@@ -147,6 +187,126 @@ pub async fn get_monthly_electricity_consumption(
         .collect())
 }
 
+#[tauri::command]
+pub async fn get_weekly_electricity_consumption(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<WeeklyElectricityConsumption>, ApiError> {
+    debug!(
+        "get_weekly_electricity_consumption({}, {}) called",
+        start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let weekly_consumption = async_runtime::spawn_blocking(move || {
+        let repository = SqliteElectricityConsumptionRepository::new(connection_pool_clone);
+
+        repository.get_weekly(start, end)
+    })
+    .await
+    .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
+    .map_err(ApiError::from)?;
+
+    Ok(weekly_consumption
+        .iter()
+        .map(|x| {
+            let iso_week = x.0.iso_week();
+
+            WeeklyElectricityConsumption {
+                timestamp: x.0,
+                iso_year: iso_week.year(),
+                iso_week: iso_week.week(),
+                value: x.1,
+                is_partial: is_partial_period(&x.0, &(x.0 + Duration::days(7)), &start, &end),
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_yearly_electricity_consumption(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<YearlyElectricityConsumption>, ApiError> {
+    debug!(
+        "get_yearly_electricity_consumption({}, {}) called",
+        start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let yearly_consumption = async_runtime::spawn_blocking(move || {
+        let repository = SqliteElectricityConsumptionRepository::new(connection_pool_clone);
+
+        repository.get_yearly(start, end)
+    })
+    .await
+    .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
+    .map_err(ApiError::from)?;
+
+    Ok(yearly_consumption
+        .iter()
+        .map(|x| {
+            let next_year = x.0.with_year(x.0.year() + 1).expect("January 1st to exist");
+
+            YearlyElectricityConsumption {
+                timestamp: x.0,
+                value: x.1,
+                is_partial: is_partial_period(&x.0, &next_year, &start, &end),
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_electricity_consumption_profile(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    split: Option<ProfileSplit>,
+) -> Result<Vec<ElectricityConsumptionProfile>, ApiError> {
+    debug!(
+        "get_electricity_consumption_profile({}, {}, {:?}) called",
+        start_date, end_date, split
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let profile = async_runtime::spawn_blocking(move || {
+        let repository = SqliteElectricityConsumptionRepository::new(connection_pool_clone);
+
+        repository.get_profile(start, end, split.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
+    .map_err(ApiError::from)?;
+
+    Ok(profile
+        .into_iter()
+        .map(|x| ElectricityConsumptionProfile {
+            group: x.profile_group,
+            half_hour: x.half_hour,
+            sample_count: x.sample_count,
+            average: x.average_wh,
+            p10: x.p10_wh,
+            p50: x.p50_wh,
+            p90: x.p90_wh,
+        })
+        .collect())
+}
+
 #[tauri::command]
 pub async fn get_electricity_tariff_history(
     app_state: State<'_, AppState>,
@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use log::debug;
+use serde::Deserialize;
+use tauri::State;
+
+use crate::{
+    data::Fuel,
+    export::{
+        load_rows, load_tariff_timeline, write_export, ExportFormat, ExportGranularity, ExportRow,
+    },
+    utils::parse_iso_string_to_naive_date,
+    AppState,
+};
+
+use super::ApiError;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    pub fuels: Vec<Fuel>,
+    pub granularity: ExportGranularity,
+    pub format: ExportFormat,
+    pub start_date: String,
+    pub end_date: String,
+    pub path: String,
+}
+
+/// Exports consumption, costs and tariffs for a date range to a file, returning the number of
+/// rows written. Invalid readings are left out, as they are from the charts.
+#[tauri::command]
+pub async fn export_consumption(
+    app_state: State<'_, AppState>,
+    options: ExportOptions,
+) -> Result<usize, ApiError> {
+    debug!("export_consumption({:?}) called", options);
+
+    let start = parse_iso_string_to_naive_date(&options.start_date)?;
+    let end = parse_iso_string_to_naive_date(&options.end_date)?;
+
+    let mut rows: Vec<ExportRow> = vec![];
+
+    for fuel in options.fuels {
+        let connection_pool_clone = app_state.db_pool.clone();
+        let granularity = options.granularity;
+
+        let fuel_rows = tokio::task::spawn_blocking(move || {
+            let tariffs = load_tariff_timeline(connection_pool_clone.clone(), fuel)?;
+
+            load_rows(
+                connection_pool_clone,
+                fuel,
+                start,
+                end,
+                granularity,
+                &tariffs,
+            )
+        })
+        .await??;
+
+        rows.extend(fuel_rows);
+    }
+
+    let row_count = rows.len();
+    let path = PathBuf::from(options.path);
+    let format = options.format;
+
+    tokio::task::spawn_blocking(move || write_export(&path, format, &rows)).await??;
+
+    Ok(row_count)
+}
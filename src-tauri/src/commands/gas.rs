@@ -1,32 +1,47 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
 use log::{debug, warn};
 use serde::Serialize;
 use tauri::{async_runtime, State};
 
-use super::tariff::{DailyCost, StandingCharge, TariffHistoryResponse, UnitPrice};
+use super::{
+    tariff::{DailyCost, StandingCharge, TariffHistoryResponse, UnitPrice},
+    weather::get_weather_settings,
+};
 use crate::{
+    analysis::degree_days::heating_degree_days,
     commands::ApiError,
     data::{
-        consumption::{ConsumptionRepository, SqliteGasConsumptionRepository},
+        consumption::{ConsumptionRepository, ProfileSplit, SqliteGasConsumptionRepository},
         tariff::{SqliteGasTariffRepository, TariffRepository},
+        weather::{SqliteWeatherRepository, WeatherRepository},
+        RepositoryError,
     },
+    dates::is_partial_period,
     utils::parse_iso_string_to_naive_date,
     AppState,
 };
 
 #[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct GasConsumption {
     #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
     pub timestamp: NaiveDateTime,
     pub value: i64,
+    pub is_provisional: bool,
 }
 
 #[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct DailyGasConsumption {
     pub timestamp: NaiveDate,
     pub value: i64,
+    pub is_provisional: bool,
+    pub mean_temperature: Option<f64>,
+    pub heating_degree_days: Option<f64>,
+    /// The consumption normalised by the weather, for days that needed heating
+    pub kwh_per_degree_day: Option<f64>,
 }
 
 #[derive(Serialize, Debug)]
@@ -35,6 +50,37 @@ pub struct MonthlyGasConsumption {
     pub value: i64,
 }
 
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyGasConsumption {
+    /// The Monday the week starts on
+    pub timestamp: NaiveDate,
+    pub iso_year: i32,
+    pub iso_week: u32,
+    pub value: i64,
+    pub is_partial: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct YearlyGasConsumption {
+    pub timestamp: NaiveDate,
+    pub value: i64,
+    pub is_partial: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GasConsumptionProfile {
+    pub group: String,
+    pub half_hour: i32,
+    pub sample_count: i64,
+    pub average: f64,
+    pub p10: i64,
+    pub p50: i64,
+    pub p90: i64,
+}
+
 #[tauri::command]
 pub async fn get_raw_gas_consumption(
     app_state: State<'_, AppState>,
@@ -62,6 +108,7 @@ pub async fn get_raw_gas_consumption(
         .map(|x| GasConsumption {
             timestamp: x.timestamp,
             value: x.energy_consumption_wh,
+            is_provisional: x.is_provisional,
         })
         .collect())
 }
@@ -79,20 +126,40 @@ pub async fn get_daily_gas_consumption(
 
     let connection_pool_clone = app_state.db_pool.clone();
 
-    let daily_consumption = async_runtime::spawn_blocking(move || {
-        let repository = SqliteGasConsumptionRepository::new(connection_pool_clone);
+    let base_temperature = get_weather_settings(app_state.clone())?.base_temperature_celsius;
 
-        repository.get_daily(start, end)
+    let (daily_consumption, daily_temperature) = async_runtime::spawn_blocking(move || {
+        let repository = SqliteGasConsumptionRepository::new(connection_pool_clone.clone());
+        let weather_repository = SqliteWeatherRepository::new(connection_pool_clone);
+
+        Ok::<_, RepositoryError>((
+            repository.get_daily(start, end)?,
+            weather_repository.get_daily_mean(start, end)?,
+        ))
     })
     .await
     .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
     .map_err(ApiError::from)?;
 
+    let temperatures: HashMap<_, _> = daily_temperature.into_iter().collect();
+
     Ok(daily_consumption
         .iter()
-        .map(|x| DailyGasConsumption {
-            timestamp: x.0,
-            value: x.1,
+        .map(|x| {
+            let mean_temperature = temperatures.get(&x.0).copied();
+            let heating_degree_days =
+                mean_temperature.map(|t| heating_degree_days(t, base_temperature));
+
+            DailyGasConsumption {
+                timestamp: x.0,
+                value: x.1,
+                is_provisional: x.2,
+                mean_temperature,
+                heating_degree_days,
+                kwh_per_degree_day: heating_degree_days
+                    .filter(|hdd| *hdd > 0.0)
+                    .map(|hdd| x.1 as f64 / 1000.0 / hdd),
+            }
         })
         .collect())
 }
@@ -128,6 +195,126 @@ pub async fn get_monthly_gas_consumption(
         .collect())
 }
 
+#[tauri::command]
+pub async fn get_weekly_gas_consumption(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<WeeklyGasConsumption>, ApiError> {
+    debug!(
+        "get_weekly_gas_consumption({}, {}) called",
+        start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let weekly_consumption = async_runtime::spawn_blocking(move || {
+        let repository = SqliteGasConsumptionRepository::new(connection_pool_clone);
+
+        repository.get_weekly(start, end)
+    })
+    .await
+    .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
+    .map_err(ApiError::from)?;
+
+    Ok(weekly_consumption
+        .iter()
+        .map(|x| {
+            let iso_week = x.0.iso_week();
+
+            WeeklyGasConsumption {
+                timestamp: x.0,
+                iso_year: iso_week.year(),
+                iso_week: iso_week.week(),
+                value: x.1,
+                is_partial: is_partial_period(&x.0, &(x.0 + Duration::days(7)), &start, &end),
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_yearly_gas_consumption(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<YearlyGasConsumption>, ApiError> {
+    debug!(
+        "get_yearly_gas_consumption({}, {}) called",
+        start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let yearly_consumption = async_runtime::spawn_blocking(move || {
+        let repository = SqliteGasConsumptionRepository::new(connection_pool_clone);
+
+        repository.get_yearly(start, end)
+    })
+    .await
+    .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
+    .map_err(ApiError::from)?;
+
+    Ok(yearly_consumption
+        .iter()
+        .map(|x| {
+            let next_year = x.0.with_year(x.0.year() + 1).expect("January 1st to exist");
+
+            YearlyGasConsumption {
+                timestamp: x.0,
+                value: x.1,
+                is_partial: is_partial_period(&x.0, &next_year, &start, &end),
+            }
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn get_gas_consumption_profile(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+    split: Option<ProfileSplit>,
+) -> Result<Vec<GasConsumptionProfile>, ApiError> {
+    debug!(
+        "get_gas_consumption_profile({}, {}, {:?}) called",
+        start_date, end_date, split
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let profile = async_runtime::spawn_blocking(move || {
+        let repository = SqliteGasConsumptionRepository::new(connection_pool_clone);
+
+        repository.get_profile(start, end, split.unwrap_or_default())
+    })
+    .await
+    .map_err(|e| ApiError::Custom(format!("Database query failed: {}", e)))?
+    .map_err(ApiError::from)?;
+
+    Ok(profile
+        .into_iter()
+        .map(|x| GasConsumptionProfile {
+            group: x.profile_group,
+            half_hour: x.half_hour,
+            sample_count: x.sample_count,
+            average: x.average_wh,
+            p10: x.p10_wh,
+            p50: x.p50_wh,
+            p90: x.p90_wh,
+        })
+        .collect())
+}
+
 #[tauri::command]
 pub async fn get_gas_tariff_history(
     app_state: State<'_, AppState>,
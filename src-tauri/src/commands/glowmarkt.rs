@@ -2,7 +2,7 @@ use tauri::{AppHandle, State};
 
 use crate::{
     commands::ApiError,
-    download::spawn_download_tasks,
+    download_tasks::spawn_download_tasks,
     utils::{
         get_glowmarkt_credentials_opt, get_glowmarkt_data_provider, save_glowmarkt_credentials,
         GlowmarktCredentials,
@@ -1,12 +1,19 @@
 use crate::{clients::glowmarkt::GlowmarktDataProviderError, data::RepositoryError, AppError};
 
+pub mod alerts;
+pub mod analysis;
+pub mod api_server;
 pub mod app;
+pub mod backup;
 pub mod electricity;
+pub mod export;
 pub mod gas;
 pub mod glowmarkt;
 pub mod mqtt;
 pub mod profiles;
+pub mod quality;
 pub mod tariff;
+pub mod weather;
 
 pub(crate) const APP_SERVICE_NAME: &str = "io.github.rars.smart_energy_explorer";
 
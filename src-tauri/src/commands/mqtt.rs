@@ -1,14 +1,111 @@
+use serde::Deserialize;
 use tauri::{AppHandle, State};
 
 use crate::{
     commands::ApiError,
     utils::{
-        get_mqtt_settings_opt, save_mqtt_credentials, MqttAppSettings, MqttCredentials,
-        MqttSettings,
+        delete_mqtt_broker_credentials, get_mqtt_settings_opt, new_mqtt_client_id,
+        refresh_mqtt_settings, save_mqtt_broker_credentials, save_mqtt_credentials,
+        set_glow_source_broker, MqttAppSettings, MqttBrokerConfig, MqttCredentials,
+        MqttPayloadParser, MqttReplaySettings, MqttSettings, DEFAULT_MQTT_BROKER_NAME,
+        DEFAULT_MQTT_KEEP_ALIVE_SECONDS, DEFAULT_MQTT_QOS,
     },
     AppState, MqttMessage,
 };
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MqttBrokerParam {
+    pub name: String,
+    pub parser: MqttPayloadParser,
+    pub hostname: String,
+    pub topic: String,
+    pub gas_topic: String,
+    pub username: String,
+    pub password: String,
+    pub client_id: Option<String>,
+    pub qos: Option<i32>,
+    pub persistent_session: Option<bool>,
+    pub keep_alive_seconds: Option<u64>,
+    pub status_topic: Option<String>,
+    pub glow_source: Option<bool>,
+}
+
+impl MqttBrokerParam {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.name.trim().is_empty() {
+            return Err(ApiError::Custom(
+                "MQTT broker name must not be empty".into(),
+            ));
+        }
+
+        if let Some(qos) = self.qos {
+            if !(0..=2).contains(&qos) {
+                return Err(ApiError::Custom(format!(
+                    "MQTT QoS must be 0, 1 or 2, not {}",
+                    qos
+                )));
+            }
+        }
+
+        if self.glow_source == Some(true) && self.parser != MqttPayloadParser::Glow {
+            return Err(ApiError::Custom(
+                "Only a broker using the Glow parser can be the Glow source".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn to_config(&self, existing: Option<&MqttBrokerConfig>) -> MqttBrokerConfig {
+        let client_id = self
+            .client_id
+            .as_ref()
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .or(existing.map(|e| e.client_id.clone()))
+            .unwrap_or_else(new_mqtt_client_id);
+
+        MqttBrokerConfig {
+            name: self.name.trim().to_string(),
+            parser: self.parser,
+            hostname: self.hostname.trim().to_string(),
+            topic: self.topic.trim().to_string(),
+            gas_topic: self.gas_topic.trim().to_string(),
+            client_id,
+            qos: self
+                .qos
+                .or(existing.map(|e| e.qos))
+                .unwrap_or(DEFAULT_MQTT_QOS),
+            persistent_session: self
+                .persistent_session
+                .or(existing.map(|e| e.persistent_session))
+                .unwrap_or(true),
+            keep_alive_seconds: self
+                .keep_alive_seconds
+                .or(existing.map(|e| e.keep_alive_seconds))
+                .unwrap_or(DEFAULT_MQTT_KEEP_ALIVE_SECONDS),
+            status_topic: self
+                .status_topic
+                .as_ref()
+                .map(|t| t.trim().to_string())
+                .or(existing.map(|e| e.status_topic.clone()))
+                .unwrap_or_default(),
+            glow_source: self
+                .glow_source
+                .or(existing.map(|e| e.glow_source))
+                .unwrap_or(false),
+        }
+    }
+
+    fn credentials(&self) -> MqttCredentials {
+        MqttCredentials {
+            username: self.username.trim().into(),
+            password: self.password.trim().into(),
+        }
+    }
+}
+
 #[tauri::command]
 pub async fn get_mqtt_settings(app_state: State<'_, AppState>) -> Result<MqttSettings, ApiError> {
     let mqtt_app_settings = {
@@ -16,16 +113,24 @@ pub async fn get_mqtt_settings(app_state: State<'_, AppState>) -> Result<MqttSet
         MqttAppSettings::from_app_settings(&app_settings)?
     };
 
-    if let Some(settings) = get_mqtt_settings_opt(mqtt_app_settings).await? {
+    if let Some(settings) = get_mqtt_settings_opt(mqtt_app_settings.clone()).await? {
         return Ok(settings);
     }
 
     Ok(MqttSettings {
+        name: DEFAULT_MQTT_BROKER_NAME.to_string(),
+        parser: MqttPayloadParser::Glow,
         hostname: "".to_string(),
         topic: "".to_string(),
         gas_topic: "".to_string(),
         username: "".to_string(),
         password: "".to_string(),
+        client_id: mqtt_app_settings.client_id,
+        qos: mqtt_app_settings.qos,
+        persistent_session: mqtt_app_settings.persistent_session,
+        keep_alive_seconds: mqtt_app_settings.keep_alive_seconds,
+        status_topic: mqtt_app_settings.status_topic,
+        glow_source: mqtt_app_settings.glow_source,
     })
 }
 
@@ -37,7 +142,22 @@ pub async fn store_mqtt_settings(
     gas_topic: String,
     username: String,
     password: String,
+    client_id: Option<String>,
+    qos: Option<i32>,
+    persistent_session: Option<bool>,
+    keep_alive_seconds: Option<u64>,
+    status_topic: Option<String>,
+    glow_source: Option<bool>,
 ) -> Result<(), ApiError> {
+    if let Some(qos) = qos {
+        if !(0..=2).contains(&qos) {
+            return Err(ApiError::Custom(format!(
+                "MQTT QoS must be 0, 1 or 2, not {}",
+                qos
+            )));
+        }
+    }
+
     let credentials = MqttCredentials {
         username: username.trim().into(),
         password: password.trim().into(),
@@ -45,7 +165,7 @@ pub async fn store_mqtt_settings(
 
     tokio::task::spawn_blocking(move || save_mqtt_credentials(&credentials)).await??;
 
-    let mqtt_app_settings = {
+    {
         let app_settings =
             app_state
                 .app_settings
@@ -60,13 +180,75 @@ pub async fn store_mqtt_settings(
 
         app_settings.safe_set("mqttGasTopic", gas_topic.trim().to_string())?;
 
-        MqttAppSettings::from_app_settings(&app_settings)?
-    };
+        // An empty client ID would leave the default broker without one
+        if let Some(client_id) = client_id.filter(|c| !c.trim().is_empty()) {
+            app_settings.safe_set("mqttClientId", client_id.trim().to_string())?;
+        }
+
+        if let Some(qos) = qos {
+            app_settings.safe_set("mqttQos", qos)?;
+        }
+
+        if let Some(persistent_session) = persistent_session {
+            app_settings.safe_set("mqttPersistentSession", persistent_session)?;
+        }
+
+        if let Some(keep_alive_seconds) = keep_alive_seconds {
+            app_settings.safe_set("mqttKeepAliveSeconds", keep_alive_seconds)?;
+        }
+
+        if let Some(status_topic) = status_topic {
+            app_settings.safe_set("mqttStatusTopic", status_topic.trim().to_string())?;
+        }
+
+        match glow_source {
+            Some(true) => set_glow_source_broker(&app_settings, DEFAULT_MQTT_BROKER_NAME)?,
+            Some(false) => app_settings.safe_set("mqttGlowSource", false)?,
+            None => (),
+        }
+    }
+
+    refresh_mqtt_settings(&app_state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn reset_mqtt_settings(app_handle: AppHandle) -> Result<(), ApiError> {
+    crate::utils::reset_mqtt_settings(&app_handle).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_mqtt_replay_settings(
+    app_state: State<'_, AppState>,
+) -> Result<MqttReplaySettings, ApiError> {
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
 
-    let updated_settings = get_mqtt_settings_opt(mqtt_app_settings).await?;
+    Ok(MqttReplaySettings::from_app_settings(&app_settings)?)
+}
+
+#[tauri::command]
+pub async fn store_mqtt_replay_settings(
+    app_state: State<'_, AppState>,
+    settings: MqttReplaySettings,
+) -> Result<(), ApiError> {
+    {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| ApiError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
 
-    if let Ok(mut guard) = app_state.mqtt_settings.lock() {
-        *guard = updated_settings;
+        settings.save(&app_settings)?;
     }
 
     app_state
@@ -79,8 +261,181 @@ pub async fn store_mqtt_settings(
 }
 
 #[tauri::command]
-pub async fn reset_mqtt_settings(app_handle: AppHandle) -> Result<(), ApiError> {
-    crate::utils::reset_mqtt_settings(&app_handle).await?;
+pub fn get_mqtt_brokers(app_state: State<'_, AppState>) -> Result<Vec<MqttSettings>, ApiError> {
+    let mqtt_settings =
+        app_state
+            .mqtt_settings
+            .lock()
+            .map_err(|_| ApiError::MutexPoisonedError {
+                name: "mqtt_settings".into(),
+            })?;
+
+    Ok(mqtt_settings.clone())
+}
+
+#[tauri::command]
+pub async fn add_mqtt_broker(
+    app_state: State<'_, AppState>,
+    broker: MqttBrokerParam,
+) -> Result<(), ApiError> {
+    broker.validate()?;
+
+    let config = broker.to_config(None);
+
+    if config.name == DEFAULT_MQTT_BROKER_NAME {
+        return Err(ApiError::Custom(format!(
+            "The name '{}' is reserved for the default MQTT broker",
+            DEFAULT_MQTT_BROKER_NAME
+        )));
+    }
+
+    {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| ApiError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        let mut brokers = MqttBrokerConfig::load_all(&app_settings)?;
+
+        if brokers.iter().any(|b| b.name == config.name) {
+            return Err(ApiError::Custom(format!(
+                "An MQTT broker named '{}' already exists",
+                config.name
+            )));
+        }
+
+        brokers.push(config.clone());
+
+        MqttBrokerConfig::save_all(&app_settings, &brokers)?;
+
+        if config.glow_source {
+            set_glow_source_broker(&app_settings, &config.name)?;
+        }
+    }
+
+    let credentials = broker.credentials();
+
+    tokio::task::spawn_blocking(move || save_mqtt_broker_credentials(&config.name, &credentials))
+        .await??;
+
+    refresh_mqtt_settings(&app_state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn update_mqtt_broker(
+    app_state: State<'_, AppState>,
+    name: String,
+    broker: MqttBrokerParam,
+) -> Result<(), ApiError> {
+    broker.validate()?;
+
+    if name == DEFAULT_MQTT_BROKER_NAME {
+        return store_mqtt_settings(
+            app_state,
+            broker.hostname,
+            broker.topic,
+            broker.gas_topic,
+            broker.username,
+            broker.password,
+            broker.client_id,
+            broker.qos,
+            broker.persistent_session,
+            broker.keep_alive_seconds,
+            broker.status_topic,
+            broker.glow_source,
+        )
+        .await;
+    }
+
+    let config = {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| ApiError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        let mut brokers = MqttBrokerConfig::load_all(&app_settings)?;
+
+        let position = brokers
+            .iter()
+            .position(|b| b.name == name)
+            .ok_or_else(|| ApiError::Custom(format!("No MQTT broker named '{}'", name)))?;
+
+        let config = broker.to_config(Some(&brokers[position]));
+
+        if config.name != name
+            && (config.name == DEFAULT_MQTT_BROKER_NAME
+                || brokers.iter().any(|b| b.name == config.name))
+        {
+            return Err(ApiError::Custom(format!(
+                "An MQTT broker named '{}' already exists",
+                config.name
+            )));
+        }
+
+        brokers[position] = config.clone();
+
+        MqttBrokerConfig::save_all(&app_settings, &brokers)?;
+
+        if config.glow_source {
+            set_glow_source_broker(&app_settings, &config.name)?;
+        }
+
+        config
+    };
+
+    let credentials = broker.credentials();
+
+    tokio::task::spawn_blocking(move || {
+        if config.name != name {
+            delete_mqtt_broker_credentials(&name)?;
+        }
+
+        save_mqtt_broker_credentials(&config.name, &credentials)
+    })
+    .await??;
+
+    refresh_mqtt_settings(&app_state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_mqtt_broker(
+    app_handle: AppHandle,
+    app_state: State<'_, AppState>,
+    name: String,
+) -> Result<(), ApiError> {
+    if name == DEFAULT_MQTT_BROKER_NAME {
+        return reset_mqtt_settings(app_handle).await;
+    }
+
+    {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| ApiError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        let mut brokers = MqttBrokerConfig::load_all(&app_settings)?;
+
+        brokers.retain(|b| b.name != name);
+
+        MqttBrokerConfig::save_all(&app_settings, &brokers)?;
+    }
+
+    tokio::task::spawn_blocking(move || delete_mqtt_broker_credentials(&name)).await??;
+
+    refresh_mqtt_settings(&app_state).await?;
 
     Ok(())
 }
@@ -4,7 +4,7 @@ use tauri::{AppHandle, State};
 
 use crate::{
     data::energy_profile::{EnergyProfile, EnergyProfileRepository, SqliteEnergyProfileRepository},
-    download::spawn_download_tasks,
+    download_tasks::spawn_download_tasks,
     utils::{get_glowmarkt_data_provider, parse_iso_string_to_naive_date},
     AppState,
 };
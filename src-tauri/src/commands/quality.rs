@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use log::debug;
+use serde::Serialize;
+use tauri::State;
+
+use crate::{
+    data::{
+        quality::{DailyQualityRecord, DataQualityRepository, SqliteDataQualityRepository},
+        Fuel,
+    },
+    dates::{london_date_id_to_naive_date, london_slot_count, london_today},
+    utils::parse_iso_string_to_naive_date,
+    AppState,
+};
+
+use super::ApiError;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyDataQuality {
+    pub date: NaiveDate,
+    /// The number of half hours in the day, which is 46 or 50 when the clocks change
+    pub expected_readings: i64,
+    pub readings: i64,
+    pub valid_readings: i64,
+    pub missing_readings: i64,
+    pub error_code_readings: i64,
+    pub negative_readings: i64,
+    pub implausible_readings: i64,
+    pub duplicate_readings: i64,
+    /// Whether fewer half hours have readings than expected
+    pub is_short_day: bool,
+}
+
+/// Returns the quality of the stored readings for each London day in a range, up to yesterday.
+/// Only valid readings are included in any aggregate, so days with anything other than
+/// `expectedReadings` valid readings are incomplete.
+#[tauri::command]
+pub async fn get_data_quality_summary(
+    app_state: State<'_, AppState>,
+    fuel: Fuel,
+    start_date: String,
+    end_date: String,
+) -> Result<Vec<DailyDataQuality>, ApiError> {
+    debug!(
+        "get_data_quality_summary({}, {}, {}) called",
+        fuel, start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?.min(london_today());
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let records = tokio::task::spawn_blocking(move || {
+        SqliteDataQualityRepository::new(connection_pool_clone).get_daily_quality(fuel, start, end)
+    })
+    .await??;
+
+    Ok(summarise_daily_quality(records, start, end))
+}
+
+/// One summary for every London day from `start` up to `end`, including days without readings.
+fn summarise_daily_quality(
+    records: Vec<DailyQualityRecord>,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Vec<DailyDataQuality> {
+    let records_by_date: HashMap<NaiveDate, _> = records
+        .into_iter()
+        .map(|x| (london_date_id_to_naive_date(x.london_date_id), x))
+        .collect();
+
+    start
+        .iter_days()
+        .take_while(|date| *date < end)
+        .map(|date| {
+            let expected_readings = london_slot_count(&date) as i64;
+
+            match records_by_date.get(&date) {
+                Some(x) => {
+                    let readings = x.reading_count - x.duplicate_count;
+
+                    DailyDataQuality {
+                        date,
+                        expected_readings,
+                        readings,
+                        valid_readings: x.valid_count,
+                        missing_readings: (expected_readings - readings).max(0),
+                        error_code_readings: x.error_code_count,
+                        negative_readings: x.negative_count,
+                        implausible_readings: x.implausible_count,
+                        duplicate_readings: x.duplicate_count,
+                        is_short_day: readings < expected_readings,
+                    }
+                }
+                None => DailyDataQuality {
+                    date,
+                    expected_readings,
+                    readings: 0,
+                    valid_readings: 0,
+                    missing_readings: expected_readings,
+                    error_code_readings: 0,
+                    negative_readings: 0,
+                    implausible_readings: 0,
+                    duplicate_readings: 0,
+                    is_short_day: true,
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDateTime};
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::data::consumption::{
+        ConsumptionRepository, ElectricityConsumptionValue, SqliteElectricityConsumptionRepository,
+    };
+    use crate::db::TestDatabase;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    fn half_hours(start: NaiveDateTime, count: i64) -> impl Iterator<Item = NaiveDateTime> {
+        (0..count).map(move |i| start + Duration::minutes(30 * i))
+    }
+
+    #[test]
+    fn test_summary_counts_short_days_around_a_clock_change() {
+        let database = TestDatabase::new();
+
+        // The clocks go forward on March 29th, so it has 46 half hours, and March 30th is one
+        // short with a misaligned reading that doesn't fill the gap
+        let timestamps = half_hours(date(29).and_hms_opt(0, 0, 0).unwrap(), 46)
+            .chain(half_hours(date(29).and_hms_opt(23, 0, 0).unwrap(), 47))
+            .chain([date(30).and_hms_opt(10, 10, 0).unwrap()]);
+
+        SqliteElectricityConsumptionRepository::new(database.pool.clone())
+            .insert(
+                timestamps
+                    .map(|timestamp| ElectricityConsumptionValue {
+                        timestamp,
+                        value: Decimal::new(100, 3),
+                    })
+                    .collect(),
+            )
+            .unwrap();
+
+        let records = SqliteDataQualityRepository::new(database.pool.clone())
+            .get_daily_quality(Fuel::Electricity, date(29), date(31) + Duration::days(1))
+            .unwrap();
+
+        let summary = summarise_daily_quality(records, date(29), date(31) + Duration::days(1));
+
+        let counts: Vec<_> = summary
+            .iter()
+            .map(|x| {
+                (
+                    x.date,
+                    x.expected_readings,
+                    x.readings,
+                    x.valid_readings,
+                    x.missing_readings,
+                    x.duplicate_readings,
+                    x.is_short_day,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            counts,
+            vec![
+                (date(29), 46, 46, 46, 0, 0, false),
+                (date(30), 48, 47, 47, 1, 1, true),
+                (date(31), 48, 0, 0, 48, 0, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use log::debug;
+use tauri::State;
+
+use crate::{
+    analysis::degree_days::{degree_day_regression, heating_degree_days, DegreeDayRegression},
+    data::{
+        consumption::{
+            ConsumptionRepository, SqliteGasConsumptionRepository, ENERGY_CONSUMPTION_WH_ERROR_CODE,
+        },
+        weather::{SqliteWeatherRepository, WeatherRepository},
+    },
+    utils::{parse_iso_string_to_naive_date, WeatherSettings},
+    weather::{fetch_temperatures, parse_temperature_csv},
+    AppState,
+};
+
+use super::ApiError;
+
+#[tauri::command]
+pub fn get_weather_settings(app_state: State<'_, AppState>) -> Result<WeatherSettings, ApiError> {
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(WeatherSettings::from_app_settings(&app_settings)?)
+}
+
+#[tauri::command]
+pub fn store_weather_settings(
+    app_state: State<'_, AppState>,
+    settings: WeatherSettings,
+) -> Result<(), ApiError> {
+    if !settings.base_temperature_celsius.is_finite() {
+        return Err(ApiError::Custom(
+            "The base temperature must be a number".into(),
+        ));
+    }
+
+    let app_settings = app_state
+        .app_settings
+        .lock()
+        .map_err(|_| ApiError::MutexPoisonedError {
+            name: "app_settings".into(),
+        })?;
+
+    Ok(settings.save(&app_settings)?)
+}
+
+/// Imports temperatures from a CSV file, returning the number of readings imported.
+#[tauri::command]
+pub async fn import_weather_csv(
+    app_state: State<'_, AppState>,
+    path: String,
+) -> Result<usize, ApiError> {
+    debug!("import_weather_csv({}) called", path);
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let reading_count = tokio::task::spawn_blocking(move || -> Result<usize, ApiError> {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| ApiError::Custom(format!("Failed to read {}: {}", path, e)))?;
+
+        let readings = parse_temperature_csv(&text)?;
+        let reading_count = readings.len();
+
+        SqliteWeatherRepository::new(connection_pool_clone).insert(readings)?;
+
+        Ok(reading_count)
+    })
+    .await??;
+
+    Ok(reading_count)
+}
+
+/// Imports temperatures from the configured endpoint, returning the number of readings
+/// imported.
+#[tauri::command]
+pub async fn import_weather_from_endpoint(
+    app_state: State<'_, AppState>,
+) -> Result<usize, ApiError> {
+    let source_url = get_weather_settings(app_state.clone())?.source_url;
+
+    if source_url.is_empty() {
+        return Err(ApiError::Custom(
+            "No weather endpoint has been configured".into(),
+        ));
+    }
+
+    debug!("import_weather_from_endpoint({}) called", source_url);
+
+    let readings = fetch_temperatures(&source_url).await?;
+    let reading_count = readings.len();
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        SqliteWeatherRepository::new(connection_pool_clone).insert(readings)
+    })
+    .await??;
+
+    Ok(reading_count)
+}
+
+/// Fits daily gas consumption against heating degree days, splitting it into heating and hot
+/// water. Days without a temperature, or with missing readings, are left out.
+#[tauri::command]
+pub async fn get_gas_degree_day_regression(
+    app_state: State<'_, AppState>,
+    start_date: String,
+    end_date: String,
+) -> Result<Option<DegreeDayRegression>, ApiError> {
+    debug!(
+        "get_gas_degree_day_regression({}, {}) called",
+        start_date, end_date
+    );
+
+    let start = parse_iso_string_to_naive_date(&start_date)?;
+    let end = parse_iso_string_to_naive_date(&end_date)?;
+
+    let base_temperature = get_weather_settings(app_state.clone())?.base_temperature_celsius;
+
+    let connection_pool_clone = app_state.db_pool.clone();
+
+    let (daily_consumption, daily_temperature) = tokio::task::spawn_blocking(move || {
+        let consumption = SqliteGasConsumptionRepository::new(connection_pool_clone.clone())
+            .get_daily(start, end)?;
+        let temperature =
+            SqliteWeatherRepository::new(connection_pool_clone).get_daily_mean(start, end)?;
+
+        Ok::<_, ApiError>((consumption, temperature))
+    })
+    .await??;
+
+    let temperatures: HashMap<_, _> = daily_temperature.into_iter().collect();
+
+    let days: Vec<(f64, f64)> = daily_consumption
+        .iter()
+        .filter(|(_, value, _)| *value >= 0 && *value < ENERGY_CONSUMPTION_WH_ERROR_CODE)
+        .filter_map(|(date, value, _)| {
+            let temperature = temperatures.get(date)?;
+
+            Some((
+                heating_degree_days(*temperature, base_temperature),
+                *value as f64 / 1000.0,
+            ))
+        })
+        .collect();
+
+    Ok(degree_day_regression(&days, base_temperature))
+}
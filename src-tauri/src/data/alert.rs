@@ -0,0 +1,165 @@
+use chrono::{NaiveDateTime, NaiveTime};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{insert_into, SqliteConnection};
+use serde::{Deserialize, Serialize};
+
+use super::RepositoryError;
+use crate::db::SqliteConnectionPool;
+use crate::schema::{alert_rule, triggered_alert};
+
+#[derive(Serialize, Queryable, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRule {
+    pub alert_rule_id: i32,
+    pub name: String,
+    pub threshold_watts: f64,
+    pub duration_seconds: i32,
+    pub window_start: Option<NaiveTime>,
+    pub window_end: Option<NaiveTime>,
+    pub hysteresis_watts: f64,
+    pub is_enabled: bool,
+    pub notify: bool,
+}
+
+#[derive(Deserialize, Insertable, AsChangeset, Debug)]
+#[diesel(table_name = alert_rule)]
+#[diesel(treat_none_as_null = true)]
+#[serde(rename_all = "camelCase")]
+pub struct NewAlertRule {
+    pub name: String,
+    pub threshold_watts: f64,
+    pub duration_seconds: i32,
+    pub window_start: Option<NaiveTime>,
+    pub window_end: Option<NaiveTime>,
+    pub hysteresis_watts: f64,
+    pub is_enabled: bool,
+    pub notify: bool,
+}
+
+#[derive(Serialize, Queryable, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggeredAlertRecord {
+    pub triggered_alert_id: i32,
+    pub alert_rule_id: i32,
+    pub rule_name: String,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub exceeded_since: NaiveDateTime,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub triggered_at: NaiveDateTime,
+    pub power_watts: f64,
+    pub threshold_watts: f64,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = triggered_alert)]
+pub struct NewTriggeredAlert {
+    pub alert_rule_id: i32,
+    pub rule_name: String,
+    pub exceeded_since: NaiveDateTime,
+    pub triggered_at: NaiveDateTime,
+    pub power_watts: f64,
+    pub threshold_watts: f64,
+}
+
+type RepositoryResult<T> = Result<T, RepositoryError>;
+
+pub trait AlertRepository {
+    fn get_rules(&self) -> RepositoryResult<Vec<AlertRule>>;
+
+    fn insert_rule(&self, rule: NewAlertRule) -> RepositoryResult<AlertRule>;
+
+    fn update_rule(&self, alert_rule_id: i32, rule: NewAlertRule) -> RepositoryResult<AlertRule>;
+
+    fn delete_rule(&self, alert_rule_id: i32) -> RepositoryResult<()>;
+
+    fn insert_triggered_alert(&self, alert: NewTriggeredAlert) -> RepositoryResult<()>;
+
+    fn get_triggered_alerts(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> RepositoryResult<Vec<TriggeredAlertRecord>>;
+}
+
+pub struct SqliteAlertRepository {
+    connection_pool: SqliteConnectionPool,
+}
+
+impl SqliteAlertRepository {
+    pub fn new(connection_pool: SqliteConnectionPool) -> Self {
+        Self { connection_pool }
+    }
+
+    fn get_connection(
+        &self,
+    ) -> RepositoryResult<PooledConnection<ConnectionManager<SqliteConnection>>> {
+        Ok(self.connection_pool.get()?)
+    }
+}
+
+impl AlertRepository for SqliteAlertRepository {
+    fn get_rules(&self) -> RepositoryResult<Vec<AlertRule>> {
+        let mut conn = self.get_connection()?;
+
+        Ok(alert_rule::table
+            .order(alert_rule::alert_rule_id)
+            .load::<AlertRule>(&mut *conn)?)
+    }
+
+    fn insert_rule(&self, rule: NewAlertRule) -> RepositoryResult<AlertRule> {
+        let mut conn = self.get_connection()?;
+
+        Ok(conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            insert_into(alert_rule::table).values(&rule).execute(conn)?;
+
+            alert_rule::table
+                .order(alert_rule::alert_rule_id.desc())
+                .first::<AlertRule>(conn)
+        })?)
+    }
+
+    fn update_rule(&self, alert_rule_id: i32, rule: NewAlertRule) -> RepositoryResult<AlertRule> {
+        let mut conn = self.get_connection()?;
+
+        diesel::update(alert_rule::table.find(alert_rule_id))
+            .set(&rule)
+            .execute(&mut *conn)?;
+
+        Ok(alert_rule::table
+            .find(alert_rule_id)
+            .first::<AlertRule>(&mut *conn)?)
+    }
+
+    fn delete_rule(&self, alert_rule_id: i32) -> RepositoryResult<()> {
+        let mut conn = self.get_connection()?;
+
+        diesel::delete(alert_rule::table.find(alert_rule_id)).execute(&mut *conn)?;
+
+        Ok(())
+    }
+
+    fn insert_triggered_alert(&self, alert: NewTriggeredAlert) -> RepositoryResult<()> {
+        let mut conn = self.get_connection()?;
+
+        insert_into(triggered_alert::table)
+            .values(&alert)
+            .execute(&mut *conn)?;
+
+        Ok(())
+    }
+
+    fn get_triggered_alerts(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> RepositoryResult<Vec<TriggeredAlertRecord>> {
+        let mut conn = self.get_connection()?;
+
+        Ok(triggered_alert::table
+            .filter(triggered_alert::triggered_at.ge(start))
+            .filter(triggered_alert::triggered_at.lt(end))
+            .order(triggered_alert::triggered_at.desc())
+            .load::<TriggeredAlertRecord>(&mut *conn)?)
+    }
+}
@@ -0,0 +1,145 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::{insert_into, SqliteConnection};
+use serde::Serialize;
+
+use super::{Fuel, RepositoryError};
+use crate::dates::{naive_date_to_london_date_id, reporting_timezone};
+use crate::db::{reporting_timezone_for_writing, SqliteConnectionPool};
+use crate::schema::consumption_anomaly;
+
+#[derive(Serialize, Queryable, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnomalyRecord {
+    pub consumption_anomaly_id: i32,
+    pub fuel: String,
+    /// "day" or "interval"
+    pub granularity: String,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub period_start: NaiveDateTime,
+    pub london_date_id: i32,
+    pub expected_wh: f64,
+    pub actual_wh: i64,
+    pub z_score: f64,
+    #[serde(serialize_with = "crate::serde_utils::serialize_naive_as_utc")]
+    pub detected_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = consumption_anomaly)]
+pub struct NewAnomaly {
+    pub fuel: String,
+    pub granularity: String,
+    pub period_start: NaiveDateTime,
+    pub london_date_id: i32,
+    pub expected_wh: f64,
+    pub actual_wh: i64,
+    pub z_score: f64,
+    pub detected_at: NaiveDateTime,
+}
+
+type RepositoryResult<T> = Result<T, RepositoryError>;
+
+pub trait AnomalyRepository {
+    /// Replaces the anomalies for a fuel on London days from `start` up to `end`, so that
+    /// detecting again with a different threshold doesn't leave stale flags behind.
+    fn replace_anomalies(
+        &self,
+        fuel: Fuel,
+        start: NaiveDate,
+        end: NaiveDate,
+        anomalies: Vec<NewAnomaly>,
+    ) -> RepositoryResult<()>;
+
+    fn get_anomalies(
+        &self,
+        fuel: Option<Fuel>,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<AnomalyRecord>>;
+}
+
+pub struct SqliteAnomalyRepository {
+    connection_pool: SqliteConnectionPool,
+}
+
+impl SqliteAnomalyRepository {
+    pub fn new(connection_pool: SqliteConnectionPool) -> Self {
+        Self { connection_pool }
+    }
+
+    fn get_connection(
+        &self,
+    ) -> RepositoryResult<PooledConnection<ConnectionManager<SqliteConnection>>> {
+        Ok(self.connection_pool.get()?)
+    }
+}
+
+impl AnomalyRepository for SqliteAnomalyRepository {
+    fn replace_anomalies(
+        &self,
+        fuel: Fuel,
+        start: NaiveDate,
+        end: NaiveDate,
+        anomalies: Vec<NewAnomaly>,
+    ) -> RepositoryResult<()> {
+        let start_london_date_id = naive_date_to_london_date_id(&start);
+        let end_london_date_id = naive_date_to_london_date_id(&end);
+
+        self.get_connection()?
+            .immediate_transaction::<_, RepositoryError, _>(|conn| {
+                // The anomalies were detected in the app's timezone, so they're only stored if
+                // the dates haven't been recalculated for another one since
+                if reporting_timezone_for_writing(conn)? != reporting_timezone() {
+                    return Err(RepositoryError::ReportingTimezoneError(
+                        "it changed while detecting anomalies".into(),
+                    ));
+                }
+
+                diesel::delete(
+                    consumption_anomaly::table
+                        .filter(consumption_anomaly::fuel.eq(fuel.as_str()))
+                        .filter(consumption_anomaly::london_date_id.ge(start_london_date_id))
+                        .filter(consumption_anomaly::london_date_id.lt(end_london_date_id)),
+                )
+                .execute(conn)?;
+
+                if !anomalies.is_empty() {
+                    insert_into(consumption_anomaly::table)
+                        .values(&anomalies)
+                        .execute(conn)?;
+                }
+
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+
+    fn get_anomalies(
+        &self,
+        fuel: Option<Fuel>,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<AnomalyRecord>> {
+        let mut conn = self.get_connection()?;
+
+        let mut query = consumption_anomaly::table
+            .filter(consumption_anomaly::london_date_id.ge(naive_date_to_london_date_id(&start)))
+            .filter(consumption_anomaly::london_date_id.lt(naive_date_to_london_date_id(&end)))
+            .into_boxed();
+
+        if let Some(fuel) = fuel {
+            query = query.filter(consumption_anomaly::fuel.eq(fuel.as_str()));
+        }
+
+        Ok(query
+            .order((
+                consumption_anomaly::period_start,
+                consumption_anomaly::fuel,
+                consumption_anomaly::granularity,
+            ))
+            .load::<AnomalyRecord>(&mut *conn)?)
+    }
+}
@@ -1,25 +1,31 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
 use diesel::dsl::sql;
-use diesel::insert_into;
+use diesel::prelude::*;
 use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
 use diesel::r2d2::PooledConnection;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Bool, Double, Integer, Text, Timestamp};
+use diesel::sqlite::Sqlite;
 use diesel::SqliteConnection;
-use diesel::{prelude::*, upsert::excluded};
 use log::error;
 use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
+use serde::Deserialize;
 
-use crate::db::SqliteConnectionPool;
-use crate::schema::{electricity_consumption, gas_consumption};
-use crate::utils::london_date_id_to_naive_date;
-use crate::utils::{
-    london_midnight_as_utc, naive_date_to_london_date_id, utc_timestamp_to_london_date_id,
+use crate::dates::london_date_id_to_naive_date;
+use crate::dates::{
+    london_midnight_as_utc, naive_date_to_london_date_id, reporting_timezone, utc_offset_periods,
+    utc_timestamp_to_london_date_id_in,
 };
+use crate::db::{reporting_timezone_for_writing, SqliteConnectionPool};
 
-use super::RepositoryError;
+use super::quality::{quality_refresh_range, refresh_quality_flags, QualityFlag};
+use super::rollup::{refresh_rollups, LONDON_MONTH_ID_SQL};
+use super::{Fuel, RepositoryError};
 
-const ENERGY_CONSUMPTION_WH_ERROR_CODE: i64 = 16777215i64;
+pub const ENERGY_CONSUMPTION_WH_ERROR_CODE: i64 = 16777215i64;
 
 const KWH_TO_WH_SCALE: Decimal = Decimal::ONE_THOUSAND;
 
@@ -33,20 +39,9 @@ pub struct GasConsumptionValue {
     pub value: Decimal,
 }
 
-#[derive(Insertable)]
-#[diesel(table_name = electricity_consumption)]
-struct NewElectricityConsumption {
+struct NewConsumption {
     timestamp: NaiveDateTime,
     energy_consumption_wh: i64,
-    london_date_id: i32,
-}
-
-#[derive(Insertable)]
-#[diesel(table_name = gas_consumption)]
-struct NewGasConsumption {
-    timestamp: NaiveDateTime,
-    energy_consumption_wh: i64,
-    london_date_id: i32,
 }
 
 #[derive(Queryable)]
@@ -55,6 +50,8 @@ pub struct ElectricityConsumptionRecord {
     pub timestamp: NaiveDateTime,
     pub energy_consumption_wh: i64,
     pub london_date_id: Option<i32>,
+    pub is_provisional: bool,
+    pub quality_flag: i32,
 }
 
 #[derive(Queryable)]
@@ -63,6 +60,41 @@ pub struct GasConsumptionRecord {
     pub timestamp: NaiveDateTime,
     pub energy_consumption_wh: i64,
     pub london_date_id: Option<i32>,
+    pub is_provisional: bool,
+    pub quality_flag: i32,
+}
+
+/// How a consumption profile is split into separate curves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProfileSplit {
+    #[default]
+    None,
+    /// Separate curves for weekdays and weekends
+    DayType,
+    /// A separate curve for each calendar month
+    Month,
+}
+
+/// The consumption in one half hour of the London day, across every day in a range.
+#[derive(QueryableByName, Debug)]
+pub struct ConsumptionProfileRecord {
+    /// "all", "weekday", "weekend" or the month from "01" to "12", depending on the split
+    #[diesel(sql_type = Text)]
+    pub profile_group: String,
+    /// From 0 for 00:00 to 47 for 23:30
+    #[diesel(sql_type = Integer)]
+    pub half_hour: i32,
+    #[diesel(sql_type = BigInt)]
+    pub sample_count: i64,
+    #[diesel(sql_type = Double)]
+    pub average_wh: f64,
+    #[diesel(sql_type = BigInt)]
+    pub p10_wh: i64,
+    #[diesel(sql_type = BigInt)]
+    pub p50_wh: i64,
+    #[diesel(sql_type = BigInt)]
+    pub p90_wh: i64,
 }
 
 type RepositoryResult<T> = Result<T, RepositoryError>;
@@ -70,19 +102,282 @@ type RepositoryResult<T> = Result<T, RepositoryError>;
 pub trait ConsumptionRepository<T, U> {
     fn insert(&self, records: Vec<T>) -> RepositoryResult<()>;
 
+    /// Inserts values derived from live readings. These never overwrite readings that were
+    /// inserted by `insert`, whereas `insert` always replaces any provisional value.
+    fn insert_provisional(&self, records: Vec<T>) -> RepositoryResult<()>;
+
     fn get_raw(&self, start: NaiveDate, end: NaiveDate) -> RepositoryResult<Vec<U>>;
 
+    /// Returns the total consumption per day, and whether any of it is provisional.
     fn get_daily(
         &self,
         start: NaiveDate,
         end: NaiveDate,
-    ) -> RepositoryResult<Vec<(NaiveDate, i64)>>;
+    ) -> RepositoryResult<Vec<(NaiveDate, i64, bool)>>;
 
     fn get_monthly(
         &self,
         start: NaiveDate,
         end: NaiveDate,
     ) -> RepositoryResult<Vec<(NaiveDate, i64)>>;
+
+    /// Returns the total consumption per ISO week, keyed by the Monday each week starts on.
+    fn get_weekly(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, i64)>>;
+
+    fn get_yearly(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, i64)>>;
+
+    /// Returns the average and the 10th, 50th and 90th percentile consumption for each half hour
+    /// of the London day.
+    fn get_profile(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        split: ProfileSplit,
+    ) -> RepositoryResult<Vec<ConsumptionProfileRecord>>;
+}
+
+/// The London date id of the Monday that starts the ISO week containing `london_date_id`.
+const LONDON_WEEK_ID_SQL: &str = r#"
+    CAST(strftime('%Y%m%d', date(
+        printf('%04d-%02d-%02d', london_date_id / 10000, london_date_id / 100 % 100, london_date_id % 100),
+        '-6 days',
+        'weekday 1'
+    )) AS INTEGER)"#;
+
+const LONDON_YEAR_ID_SQL: &str = "london_date_id - (london_date_id % 10000) + 101";
+
+/// Converts the UTC `timestamp` column to local time in `timezone`, for readings from `start`
+/// up to `end`. SQLite has no timezone data, so the offsets come from `utc_offset_periods`.
+pub fn london_timestamp_sql(timezone: Tz, start: NaiveDateTime, end: NaiveDateTime) -> String {
+    let periods = utc_offset_periods(timezone, start, end);
+
+    let Some((last, earlier)) = periods.split_last() else {
+        return "timestamp".to_string();
+    };
+
+    let cases: String = earlier
+        .iter()
+        .map(|period| {
+            format!(
+                "WHEN timestamp < '{}' THEN '{:+} minutes' ",
+                period.end.format("%Y-%m-%d %H:%M:%S"),
+                period.offset_minutes
+            )
+        })
+        .collect();
+
+    format!(
+        "datetime(timestamp, CASE {}ELSE '{:+} minutes' END)",
+        cases, last.offset_minutes
+    )
+}
+
+fn get_profile_from_table(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    split: ProfileSplit,
+) -> RepositoryResult<Vec<ConsumptionProfileRecord>> {
+    let profile_group = match split {
+        ProfileSplit::None => "'all'",
+        ProfileSplit::DayType => {
+            "CASE WHEN strftime('%w', london_timestamp) IN ('0', '6') THEN 'weekend' ELSE 'weekday' END"
+        }
+        ProfileSplit::Month => "strftime('%m', london_timestamp)",
+    };
+
+    let start_utc = london_midnight_as_utc(&start);
+    let end_utc = london_midnight_as_utc(&end);
+    let london_timestamp = london_timestamp_sql(reporting_timezone(), start_utc, end_utc);
+
+    // Percentiles use the nearest-rank method
+    let query = format!(
+        r#"
+            WITH london_consumption AS (
+                SELECT
+                    energy_consumption_wh,
+                    {london_timestamp} AS london_timestamp
+                FROM {table_name}
+                WHERE timestamp >= ? AND timestamp < ? AND quality_flag = ?
+            ),
+            grouped_consumption AS (
+                SELECT
+                    {profile_group} AS profile_group,
+                    CAST(strftime('%H', london_timestamp) AS INTEGER) * 2
+                        + CAST(strftime('%M', london_timestamp) AS INTEGER) / 30 AS half_hour,
+                    energy_consumption_wh
+                FROM london_consumption
+            ),
+            ranked_consumption AS (
+                SELECT
+                    profile_group,
+                    half_hour,
+                    energy_consumption_wh,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY profile_group, half_hour ORDER BY energy_consumption_wh
+                    ) AS sample_rank,
+                    COUNT(*) OVER (PARTITION BY profile_group, half_hour) AS sample_count
+                FROM grouped_consumption
+            )
+            SELECT
+                profile_group,
+                half_hour,
+                MAX(sample_count) AS sample_count,
+                AVG(energy_consumption_wh) AS average_wh,
+                MIN(CASE WHEN sample_rank >= 0.1 * sample_count THEN energy_consumption_wh END) AS p10_wh,
+                MIN(CASE WHEN sample_rank >= 0.5 * sample_count THEN energy_consumption_wh END) AS p50_wh,
+                MIN(CASE WHEN sample_rank >= 0.9 * sample_count THEN energy_consumption_wh END) AS p90_wh
+            FROM ranked_consumption
+            GROUP BY profile_group, half_hour
+            ORDER BY profile_group, half_hour
+        "#
+    );
+
+    Ok(sql_query(query)
+        .bind::<Timestamp, _>(start_utc)
+        .bind::<Timestamp, _>(end_utc)
+        .bind::<Integer, _>(QualityFlag::Valid as i32)
+        .load::<ConsumptionProfileRecord>(conn)?)
+}
+
+/// The most readings inserted by one statement, well within SQLite's limit on the number of
+/// bound parameters.
+pub const INSERT_BATCH_SIZE: usize = 500;
+
+/// Inserts or updates readings with multi-row statements, then updates their quality flags and
+/// the rollups. Call this within a transaction, with the timezone from
+/// [reporting_timezone_for_writing] in the same transaction.
+///
+/// Provisional readings never overwrite readings that aren't provisional, whereas other
+/// readings always replace any provisional value.
+fn upsert_consumption(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    records: &[NewConsumption],
+    is_provisional: bool,
+    timezone: Tz,
+    batch_size: usize,
+) -> Result<(), diesel::result::Error> {
+    let table_name = fuel.consumption_table();
+
+    let on_conflict = if is_provisional {
+        format!(
+            "SET energy_consumption_wh = excluded.energy_consumption_wh WHERE {table_name}.is_provisional"
+        )
+    } else {
+        "SET energy_consumption_wh = excluded.energy_consumption_wh, is_provisional = FALSE"
+            .to_string()
+    };
+
+    for batch in records.chunks(batch_size.max(1)) {
+        let values = vec!["(?, ?, ?, ?)"; batch.len()].join(", ");
+
+        let mut query = sql_query(format!(
+            r#"
+                INSERT INTO {table_name}
+                    (timestamp, energy_consumption_wh, london_date_id, is_provisional)
+                VALUES {values}
+                ON CONFLICT (timestamp) DO UPDATE {on_conflict}
+            "#
+        ))
+        .into_boxed::<Sqlite>();
+
+        for record in batch {
+            query = query
+                .bind::<Timestamp, _>(record.timestamp)
+                .bind::<BigInt, _>(record.energy_consumption_wh)
+                .bind::<Integer, _>(utc_timestamp_to_london_date_id_in(
+                    timezone,
+                    &record.timestamp,
+                ))
+                .bind::<Bool, _>(is_provisional);
+        }
+
+        query.execute(conn)?;
+    }
+
+    if let Some((start, end)) = quality_refresh_range(records.iter().map(|x| x.timestamp)) {
+        refresh_quality_flags(conn, fuel, start, end)?;
+        refresh_rollups(
+            conn,
+            fuel,
+            utc_timestamp_to_london_date_id_in(timezone, &start),
+            utc_timestamp_to_london_date_id_in(timezone, &end),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct MonthlyConsumptionRecord {
+    #[diesel(sql_type = Integer)]
+    london_month_id: i32,
+    #[diesel(sql_type = BigInt)]
+    energy_consumption_wh: i64,
+}
+
+/// Returns the monthly totals, using the monthly rollup for whole months in the range and the
+/// daily rollup for any partial months at either end.
+fn get_monthly_from_rollups(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
+    let first_whole_month = match start.day() {
+        1 => start,
+        _ => start.with_day(1).expect("The first of the month to exist") + Months::new(1),
+    };
+    let end_of_whole_months = end.with_day(1).expect("The first of the month to exist");
+
+    let fuel_name = fuel.as_str();
+
+    let query = format!(
+        r#"
+            SELECT london_month_id, energy_consumption_wh
+            FROM {fuel_name}_monthly_rollup
+            WHERE london_month_id >= ? AND london_month_id < ?
+            UNION ALL
+            SELECT {LONDON_MONTH_ID_SQL} AS london_month_id, SUM(energy_consumption_wh)
+            FROM {fuel_name}_daily_rollup
+            WHERE london_date_id >= ? AND london_date_id < ?
+                AND (london_date_id < ? OR london_date_id >= ?)
+            GROUP BY {LONDON_MONTH_ID_SQL}
+            ORDER BY london_month_id
+        "#
+    );
+
+    let first_whole_month_id = naive_date_to_london_date_id(&first_whole_month);
+    let end_of_whole_months_id = naive_date_to_london_date_id(&end_of_whole_months);
+
+    let monthly_consumption = sql_query(query)
+        .bind::<Integer, _>(first_whole_month_id)
+        .bind::<Integer, _>(end_of_whole_months_id)
+        .bind::<Integer, _>(naive_date_to_london_date_id(&start))
+        .bind::<Integer, _>(naive_date_to_london_date_id(&end))
+        .bind::<Integer, _>(first_whole_month_id)
+        .bind::<Integer, _>(end_of_whole_months_id)
+        .load::<MonthlyConsumptionRecord>(conn)?;
+
+    Ok(monthly_consumption
+        .iter()
+        .map(|x| {
+            (
+                london_date_id_to_naive_date(x.london_month_id),
+                x.energy_consumption_wh,
+            )
+        })
+        .collect())
 }
 
 pub struct SqliteElectricityConsumptionRepository {
@@ -107,30 +402,57 @@ impl ConsumptionRepository<ElectricityConsumptionValue, ElectricityConsumptionRe
     fn insert(&self, records: Vec<ElectricityConsumptionValue>) -> RepositoryResult<()> {
         let new_records: Vec<_> = records
             .into_iter()
-            .map(|x| NewElectricityConsumption {
+            .map(|x| NewConsumption {
                 timestamp: x.timestamp,
                 energy_consumption_wh: (x.value * KWH_TO_WH_SCALE)
                     .to_i64()
                     .expect("Electricity consumption to fit in 64-bit integer"),
-                london_date_id: utc_timestamp_to_london_date_id(&x.timestamp),
             })
             .collect();
 
         self.get_connection()?
-            .transaction::<_, diesel::result::Error, _>(|conn| {
-                for record in new_records {
-                    insert_into(electricity_consumption::table)
-                        .values(&record)
-                        .on_conflict(electricity_consumption::timestamp)
-                        .do_update()
-                        .set(
-                            electricity_consumption::energy_consumption_wh
-                                .eq(excluded(electricity_consumption::energy_consumption_wh)),
-                        ) // Use the correct field reference
-                        .execute(conn)?;
-                }
+            .immediate_transaction::<_, RepositoryError, _>(|conn| {
+                let timezone = reporting_timezone_for_writing(conn)?;
 
-                Ok(())
+                Ok(upsert_consumption(
+                    conn,
+                    Fuel::Electricity,
+                    &new_records,
+                    false,
+                    timezone,
+                    INSERT_BATCH_SIZE,
+                )?)
+            })?;
+
+        Ok(())
+    }
+
+    fn insert_provisional(
+        &self,
+        records: Vec<ElectricityConsumptionValue>,
+    ) -> RepositoryResult<()> {
+        let new_records: Vec<_> = records
+            .into_iter()
+            .map(|x| NewConsumption {
+                timestamp: x.timestamp,
+                energy_consumption_wh: (x.value * KWH_TO_WH_SCALE)
+                    .to_i64()
+                    .expect("Electricity consumption to fit in 64-bit integer"),
+            })
+            .collect();
+
+        self.get_connection()?
+            .immediate_transaction::<_, RepositoryError, _>(|conn| {
+                let timezone = reporting_timezone_for_writing(conn)?;
+
+                Ok(upsert_consumption(
+                    conn,
+                    Fuel::Electricity,
+                    &new_records,
+                    true,
+                    timezone,
+                    INSERT_BATCH_SIZE,
+                )?)
             })?;
 
         Ok(())
@@ -148,6 +470,7 @@ impl ConsumptionRepository<ElectricityConsumptionValue, ElectricityConsumptionRe
         Ok(electricity_consumption
             .filter(timestamp.ge(london_midnight_as_utc(&start)))
             .filter(timestamp.lt(london_midnight_as_utc(&end)))
+            .filter(quality_flag.eq(QualityFlag::Valid as i32))
             .load::<ElectricityConsumptionRecord>(&mut *conn)?)
     }
 
@@ -155,27 +478,66 @@ impl ConsumptionRepository<ElectricityConsumptionValue, ElectricityConsumptionRe
         &self,
         start: NaiveDate,
         end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, i64, bool)>> {
+        use crate::schema::electricity_daily_rollup::dsl::*;
+
+        let mut conn = self.get_connection()?;
+
+        let start_london_date_id = naive_date_to_london_date_id(&start);
+        let end_london_date_id = naive_date_to_london_date_id(&end);
+
+        let daily_consumption = electricity_daily_rollup
+            .filter(london_date_id.ge(start_london_date_id))
+            .filter(london_date_id.lt(end_london_date_id))
+            .select((london_date_id, energy_consumption_wh, is_provisional))
+            .order(london_date_id)
+            .load::<(i32, i64, bool)>(&mut *conn)?;
+
+        Ok(daily_consumption
+            .iter()
+            .map(|(date_id, energy, provisional)| {
+                let date = london_date_id_to_naive_date(*date_id);
+                (date, *energy, *provisional)
+            })
+            .collect())
+    }
+
+    fn get_monthly(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
     ) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
-        use crate::schema::electricity_consumption::dsl::*;
+        let mut conn = self.get_connection()?;
+
+        get_monthly_from_rollups(&mut conn, Fuel::Electricity, start, end)
+    }
+
+    fn get_weekly(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
+        use crate::schema::electricity_daily_rollup::dsl::*;
 
         let mut conn = self.get_connection()?;
 
         let start_london_date_id = naive_date_to_london_date_id(&start);
         let end_london_date_id = naive_date_to_london_date_id(&end);
 
-        let daily_consumption = electricity_consumption
-            .filter(london_date_id.is_not_null())
+        let london_week_id = sql::<diesel::sql_types::Integer>(LONDON_WEEK_ID_SQL);
+
+        let weekly_consumption = electricity_daily_rollup
             .filter(london_date_id.ge(start_london_date_id))
             .filter(london_date_id.lt(end_london_date_id))
             .select((
-                london_date_id.assume_not_null(),
+                london_week_id.clone(),
                 sql::<diesel::sql_types::BigInt>("COALESCE(SUM(energy_consumption_wh), 0)"),
             ))
-            .group_by(london_date_id)
-            .order(london_date_id)
+            .group_by(london_week_id.clone())
+            .order(london_week_id)
             .load::<(i32, i64)>(&mut *conn)?;
 
-        Ok(daily_consumption
+        Ok(weekly_consumption
             .iter()
             .map(|(date_id, energy)| {
                 let date = london_date_id_to_naive_date(*date_id);
@@ -184,34 +546,32 @@ impl ConsumptionRepository<ElectricityConsumptionValue, ElectricityConsumptionRe
             .collect())
     }
 
-    fn get_monthly(
+    fn get_yearly(
         &self,
         start: NaiveDate,
         end: NaiveDate,
     ) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
-        use crate::schema::electricity_consumption::dsl::*;
+        use crate::schema::electricity_daily_rollup::dsl::*;
 
         let mut conn = self.get_connection()?;
 
         let start_london_date_id = naive_date_to_london_date_id(&start);
         let end_london_date_id = naive_date_to_london_date_id(&end);
 
-        let london_month_id =
-            sql::<diesel::sql_types::Integer>("london_date_id - (london_date_id % 100) + 1");
+        let london_year_id = sql::<diesel::sql_types::Integer>(LONDON_YEAR_ID_SQL);
 
-        let monthly_consumption = electricity_consumption
-            .filter(london_date_id.is_not_null())
+        let yearly_consumption = electricity_daily_rollup
             .filter(london_date_id.ge(start_london_date_id))
             .filter(london_date_id.lt(end_london_date_id))
             .select((
-                london_month_id.clone().assume_not_null(),
+                london_year_id.clone(),
                 sql::<diesel::sql_types::BigInt>("COALESCE(SUM(energy_consumption_wh), 0)"),
             ))
-            .group_by(london_month_id.clone())
-            .order(london_month_id)
+            .group_by(london_year_id.clone())
+            .order(london_year_id)
             .load::<(i32, i64)>(&mut *conn)?;
 
-        Ok(monthly_consumption
+        Ok(yearly_consumption
             .iter()
             .map(|(date_id, energy)| {
                 let date = london_date_id_to_naive_date(*date_id);
@@ -219,6 +579,17 @@ impl ConsumptionRepository<ElectricityConsumptionValue, ElectricityConsumptionRe
             })
             .collect())
     }
+
+    fn get_profile(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        split: ProfileSplit,
+    ) -> RepositoryResult<Vec<ConsumptionProfileRecord>> {
+        let mut conn = self.get_connection()?;
+
+        get_profile_from_table(&mut conn, "electricity_consumption", start, end, split)
+    }
 }
 
 pub struct SqliteGasConsumptionRepository {
@@ -243,34 +614,65 @@ impl ConsumptionRepository<GasConsumptionValue, GasConsumptionRecord>
     fn insert(&self, records: Vec<GasConsumptionValue>) -> RepositoryResult<()> {
         let new_records: Vec<_> = records
             .into_iter()
-            .map(|x| NewGasConsumption {
+            .map(|x| NewConsumption {
                 timestamp: x.timestamp,
                 energy_consumption_wh: (x.value * KWH_TO_WH_SCALE)
                     .to_i64()
                     .expect("Gas consumption to fit in i64"),
-                london_date_id: utc_timestamp_to_london_date_id(&x.timestamp),
             })
             .collect();
 
         self.get_connection()?
-            .transaction::<_, diesel::result::Error, _>(|conn| {
-                for record in new_records {
-                    insert_into(gas_consumption::table)
-                        .values(&record)
-                        .on_conflict(gas_consumption::timestamp)
-                        .do_update()
-                        .set(
-                            gas_consumption::energy_consumption_wh
-                                .eq(excluded(gas_consumption::energy_consumption_wh)),
-                        )
-                        .execute(conn)
-                        .map_err(|e| {
-                            error!("Error inserting new gas consumption entry: {:?}", e);
-                            e
-                        })?;
-                }
+            .immediate_transaction::<_, RepositoryError, _>(|conn| {
+                let timezone = reporting_timezone_for_writing(conn)?;
+
+                Ok(upsert_consumption(
+                    conn,
+                    Fuel::Gas,
+                    &new_records,
+                    false,
+                    timezone,
+                    INSERT_BATCH_SIZE,
+                )?)
+            })
+            .map_err(|e| {
+                error!("Error inserting new gas consumption entries: {:?}", e);
+                e
+            })?;
+
+        Ok(())
+    }
+
+    fn insert_provisional(&self, records: Vec<GasConsumptionValue>) -> RepositoryResult<()> {
+        let new_records: Vec<_> = records
+            .into_iter()
+            .map(|x| NewConsumption {
+                timestamp: x.timestamp,
+                energy_consumption_wh: (x.value * KWH_TO_WH_SCALE)
+                    .to_i64()
+                    .expect("Gas consumption to fit in i64"),
+            })
+            .collect();
+
+        self.get_connection()?
+            .immediate_transaction::<_, RepositoryError, _>(|conn| {
+                let timezone = reporting_timezone_for_writing(conn)?;
 
-                Ok(())
+                Ok(upsert_consumption(
+                    conn,
+                    Fuel::Gas,
+                    &new_records,
+                    true,
+                    timezone,
+                    INSERT_BATCH_SIZE,
+                )?)
+            })
+            .map_err(|e| {
+                error!(
+                    "Error inserting provisional gas consumption entries: {:?}",
+                    e
+                );
+                e
             })?;
 
         Ok(())
@@ -288,7 +690,7 @@ impl ConsumptionRepository<GasConsumptionValue, GasConsumptionRecord>
         Ok(gas_consumption
             .filter(timestamp.ge(london_midnight_as_utc(&start)))
             .filter(timestamp.lt(london_midnight_as_utc(&end)))
-            .filter(energy_consumption_wh.ne(ENERGY_CONSUMPTION_WH_ERROR_CODE))
+            .filter(quality_flag.eq(QualityFlag::Valid as i32))
             .load::<GasConsumptionRecord>(&mut *conn)?)
     }
 
@@ -296,27 +698,66 @@ impl ConsumptionRepository<GasConsumptionValue, GasConsumptionRecord>
         &self,
         start: NaiveDate,
         end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, i64, bool)>> {
+        use crate::schema::gas_daily_rollup::dsl::*;
+
+        let mut conn = self.get_connection()?;
+
+        let start_london_date_id = naive_date_to_london_date_id(&start);
+        let end_london_date_id = naive_date_to_london_date_id(&end);
+
+        let daily_consumption = gas_daily_rollup
+            .filter(london_date_id.ge(start_london_date_id))
+            .filter(london_date_id.lt(end_london_date_id))
+            .select((london_date_id, energy_consumption_wh, is_provisional))
+            .order(london_date_id)
+            .load::<(i32, i64, bool)>(&mut *conn)?;
+
+        Ok(daily_consumption
+            .iter()
+            .map(|(date_id, energy, provisional)| {
+                let date = london_date_id_to_naive_date(*date_id);
+                (date, *energy, *provisional)
+            })
+            .collect())
+    }
+
+    fn get_monthly(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
     ) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
-        use crate::schema::gas_consumption::dsl::*;
+        let mut conn = self.get_connection()?;
+
+        get_monthly_from_rollups(&mut conn, Fuel::Gas, start, end)
+    }
+
+    fn get_weekly(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
+        use crate::schema::gas_daily_rollup::dsl::*;
 
         let mut conn = self.get_connection()?;
 
         let start_london_date_id = naive_date_to_london_date_id(&start);
         let end_london_date_id = naive_date_to_london_date_id(&end);
 
-        let daily_consumption = gas_consumption
-            .filter(london_date_id.is_not_null())
+        let london_week_id = sql::<diesel::sql_types::Integer>(LONDON_WEEK_ID_SQL);
+
+        let weekly_consumption = gas_daily_rollup
             .filter(london_date_id.ge(start_london_date_id))
             .filter(london_date_id.lt(end_london_date_id))
             .select((
-                london_date_id.assume_not_null(),
+                london_week_id.clone(),
                 sql::<diesel::sql_types::BigInt>("COALESCE(SUM(energy_consumption_wh), 0)"),
             ))
-            .group_by(london_date_id)
-            .order(london_date_id)
+            .group_by(london_week_id.clone())
+            .order(london_week_id)
             .load::<(i32, i64)>(&mut *conn)?;
 
-        Ok(daily_consumption
+        Ok(weekly_consumption
             .iter()
             .map(|(date_id, energy)| {
                 let date = london_date_id_to_naive_date(*date_id);
@@ -325,34 +766,32 @@ impl ConsumptionRepository<GasConsumptionValue, GasConsumptionRecord>
             .collect())
     }
 
-    fn get_monthly(
+    fn get_yearly(
         &self,
         start: NaiveDate,
         end: NaiveDate,
     ) -> RepositoryResult<Vec<(NaiveDate, i64)>> {
-        use crate::schema::gas_consumption::dsl::*;
+        use crate::schema::gas_daily_rollup::dsl::*;
 
         let mut conn = self.get_connection()?;
 
         let start_london_date_id = naive_date_to_london_date_id(&start);
         let end_london_date_id = naive_date_to_london_date_id(&end);
 
-        let london_month_id =
-            sql::<diesel::sql_types::Integer>("london_date_id - (london_date_id % 100) + 1");
+        let london_year_id = sql::<diesel::sql_types::Integer>(LONDON_YEAR_ID_SQL);
 
-        let monthly_consumption = gas_consumption
-            .filter(london_date_id.is_not_null())
+        let yearly_consumption = gas_daily_rollup
             .filter(london_date_id.ge(start_london_date_id))
             .filter(london_date_id.lt(end_london_date_id))
             .select((
-                london_month_id.clone().assume_not_null(),
+                london_year_id.clone(),
                 sql::<diesel::sql_types::BigInt>("COALESCE(SUM(energy_consumption_wh), 0)"),
             ))
-            .group_by(london_month_id.clone())
-            .order(london_month_id)
+            .group_by(london_year_id.clone())
+            .order(london_year_id)
             .load::<(i32, i64)>(&mut *conn)?;
 
-        Ok(monthly_consumption
+        Ok(yearly_consumption
             .iter()
             .map(|(date_id, energy)| {
                 let date = london_date_id_to_naive_date(*date_id);
@@ -360,4 +799,361 @@ impl ConsumptionRepository<GasConsumptionValue, GasConsumptionRecord>
             })
             .collect())
     }
+
+    fn get_profile(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        split: ProfileSplit,
+    ) -> RepositoryResult<Vec<ConsumptionProfileRecord>> {
+        let mut conn = self.get_connection()?;
+
+        get_profile_from_table(&mut conn, "gas_consumption", start, end, split)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::dates::DEFAULT_REPORTING_TIMEZONE;
+    use crate::db::TestDatabase;
+    use chrono::Duration;
+
+    /// Half-hourly readings of 0.25 kWh for 2025, which has 17,520 half hours.
+    fn year_of_records() -> Vec<NewConsumption> {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        (0..17_520)
+            .map(|i| {
+                let timestamp = start + Duration::minutes(30 * i);
+
+                NewConsumption {
+                    timestamp,
+                    energy_consumption_wh: 250,
+                }
+            })
+            .collect()
+    }
+
+    fn time_insert(records: &[NewConsumption], batch_size: usize) -> std::time::Duration {
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
+
+        let started = Instant::now();
+
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            upsert_consumption(
+                conn,
+                Fuel::Electricity,
+                records,
+                false,
+                DEFAULT_REPORTING_TIMEZONE,
+                batch_size,
+            )
+        })
+        .unwrap();
+
+        started.elapsed()
+    }
+
+    #[test]
+    fn test_insert_a_year_of_readings() {
+        let database = TestDatabase::new();
+        let repository = SqliteElectricityConsumptionRepository::new(database.pool.clone());
+
+        let values: Vec<_> = year_of_records()
+            .iter()
+            .map(|x| ElectricityConsumptionValue {
+                timestamp: x.timestamp,
+                value: Decimal::new(250, 3),
+            })
+            .collect();
+
+        repository.insert(values).unwrap();
+
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+
+        // Midnight on January 1st UTC is also midnight in London, so the year is complete
+        let daily = repository.get_daily(start, end).unwrap();
+        let monthly = repository.get_monthly(start, end).unwrap();
+
+        assert_eq!(daily.len(), 365);
+        assert_eq!(daily.iter().map(|x| x.1).sum::<i64>(), 17_520 * 250);
+        assert_eq!(monthly.len(), 12);
+        assert_eq!(monthly.iter().map(|x| x.1).sum::<i64>(), 17_520 * 250);
+
+        // The clocks went forward on March 30th and back on October 26th
+        let day_total = |date: NaiveDate| daily.iter().find(|x| x.0 == date).unwrap().1;
+
+        assert_eq!(
+            day_total(NaiveDate::from_ymd_opt(2025, 3, 30).unwrap()),
+            46 * 250
+        );
+        assert_eq!(
+            day_total(NaiveDate::from_ymd_opt(2025, 10, 26).unwrap()),
+            50 * 250
+        );
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn electricity_repository_with(
+        database: &TestDatabase,
+        readings: &[(NaiveDateTime, i64)],
+    ) -> SqliteElectricityConsumptionRepository {
+        let repository = SqliteElectricityConsumptionRepository::new(database.pool.clone());
+
+        repository
+            .insert(
+                readings
+                    .iter()
+                    .map(|(timestamp, wh)| ElectricityConsumptionValue {
+                        timestamp: *timestamp,
+                        value: Decimal::new(*wh, 3),
+                    })
+                    .collect(),
+            )
+            .unwrap();
+
+        repository
+    }
+
+    fn profile_points(records: &[ConsumptionProfileRecord]) -> Vec<(&str, i32, i64)> {
+        records
+            .iter()
+            .map(|x| (x.profile_group.as_str(), x.half_hour, x.sample_count))
+            .collect()
+    }
+
+    #[test]
+    fn test_profile_percentiles_use_nearest_rank() {
+        let database = TestDatabase::new();
+
+        // Noon on ten days in January, when London is on GMT, of 100 Wh to 1,000 Wh
+        let readings: Vec<_> = (1..=10)
+            .map(|day| (utc(2025, 1, day, 12, 0), day as i64 * 100))
+            .collect();
+
+        let repository = electricity_repository_with(&database, &readings);
+
+        let profile = repository
+            .get_profile(
+                NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 1, 11).unwrap(),
+                ProfileSplit::None,
+            )
+            .unwrap();
+
+        assert_eq!(profile_points(&profile), vec![("all", 24, 10)]);
+        assert_eq!(profile[0].average_wh, 550.0);
+        assert_eq!(profile[0].p10_wh, 100);
+        assert_eq!(profile[0].p50_wh, 500);
+        assert_eq!(profile[0].p90_wh, 900);
+    }
+
+    #[test]
+    fn test_profile_uses_london_time_across_a_clock_change() {
+        let database = TestDatabase::new();
+
+        // The clocks went forward at 01:00 UTC on Sunday March 30th 2025
+        let repository = electricity_repository_with(
+            &database,
+            &[
+                // 23:30 on Friday the 28th, before the range
+                (utc(2025, 3, 28, 23, 30), 100),
+                // Noon GMT on Saturday
+                (utc(2025, 3, 29, 12, 0), 100),
+                // 00:30 GMT and then 02:00 BST on Sunday
+                (utc(2025, 3, 30, 0, 30), 100),
+                (utc(2025, 3, 30, 1, 0), 100),
+                // 13:00 BST on Monday
+                (utc(2025, 3, 31, 12, 0), 100),
+                // 00:00 BST on Tuesday, after the range
+                (utc(2025, 3, 31, 23, 0), 100),
+            ],
+        );
+
+        let profile = repository
+            .get_profile(
+                NaiveDate::from_ymd_opt(2025, 3, 29).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 4, 1).unwrap(),
+                ProfileSplit::DayType,
+            )
+            .unwrap();
+
+        assert_eq!(
+            profile_points(&profile),
+            vec![
+                ("weekday", 26, 1),
+                ("weekend", 1, 1),
+                ("weekend", 4, 1),
+                ("weekend", 24, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_profile_splits_by_london_month() {
+        let database = TestDatabase::new();
+
+        // 23:30 BST on June 30th, then 00:30 BST on July 1st
+        let repository = electricity_repository_with(
+            &database,
+            &[
+                (utc(2025, 6, 30, 22, 30), 100),
+                (utc(2025, 6, 30, 23, 30), 200),
+            ],
+        );
+
+        let profile = repository
+            .get_profile(
+                NaiveDate::from_ymd_opt(2025, 6, 30).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 7, 2).unwrap(),
+                ProfileSplit::Month,
+            )
+            .unwrap();
+
+        assert_eq!(profile_points(&profile), vec![("06", 47, 1), ("07", 1, 1)]);
+        assert_eq!(profile[1].p50_wh, 200);
+    }
+
+    #[test]
+    fn test_weekly_and_yearly_buckets_span_the_new_year() {
+        let database = TestDatabase::new();
+
+        // Noon on each day from Saturday December 28th 2024 to Monday January 6th 2025
+        let first_day = NaiveDate::from_ymd_opt(2024, 12, 28).unwrap();
+        let readings: Vec<_> = (0..10)
+            .map(|i| {
+                let day = first_day + Duration::days(i);
+                (day.and_hms_opt(12, 0, 0).unwrap(), 100)
+            })
+            .collect();
+
+        let repository = electricity_repository_with(&database, &readings);
+
+        let start = first_day;
+        let end = NaiveDate::from_ymd_opt(2025, 1, 7).unwrap();
+
+        // ISO week 1 of 2025 starts on Monday December 30th 2024
+        assert_eq!(
+            repository.get_weekly(start, end).unwrap(),
+            vec![
+                (NaiveDate::from_ymd_opt(2024, 12, 23).unwrap(), 200),
+                (NaiveDate::from_ymd_opt(2024, 12, 30).unwrap(), 700),
+                (NaiveDate::from_ymd_opt(2025, 1, 6).unwrap(), 100),
+            ]
+        );
+
+        assert_eq!(
+            repository.get_yearly(start, end).unwrap(),
+            vec![
+                (NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), 400),
+                (NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(), 600),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weekly_buckets_use_london_dates() {
+        let database = TestDatabase::new();
+
+        // 23:30 BST on Sunday March 30th 2025, then 00:30 BST on Monday the 31st
+        let repository = electricity_repository_with(
+            &database,
+            &[
+                (utc(2025, 3, 30, 22, 30), 100),
+                (utc(2025, 3, 30, 23, 30), 200),
+            ],
+        );
+
+        assert_eq!(
+            repository
+                .get_weekly(
+                    NaiveDate::from_ymd_opt(2025, 3, 24).unwrap(),
+                    NaiveDate::from_ymd_opt(2025, 4, 7).unwrap(),
+                )
+                .unwrap(),
+            vec![
+                (NaiveDate::from_ymd_opt(2025, 3, 24).unwrap(), 100),
+                (NaiveDate::from_ymd_opt(2025, 3, 31).unwrap(), 200),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provisional_readings_do_not_replace_downloaded_readings() {
+        let database = TestDatabase::new();
+        let repository = SqliteGasConsumptionRepository::new(database.pool.clone());
+
+        let value = |wh: i64| GasConsumptionValue {
+            timestamp: NaiveDate::from_ymd_opt(2026, 1, 15)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+            value: Decimal::new(wh, 3),
+        };
+
+        repository.insert_provisional(vec![value(100)]).unwrap();
+        repository.insert(vec![value(200)]).unwrap();
+        repository.insert_provisional(vec![value(300)]).unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+        let daily = repository.get_daily(day, day.succ_opt().unwrap()).unwrap();
+
+        assert_eq!(daily, vec![(day, 200, false)]);
+    }
+
+    #[test]
+    fn test_readings_are_dated_in_the_stored_timezone() {
+        let database = TestDatabase::new();
+
+        // As if the command line tool had changed it, with this process still on London
+        crate::db::store_reporting_timezone(
+            &mut database.pool.get().unwrap(),
+            chrono_tz::America::New_York,
+        )
+        .unwrap();
+
+        let repository = electricity_repository_with(&database, &[(utc(2026, 1, 15, 4, 30), 100)]);
+
+        let records = repository
+            .get_raw(
+                NaiveDate::from_ymd_opt(2026, 1, 14).unwrap(),
+                NaiveDate::from_ymd_opt(2026, 1, 16).unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(records[0].london_date_id, Some(20260114));
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn benchmark_insert_a_year_of_readings() {
+        let records = year_of_records();
+
+        let row_by_row = time_insert(&records, 1);
+        let batched = time_insert(&records, INSERT_BATCH_SIZE);
+
+        println!(
+            "Inserted {} readings: {:?} row by row, {:?} in batches of {}",
+            records.len(),
+            row_by_row,
+            batched,
+            INSERT_BATCH_SIZE
+        );
+
+        assert!(batched < row_by_row);
+    }
 }
@@ -0,0 +1,270 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::sql_query;
+use diesel::sql_types::{Bool, Integer, Text, Timestamp};
+use diesel::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+use super::{quality::refresh_quality_flags, rollup::refresh_rollups, Fuel, RepositoryError};
+use crate::dates::{london_midnight_as_utc, naive_date_to_london_date_id};
+use crate::db::SqliteConnectionPool;
+
+/// The kinds of stored data that can be deleted for a fuel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DataKind {
+    /// Readings downloaded from the Glowmarkt API, and anomalies detected in them
+    Consumption,
+    /// Tariff plans, unit prices and standing charges
+    Tariff,
+    /// Provisional readings derived from live MQTT readings
+    LiveReadings,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletionSummary {
+    pub consumption_deleted: usize,
+    pub tariffs_deleted: usize,
+    pub live_readings_deleted: usize,
+    /// Whether the next sync will download the range again
+    pub is_download_reset: bool,
+}
+
+type RepositoryResult<T> = Result<T, RepositoryError>;
+
+pub trait DataDeletionRepository {
+    /// Deletes the chosen kinds of data for a fuel on days from `start` up to `end`. Deleting
+    /// downloaded data rewinds the fuel's energy profile, so that the next sync fills the gap.
+    fn delete(
+        &self,
+        fuel: Fuel,
+        start: NaiveDate,
+        end: NaiveDate,
+        kinds: &[DataKind],
+    ) -> RepositoryResult<DeletionSummary>;
+}
+
+pub struct SqliteDataDeletionRepository {
+    connection_pool: SqliteConnectionPool,
+}
+
+impl SqliteDataDeletionRepository {
+    pub fn new(connection_pool: SqliteConnectionPool) -> Self {
+        Self { connection_pool }
+    }
+
+    fn get_connection(
+        &self,
+    ) -> RepositoryResult<PooledConnection<ConnectionManager<SqliteConnection>>> {
+        Ok(self.connection_pool.get()?)
+    }
+}
+
+fn delete_readings(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    is_provisional: bool,
+) -> Result<usize, diesel::result::Error> {
+    let table_name = fuel.consumption_table();
+
+    sql_query(format!(
+        "DELETE FROM {table_name} WHERE timestamp >= ? AND timestamp < ? AND is_provisional = ?"
+    ))
+    .bind::<Timestamp, _>(start)
+    .bind::<Timestamp, _>(end)
+    .bind::<Bool, _>(is_provisional)
+    .execute(conn)
+}
+
+fn delete_tariffs(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<usize, diesel::result::Error> {
+    let fuel_name = fuel.as_str();
+
+    let mut deleted_count = 0;
+
+    for (table_name, column) in [
+        (format!("{fuel_name}_tariff_plan"), "effective_date"),
+        (format!("{fuel_name}_unit_price"), "price_effective_time"),
+        (format!("{fuel_name}_standing_charge"), "start_date"),
+    ] {
+        deleted_count += sql_query(format!(
+            "DELETE FROM {table_name} WHERE {column} >= ? AND {column} < ?"
+        ))
+        .bind::<Timestamp, _>(start)
+        .bind::<Timestamp, _>(end)
+        .execute(conn)?;
+    }
+
+    Ok(deleted_count)
+}
+
+/// Moves the profile's last retrieved date back to `start`, or clears it if that's before the
+/// profile starts, so that downloading resumes from there.
+fn reset_last_date_retrieved(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    start: NaiveDate,
+) -> Result<usize, diesel::result::Error> {
+    sql_query(
+        r#"
+            UPDATE energy_profile
+            SET last_date_retrieved = CASE
+                WHEN ? <= start_date THEN NULL
+                ELSE ?
+            END
+            WHERE name = ? AND last_date_retrieved > ?
+        "#,
+    )
+    .bind::<Timestamp, _>(NaiveDateTime::from(start))
+    .bind::<Timestamp, _>(NaiveDateTime::from(start))
+    .bind::<Text, _>(fuel.as_str())
+    .bind::<Timestamp, _>(NaiveDateTime::from(start))
+    .execute(conn)
+}
+
+impl DataDeletionRepository for SqliteDataDeletionRepository {
+    fn delete(
+        &self,
+        fuel: Fuel,
+        start: NaiveDate,
+        end: NaiveDate,
+        kinds: &[DataKind],
+    ) -> RepositoryResult<DeletionSummary> {
+        let start_utc = london_midnight_as_utc(&start);
+        let end_utc = london_midnight_as_utc(&end);
+
+        let start_date_id = naive_date_to_london_date_id(&start);
+        let end_date_id = naive_date_to_london_date_id(&(end - Duration::days(1)));
+
+        let mut conn = self.get_connection()?;
+
+        let summary = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let mut summary = DeletionSummary::default();
+
+            if kinds.contains(&DataKind::Consumption) {
+                summary.consumption_deleted =
+                    delete_readings(conn, fuel, start_utc, end_utc, false)?;
+
+                sql_query(
+                    r#"
+                        DELETE FROM consumption_anomaly
+                        WHERE fuel = ? AND london_date_id >= ? AND london_date_id <= ?
+                    "#,
+                )
+                .bind::<Text, _>(fuel.as_str())
+                .bind::<Integer, _>(start_date_id)
+                .bind::<Integer, _>(end_date_id)
+                .execute(conn)?;
+            }
+
+            if kinds.contains(&DataKind::LiveReadings) {
+                summary.live_readings_deleted =
+                    delete_readings(conn, fuel, start_utc, end_utc, true)?;
+            }
+
+            if kinds.contains(&DataKind::Tariff) {
+                summary.tariffs_deleted = delete_tariffs(conn, fuel, start_utc, end_utc)?;
+            }
+
+            if summary.consumption_deleted > 0 || summary.live_readings_deleted > 0 {
+                // A reading after the range may only have been a duplicate of a deleted one
+                refresh_quality_flags(conn, fuel, start_utc, end_utc + Duration::minutes(30))?;
+                refresh_rollups(conn, fuel, start_date_id, end_date_id)?;
+            }
+
+            if kinds.contains(&DataKind::Consumption) || kinds.contains(&DataKind::Tariff) {
+                reset_last_date_retrieved(conn, fuel, start)?;
+                summary.is_download_reset = true;
+            }
+
+            Ok(summary)
+        })?;
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::consumption::{
+        ConsumptionRepository, ElectricityConsumptionValue, SqliteElectricityConsumptionRepository,
+    };
+    use crate::data::energy_profile::{EnergyProfileRepository, SqliteEnergyProfileRepository};
+    use crate::db::TestDatabase;
+    use rust_decimal::Decimal;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_deleting_a_week_of_consumption() {
+        let database = TestDatabase::new();
+        let consumption_repository =
+            SqliteElectricityConsumptionRepository::new(database.pool.clone());
+
+        let start = date(2026, 1, 1).and_hms_opt(0, 0, 0).unwrap();
+
+        consumption_repository
+            .insert(
+                (0..(48 * 28))
+                    .map(|i| ElectricityConsumptionValue {
+                        timestamp: start + Duration::minutes(30 * i),
+                        value: Decimal::new(1, 1),
+                    })
+                    .collect(),
+            )
+            .unwrap();
+
+        let profile_repository = SqliteEnergyProfileRepository::new(database.pool.clone());
+        let profile = profile_repository
+            .create_energy_profile("electricity", "kWh")
+            .unwrap();
+        profile_repository
+            .update_energy_profile(
+                profile.energy_profile_id,
+                true,
+                start,
+                date(2026, 1, 29).into(),
+            )
+            .unwrap();
+
+        let summary = SqliteDataDeletionRepository::new(database.pool.clone())
+            .delete(
+                Fuel::Electricity,
+                date(2026, 1, 8),
+                date(2026, 1, 15),
+                &[DataKind::Consumption],
+            )
+            .unwrap();
+
+        assert_eq!(summary.consumption_deleted, 48 * 7);
+        assert!(summary.is_download_reset);
+
+        let daily = consumption_repository
+            .get_daily(date(2026, 1, 1), date(2026, 1, 29))
+            .unwrap();
+
+        assert_eq!(daily.len(), 21);
+        assert!(daily
+            .iter()
+            .all(|(day, _, _)| *day < date(2026, 1, 8) || *day >= date(2026, 1, 15)));
+
+        assert_eq!(
+            profile_repository
+                .get_energy_profile("electricity")
+                .unwrap()
+                .last_date_retrieved,
+            Some(date(2026, 1, 8).into())
+        );
+    }
+}
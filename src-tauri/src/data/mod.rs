@@ -1,6 +1,48 @@
+pub mod alert;
+pub mod anomaly;
 pub mod consumption;
+pub mod deletion;
 pub mod energy_profile;
+pub mod quality;
+pub mod rollup;
 pub mod tariff;
+pub mod weather;
+
+use serde::{Deserialize, Serialize};
+
+/// A metered fuel, for tables and commands that cover both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Fuel {
+    Electricity,
+    Gas,
+}
+
+impl Fuel {
+    pub const ALL: [Fuel; 2] = [Fuel::Electricity, Fuel::Gas];
+
+    /// The name stored in the database, which is also the energy profile name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fuel::Electricity => "electricity",
+            Fuel::Gas => "gas",
+        }
+    }
+
+    /// The table holding the half-hourly consumption.
+    pub fn consumption_table(&self) -> &'static str {
+        match self {
+            Fuel::Electricity => "electricity_consumption",
+            Fuel::Gas => "gas_consumption",
+        }
+    }
+}
+
+impl std::fmt::Display for Fuel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum RepositoryError {
@@ -8,4 +50,6 @@ pub enum RepositoryError {
     DieselError(#[from] diesel::result::Error),
     #[error("Error with connection pool: {0}")]
     ConnectionPoolError(#[from] diesel::r2d2::PoolError),
+    #[error("Can't write dates without the reporting timezone: {0}")]
+    ReportingTimezoneError(String),
 }
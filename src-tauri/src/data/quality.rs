@@ -0,0 +1,286 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Integer, Timestamp};
+use diesel::SqliteConnection;
+
+use super::{consumption::ENERGY_CONSUMPTION_WH_ERROR_CODE, Fuel, RepositoryError};
+use crate::dates::naive_date_to_london_date_id;
+use crate::db::SqliteConnectionPool;
+
+/// Why a half-hourly reading is excluded from aggregates, stored in the `quality_flag` column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(i32)]
+pub enum QualityFlag {
+    Valid = 0,
+    /// The meter's error code, `ENERGY_CONSUMPTION_WH_ERROR_CODE`
+    ErrorCode = 1,
+    Negative = 2,
+    /// Larger than a domestic supply could deliver in half an hour
+    Implausible = 3,
+    /// Starts off the half-hour grid, less than half an hour after the previous reading
+    Duplicate = 4,
+}
+
+/// About 50 kW, well above a domestic single or three phase supply.
+pub const MAX_PLAUSIBLE_ELECTRICITY_WH: i64 = 25_000;
+
+/// About 100 kW, well above the largest domestic gas meter.
+pub const MAX_PLAUSIBLE_GAS_WH: i64 = 50_000;
+
+pub fn max_plausible_wh(fuel: Fuel) -> i64 {
+    match fuel {
+        Fuel::Electricity => MAX_PLAUSIBLE_ELECTRICITY_WH,
+        Fuel::Gas => MAX_PLAUSIBLE_GAS_WH,
+    }
+}
+
+/// The range of readings whose quality flags may change when readings at `timestamps` are
+/// inserted, which includes any reading that starts within half an hour of the last one.
+pub fn quality_refresh_range(
+    timestamps: impl Iterator<Item = NaiveDateTime>,
+) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    timestamps.fold(None, |range, timestamp| match range {
+        None => Some((timestamp, timestamp + Duration::minutes(30))),
+        Some((start, end)) => Some((
+            start.min(timestamp),
+            end.max(timestamp + Duration::minutes(30)),
+        )),
+    })
+}
+
+/// Sets the quality flag of every reading from `start` up to `end`. Call this within the
+/// transaction that inserts the readings, with `end` at least half an hour after the last one so
+/// that any reading overlapping it is flagged too.
+///
+/// Only readings off the :00 and :30 grid are flagged as duplicates, so that a stray reading
+/// never excludes the properly aligned one after it.
+pub fn refresh_quality_flags(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<usize, diesel::result::Error> {
+    let table_name = fuel.consumption_table();
+
+    let query = format!(
+        r#"
+            UPDATE {table_name} SET quality_flag = CASE
+                WHEN energy_consumption_wh = ? THEN ?
+                WHEN energy_consumption_wh < 0 THEN ?
+                WHEN energy_consumption_wh > ? THEN ?
+                WHEN strftime('%M:%S', timestamp) NOT IN ('00:00', '30:00') AND EXISTS (
+                    SELECT 1 FROM {table_name} AS previous
+                    WHERE previous.timestamp < {table_name}.timestamp
+                        AND previous.timestamp > datetime({table_name}.timestamp, '-30 minutes')
+                ) THEN ?
+                ELSE ?
+            END
+            WHERE timestamp >= ? AND timestamp < ?
+        "#
+    );
+
+    sql_query(query)
+        .bind::<BigInt, _>(ENERGY_CONSUMPTION_WH_ERROR_CODE)
+        .bind::<Integer, _>(QualityFlag::ErrorCode as i32)
+        .bind::<Integer, _>(QualityFlag::Negative as i32)
+        .bind::<BigInt, _>(max_plausible_wh(fuel))
+        .bind::<Integer, _>(QualityFlag::Implausible as i32)
+        .bind::<Integer, _>(QualityFlag::Duplicate as i32)
+        .bind::<Integer, _>(QualityFlag::Valid as i32)
+        .bind::<Timestamp, _>(start)
+        .bind::<Timestamp, _>(end)
+        .execute(conn)
+}
+
+/// The number of readings with each quality flag on a London day.
+#[derive(QueryableByName, Debug)]
+pub struct DailyQualityRecord {
+    #[diesel(sql_type = Integer)]
+    pub london_date_id: i32,
+    #[diesel(sql_type = BigInt)]
+    pub reading_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub valid_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub error_code_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub negative_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub implausible_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub duplicate_count: i64,
+}
+
+type RepositoryResult<T> = Result<T, RepositoryError>;
+
+pub trait DataQualityRepository {
+    /// Returns the quality of each London day that has any readings.
+    fn get_daily_quality(
+        &self,
+        fuel: Fuel,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<DailyQualityRecord>>;
+}
+
+pub struct SqliteDataQualityRepository {
+    connection_pool: SqliteConnectionPool,
+}
+
+impl SqliteDataQualityRepository {
+    pub fn new(connection_pool: SqliteConnectionPool) -> Self {
+        Self { connection_pool }
+    }
+
+    fn get_connection(
+        &self,
+    ) -> RepositoryResult<PooledConnection<ConnectionManager<SqliteConnection>>> {
+        Ok(self.connection_pool.get()?)
+    }
+}
+
+impl DataQualityRepository for SqliteDataQualityRepository {
+    fn get_daily_quality(
+        &self,
+        fuel: Fuel,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<DailyQualityRecord>> {
+        let mut conn = self.get_connection()?;
+
+        let query = format!(
+            r#"
+                SELECT
+                    london_date_id,
+                    COUNT(*) AS reading_count,
+                    SUM(quality_flag = ?) AS valid_count,
+                    SUM(quality_flag = ?) AS error_code_count,
+                    SUM(quality_flag = ?) AS negative_count,
+                    SUM(quality_flag = ?) AS implausible_count,
+                    SUM(quality_flag = ?) AS duplicate_count
+                FROM {}
+                WHERE london_date_id >= ? AND london_date_id < ?
+                GROUP BY london_date_id
+                ORDER BY london_date_id
+            "#,
+            fuel.consumption_table()
+        );
+
+        Ok(sql_query(query)
+            .bind::<Integer, _>(QualityFlag::Valid as i32)
+            .bind::<Integer, _>(QualityFlag::ErrorCode as i32)
+            .bind::<Integer, _>(QualityFlag::Negative as i32)
+            .bind::<Integer, _>(QualityFlag::Implausible as i32)
+            .bind::<Integer, _>(QualityFlag::Duplicate as i32)
+            .bind::<Integer, _>(naive_date_to_london_date_id(&start))
+            .bind::<Integer, _>(naive_date_to_london_date_id(&end))
+            .load::<DailyQualityRecord>(&mut *conn)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::data::consumption::{
+        ConsumptionRepository, ElectricityConsumptionValue, SqliteElectricityConsumptionRepository,
+    };
+    use crate::db::TestDatabase;
+
+    fn utc(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn insert(database: &TestDatabase, readings: &[(NaiveDateTime, i64)]) {
+        SqliteElectricityConsumptionRepository::new(database.pool.clone())
+            .insert(
+                readings
+                    .iter()
+                    .map(|(timestamp, wh)| ElectricityConsumptionValue {
+                        timestamp: *timestamp,
+                        value: Decimal::new(*wh, 3),
+                    })
+                    .collect(),
+            )
+            .unwrap();
+    }
+
+    fn daily_quality(database: &TestDatabase) -> Vec<DailyQualityRecord> {
+        SqliteDataQualityRepository::new(database.pool.clone())
+            .get_daily_quality(
+                Fuel::Electricity,
+                NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2026, 2, 1).unwrap(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_flags_sentinel_negative_and_implausible_readings() {
+        let database = TestDatabase::new();
+
+        insert(
+            &database,
+            &[
+                (utc(15, 10, 0), 100),
+                (utc(15, 10, 30), ENERGY_CONSUMPTION_WH_ERROR_CODE),
+                (utc(15, 11, 0), -1),
+                (utc(15, 11, 30), MAX_PLAUSIBLE_ELECTRICITY_WH + 1),
+                (utc(15, 12, 0), MAX_PLAUSIBLE_ELECTRICITY_WH),
+            ],
+        );
+
+        let quality = daily_quality(&database);
+
+        assert_eq!(quality.len(), 1);
+        assert_eq!(quality[0].london_date_id, 20260115);
+        assert_eq!(quality[0].reading_count, 5);
+        assert_eq!(quality[0].valid_count, 2);
+        assert_eq!(quality[0].error_code_count, 1);
+        assert_eq!(quality[0].negative_count, 1);
+        assert_eq!(quality[0].implausible_count, 1);
+        assert_eq!(quality[0].duplicate_count, 0);
+    }
+
+    #[test]
+    fn test_flags_only_misaligned_readings_as_duplicates() {
+        let database = TestDatabase::new();
+
+        insert(
+            &database,
+            &[
+                // A stray reading before the aligned ones, which must stay valid
+                (utc(15, 9, 50), 100),
+                (utc(15, 10, 0), 100),
+                (utc(15, 10, 15), 100),
+                (utc(15, 10, 30), 100),
+                // Misaligned, but nothing starts in the half hour before it
+                (utc(15, 12, 10), 100),
+            ],
+        );
+
+        let quality = daily_quality(&database);
+
+        assert_eq!(quality[0].reading_count, 5);
+        assert_eq!(quality[0].valid_count, 4);
+        assert_eq!(quality[0].duplicate_count, 1);
+    }
+
+    #[test]
+    fn test_flags_are_refreshed_when_an_earlier_reading_arrives() {
+        let database = TestDatabase::new();
+
+        insert(&database, &[(utc(15, 10, 15), 100)]);
+        assert_eq!(daily_quality(&database)[0].duplicate_count, 0);
+
+        insert(&database, &[(utc(15, 10, 0), 100)]);
+        assert_eq!(daily_quality(&database)[0].duplicate_count, 1);
+    }
+}
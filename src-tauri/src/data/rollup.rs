@@ -0,0 +1,81 @@
+use diesel::prelude::*;
+use diesel::sql_query;
+use diesel::sql_types::Integer;
+use diesel::SqliteConnection;
+
+use super::{quality::QualityFlag, Fuel};
+
+/// The London month id, the date id of the first of the month, of the `london_date_id` column.
+pub const LONDON_MONTH_ID_SQL: &str = "london_date_id - (london_date_id % 100) + 1";
+
+/// The earliest and latest possible date ids, for rebuilding every rollup.
+const MIN_LONDON_DATE_ID: i32 = 0;
+const MAX_LONDON_DATE_ID: i32 = 99_991_231;
+
+/// Recalculates the daily totals for the London days from `start_date_id` to `end_date_id`
+/// inclusive, and the monthly totals for the months containing them. Call this within the
+/// transaction that changes the readings.
+pub fn refresh_rollups(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+    start_date_id: i32,
+    end_date_id: i32,
+) -> Result<(), diesel::result::Error> {
+    let consumption_table = fuel.consumption_table();
+    let fuel_name = fuel.as_str();
+
+    sql_query(format!(
+        "DELETE FROM {fuel_name}_daily_rollup WHERE london_date_id >= ? AND london_date_id <= ?"
+    ))
+    .bind::<Integer, _>(start_date_id)
+    .bind::<Integer, _>(end_date_id)
+    .execute(conn)?;
+
+    sql_query(format!(
+        r#"
+            INSERT INTO {fuel_name}_daily_rollup
+                (london_date_id, energy_consumption_wh, is_provisional)
+            SELECT london_date_id, SUM(energy_consumption_wh), MAX(is_provisional)
+            FROM {consumption_table}
+            WHERE london_date_id >= ? AND london_date_id <= ? AND quality_flag = ?
+            GROUP BY london_date_id
+        "#
+    ))
+    .bind::<Integer, _>(start_date_id)
+    .bind::<Integer, _>(end_date_id)
+    .bind::<Integer, _>(QualityFlag::Valid as i32)
+    .execute(conn)?;
+
+    let start_month_id = start_date_id - (start_date_id % 100) + 1;
+    let end_month_id = end_date_id - (end_date_id % 100) + 1;
+
+    sql_query(format!(
+        "DELETE FROM {fuel_name}_monthly_rollup WHERE london_month_id >= ? AND london_month_id <= ?"
+    ))
+    .bind::<Integer, _>(start_month_id)
+    .bind::<Integer, _>(end_month_id)
+    .execute(conn)?;
+
+    sql_query(format!(
+        r#"
+            INSERT INTO {fuel_name}_monthly_rollup (london_month_id, energy_consumption_wh)
+            SELECT {LONDON_MONTH_ID_SQL}, SUM(energy_consumption_wh)
+            FROM {fuel_name}_daily_rollup
+            WHERE {LONDON_MONTH_ID_SQL} >= ? AND {LONDON_MONTH_ID_SQL} <= ?
+            GROUP BY {LONDON_MONTH_ID_SQL}
+        "#
+    ))
+    .bind::<Integer, _>(start_month_id)
+    .bind::<Integer, _>(end_month_id)
+    .execute(conn)?;
+
+    Ok(())
+}
+
+/// Recalculates every daily and monthly total, e.g. after the date ids have been changed.
+pub fn rebuild_rollups(
+    conn: &mut SqliteConnection,
+    fuel: Fuel,
+) -> Result<(), diesel::result::Error> {
+    refresh_rollups(conn, fuel, MIN_LONDON_DATE_ID, MAX_LONDON_DATE_ID)
+}
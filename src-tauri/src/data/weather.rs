@@ -0,0 +1,188 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::sql_query;
+use diesel::sql_types::{Bool, Double, Integer, Timestamp};
+use diesel::sqlite::Sqlite;
+use diesel::SqliteConnection;
+
+use super::{consumption::INSERT_BATCH_SIZE, RepositoryError};
+use crate::dates::{
+    london_date_id_to_naive_date, naive_date_to_london_date_id, utc_timestamp_to_london_date_id_in,
+};
+use crate::db::{reporting_timezone_for_writing, SqliteConnectionPool};
+
+/// A temperature reading, either for an hour or, for daily readings, for the London day
+/// starting at the timestamp.
+pub struct TemperatureValue {
+    pub timestamp: NaiveDateTime,
+    pub temperature_celsius: f64,
+    /// Keeps the reading on its day if the reporting timezone changes
+    pub is_daily: bool,
+}
+
+type RepositoryResult<T> = Result<T, RepositoryError>;
+
+pub trait WeatherRepository {
+    fn insert(&self, records: Vec<TemperatureValue>) -> RepositoryResult<()>;
+
+    /// Returns the mean temperature per London day, using the daily reading for a day if there is
+    /// one, and otherwise the mean of its hourly readings.
+    fn get_daily_mean(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, f64)>>;
+}
+
+pub struct SqliteWeatherRepository {
+    connection_pool: SqliteConnectionPool,
+}
+
+impl SqliteWeatherRepository {
+    pub fn new(connection_pool: SqliteConnectionPool) -> Self {
+        Self { connection_pool }
+    }
+
+    fn get_connection(
+        &self,
+    ) -> RepositoryResult<PooledConnection<ConnectionManager<SqliteConnection>>> {
+        Ok(self.connection_pool.get()?)
+    }
+}
+
+impl WeatherRepository for SqliteWeatherRepository {
+    fn insert(&self, records: Vec<TemperatureValue>) -> RepositoryResult<()> {
+        self.get_connection()?
+            .immediate_transaction::<_, RepositoryError, _>(|conn| {
+                let timezone = reporting_timezone_for_writing(conn)?;
+
+                for batch in records.chunks(INSERT_BATCH_SIZE) {
+                    let values = vec!["(?, ?, ?, ?)"; batch.len()].join(", ");
+
+                    let mut query = sql_query(format!(
+                        r#"
+                            INSERT INTO weather_temperature
+                                (timestamp, temperature_celsius, london_date_id, is_daily)
+                            VALUES {values}
+                            ON CONFLICT (timestamp) DO UPDATE
+                            SET temperature_celsius = excluded.temperature_celsius,
+                                is_daily = excluded.is_daily
+                        "#
+                    ))
+                    .into_boxed::<Sqlite>();
+
+                    for record in batch {
+                        query = query
+                            .bind::<Timestamp, _>(record.timestamp)
+                            .bind::<Double, _>(record.temperature_celsius)
+                            .bind::<Integer, _>(utc_timestamp_to_london_date_id_in(
+                                timezone,
+                                &record.timestamp,
+                            ))
+                            .bind::<Bool, _>(record.is_daily);
+                    }
+
+                    query.execute(conn)?;
+                }
+
+                Ok(())
+            })?;
+
+        Ok(())
+    }
+
+    fn get_daily_mean(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> RepositoryResult<Vec<(NaiveDate, f64)>> {
+        use crate::schema::weather_temperature::dsl::*;
+
+        let mut conn = self.get_connection()?;
+
+        let daily_mean = weather_temperature
+            .filter(london_date_id.ge(naive_date_to_london_date_id(&start)))
+            .filter(london_date_id.lt(naive_date_to_london_date_id(&end)))
+            .select((
+                london_date_id,
+                sql::<Double>(
+                    "COALESCE(AVG(CASE WHEN is_daily THEN temperature_celsius END), AVG(temperature_celsius))",
+                ),
+            ))
+            .group_by(london_date_id)
+            .order(london_date_id)
+            .load::<(i32, f64)>(&mut *conn)?;
+
+        Ok(daily_mean
+            .iter()
+            .map(|(date_id, temperature)| (london_date_id_to_naive_date(*date_id), *temperature))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::TestDatabase;
+
+    fn reading(
+        date: NaiveDate,
+        hour: u32,
+        temperature_celsius: f64,
+        is_daily: bool,
+    ) -> TemperatureValue {
+        TemperatureValue {
+            timestamp: date.and_hms_opt(hour, 0, 0).unwrap(),
+            temperature_celsius,
+            is_daily,
+        }
+    }
+
+    #[test]
+    fn test_insert_updates_existing_readings() {
+        let database = TestDatabase::new();
+        let repository = SqliteWeatherRepository::new(database.pool.clone());
+
+        let date = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+
+        repository
+            .insert(vec![reading(date, 12, 4.0, false)])
+            .unwrap();
+        repository
+            .insert(vec![reading(date, 12, 6.0, false)])
+            .unwrap();
+
+        let daily_mean = repository
+            .get_daily_mean(date, date.succ_opt().unwrap())
+            .unwrap();
+
+        assert_eq!(daily_mean, vec![(date, 6.0)]);
+    }
+
+    #[test]
+    fn test_daily_mean_prefers_the_daily_reading() {
+        let database = TestDatabase::new();
+        let repository = SqliteWeatherRepository::new(database.pool.clone());
+
+        let january_15th = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+        let january_16th = january_15th.succ_opt().unwrap();
+
+        repository
+            .insert(vec![
+                reading(january_15th, 0, 5.0, true),
+                reading(january_15th, 6, 1.0, false),
+                reading(january_15th, 12, 3.0, false),
+                reading(january_16th, 6, 2.0, false),
+                reading(january_16th, 12, 4.0, false),
+            ])
+            .unwrap();
+
+        let daily_mean = repository
+            .get_daily_mean(january_15th, january_16th.succ_opt().unwrap())
+            .unwrap();
+
+        assert_eq!(daily_mean, vec![(january_15th, 5.0), (january_16th, 3.0)]);
+    }
+}
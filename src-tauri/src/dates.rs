@@ -0,0 +1,347 @@
+use std::sync::RwLock;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{Europe::London, Tz};
+
+/// The timezone that days, months and half hours of the day are reported in. It was always
+/// London before it became a setting, hence the `london_` prefix on the helpers below and on the
+/// date id columns, which all use this timezone.
+static REPORTING_TIMEZONE: RwLock<Tz> = RwLock::new(DEFAULT_REPORTING_TIMEZONE);
+
+pub const DEFAULT_REPORTING_TIMEZONE: Tz = London;
+
+pub fn reporting_timezone() -> Tz {
+    *REPORTING_TIMEZONE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn set_reporting_timezone(timezone: Tz) {
+    *REPORTING_TIMEZONE
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = timezone;
+}
+
+pub fn london_midnight_as_utc(date: &NaiveDate) -> NaiveDateTime {
+    let timezone = reporting_timezone();
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+
+    // In a few timezones the clocks go forward at midnight, so the day starts an hour later
+    let london_midnight_local = (0..=2)
+        .find_map(|hours| {
+            timezone
+                .from_local_datetime(&(midnight + Duration::hours(hours)))
+                .earliest()
+        })
+        .unwrap();
+
+    let london_midnight_utc = london_midnight_local.with_timezone(&Utc);
+
+    london_midnight_utc.naive_utc()
+}
+
+pub fn utc_timestamp_to_london_date_id(timestamp_utc: &NaiveDateTime) -> i32 {
+    utc_timestamp_to_london_date_id_in(reporting_timezone(), timestamp_utc)
+}
+
+pub fn utc_timestamp_to_london_date_id_in(timezone: Tz, timestamp_utc: &NaiveDateTime) -> i32 {
+    let london_time = timestamp_utc.and_utc().with_timezone(&timezone);
+
+    london_time
+        .format("%Y%m%d")
+        .to_string()
+        .parse::<i32>()
+        .unwrap()
+}
+
+pub fn naive_date_to_london_date_id(date: &NaiveDate) -> i32 {
+    (date.year() * 10000) + (date.month() as i32 * 100) + (date.day() as i32)
+}
+
+pub fn london_date_id_to_naive_date(date_id: i32) -> NaiveDate {
+    let year = date_id / 10000;
+    let month = ((date_id % 10000) / 100) as u32;
+    let day = (date_id % 100) as u32;
+
+    NaiveDate::from_ymd_opt(year, month, day).expect("Invalid date_id in the database")
+}
+
+pub fn london_today() -> NaiveDate {
+    Utc::now().with_timezone(&reporting_timezone()).date_naive()
+}
+
+/// A period, from `start` up to `end` in UTC, in which a timezone has a constant offset.
+#[derive(Debug, PartialEq)]
+pub struct UtcOffsetPeriod {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub offset_minutes: i32,
+}
+
+fn utc_offset_minutes(timezone: Tz, timestamp_utc: &NaiveDateTime) -> i32 {
+    timezone
+        .offset_from_utc_datetime(timestamp_utc)
+        .fix()
+        .local_minus_utc()
+        / 60
+}
+
+/// Splits the range from `start` up to `end` in UTC into periods with a constant offset. Offsets
+/// change at whole minutes, and never more than once an hour.
+pub fn utc_offset_periods(
+    timezone: Tz,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Vec<UtcOffsetPeriod> {
+    let mut periods = vec![];
+
+    if start >= end {
+        return periods;
+    }
+
+    let mut period_start = start;
+    let mut offset_minutes = utc_offset_minutes(timezone, &start);
+    let mut hour_start = start;
+
+    while hour_start < end {
+        let hour_end = (hour_start + Duration::hours(1)).min(end);
+
+        if utc_offset_minutes(timezone, &hour_end) != offset_minutes {
+            let mut change = hour_start + Duration::minutes(1);
+
+            while utc_offset_minutes(timezone, &change) == offset_minutes {
+                change += Duration::minutes(1);
+            }
+
+            if change < end {
+                periods.push(UtcOffsetPeriod {
+                    start: period_start,
+                    end: change,
+                    offset_minutes,
+                });
+
+                period_start = change;
+                offset_minutes = utc_offset_minutes(timezone, &change);
+            }
+        }
+
+        hour_start = hour_end;
+    }
+
+    periods.push(UtcOffsetPeriod {
+        start: period_start,
+        end,
+        offset_minutes,
+    });
+
+    periods
+}
+
+/// The number of half hours in a London day: 46 when the clocks go forward, 50 when they go
+/// back and 48 otherwise.
+pub fn london_slot_count(date: &NaiveDate) -> u8 {
+    let start = london_midnight_as_utc(date);
+    let end = london_midnight_as_utc(&(*date + Duration::days(1)));
+
+    ((end - start).num_minutes() / 30) as u8
+}
+
+/// Whether a period from `period_start` up to `period_end` extends beyond the range from `start`
+/// up to `end`, so that only part of it is covered.
+pub fn is_partial_period(
+    period_start: &NaiveDate,
+    period_end: &NaiveDate,
+    start: &NaiveDate,
+    end: &NaiveDate,
+) -> bool {
+    period_start < start || period_end > end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    #[test]
+    fn test_london_midnight_during_gmt_winter() {
+        let winter_date = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+
+        let result = london_midnight_as_utc(&winter_date);
+
+        let expected = winter_date.and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_london_midnight_during_bst_summer() {
+        let summer_date = NaiveDate::from_ymd_opt(2026, 6, 26).unwrap();
+
+        let result = london_midnight_as_utc(&summer_date);
+
+        let expected_date = NaiveDate::from_ymd_opt(2026, 6, 25).unwrap();
+        let expected_time = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+        let expected = expected_date.and_time(expected_time);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_london_midnight_on_clocks_forward_transition_day() {
+        let transition_date = NaiveDate::from_ymd_opt(2026, 3, 29).unwrap();
+
+        let result = london_midnight_as_utc(&transition_date);
+
+        let expected = transition_date.and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_london_midnight_on_clocks_back_transition_day() {
+        let transition_sunday = NaiveDate::from_ymd_opt(2026, 10, 25).unwrap();
+
+        let result = london_midnight_as_utc(&transition_sunday);
+
+        let expected_date = NaiveDate::from_ymd_opt(2026, 10, 24).unwrap();
+        let expected_time = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
+        let expected = expected_date.and_time(expected_time);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_london_midnight_day_after_clocks_back() {
+        let post_transition_monday = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap();
+
+        let result = london_midnight_as_utc(&post_transition_monday);
+
+        let expected = post_transition_monday.and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_london_slot_count() {
+        assert_eq!(
+            london_slot_count(&NaiveDate::from_ymd_opt(2026, 3, 29).unwrap()),
+            46
+        );
+        assert_eq!(
+            london_slot_count(&NaiveDate::from_ymd_opt(2026, 7, 15).unwrap()),
+            48
+        );
+        assert_eq!(
+            london_slot_count(&NaiveDate::from_ymd_opt(2026, 10, 25).unwrap()),
+            50
+        );
+    }
+
+    #[test]
+    fn test_utc_offset_periods_across_british_summer_time() {
+        let start = NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let end = NaiveDate::from_ymd_opt(2027, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        let clocks_forward = NaiveDate::from_ymd_opt(2026, 3, 29)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap();
+        let clocks_back = NaiveDate::from_ymd_opt(2026, 10, 25)
+            .unwrap()
+            .and_hms_opt(1, 0, 0)
+            .unwrap();
+
+        assert_eq!(
+            utc_offset_periods(London, start, end),
+            vec![
+                UtcOffsetPeriod {
+                    start,
+                    end: clocks_forward,
+                    offset_minutes: 0,
+                },
+                UtcOffsetPeriod {
+                    start: clocks_forward,
+                    end: clocks_back,
+                    offset_minutes: 60,
+                },
+                UtcOffsetPeriod {
+                    start: clocks_back,
+                    end,
+                    offset_minutes: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_utc_offset_periods_with_a_negative_offset() {
+        let start = NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let end = NaiveDate::from_ymd_opt(2026, 6, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        let periods = utc_offset_periods(chrono_tz::America::New_York, start, end);
+
+        // The clocks go forward at 02:00 local time on the second Sunday in March
+        assert_eq!(periods.len(), 2);
+        assert_eq!(periods[0].offset_minutes, -300);
+        assert_eq!(
+            periods[1].start,
+            NaiveDate::from_ymd_opt(2026, 3, 8)
+                .unwrap()
+                .and_hms_opt(7, 0, 0)
+                .unwrap()
+        );
+        assert_eq!(periods[1].offset_minutes, -240);
+    }
+
+    #[test]
+    fn test_utc_offset_periods_empty_range() {
+        let start = NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+
+        assert!(utc_offset_periods(London, start, start).is_empty());
+    }
+
+    #[test]
+    fn test_is_partial_period_full_week() {
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let next_monday = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap();
+
+        assert!(!is_partial_period(
+            &monday,
+            &next_monday,
+            &monday,
+            &next_monday
+        ));
+    }
+
+    #[test]
+    fn test_is_partial_period_at_either_end() {
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        let next_monday = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap();
+        let wednesday = NaiveDate::from_ymd_opt(2026, 10, 21).unwrap();
+
+        assert!(is_partial_period(
+            &monday,
+            &next_monday,
+            &wednesday,
+            &next_monday
+        ));
+        assert!(is_partial_period(
+            &monday,
+            &next_monday,
+            &monday,
+            &wednesday
+        ));
+    }
+}
@@ -1,19 +1,104 @@
+use std::time::Duration;
+
+use chrono::{Duration as ChronoDuration, NaiveDateTime};
+use chrono_tz::Tz;
+use diesel::connection::SimpleConnection;
+use diesel::migration::MigrationSource;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, Pool};
-use diesel::{sqlite::SqliteConnection, Connection, ExpressionMethods};
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PoolError};
+use diesel::sql_query;
+use diesel::sql_types::{Nullable, Timestamp};
+use diesel::sqlite::{Sqlite, SqliteConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use log::info;
 
-use crate::data::RepositoryError;
-use crate::utils::utc_timestamp_to_london_date_id;
+use crate::data::consumption::london_timestamp_sql;
+use crate::data::{rollup::rebuild_rollups, Fuel, RepositoryError};
+use crate::dates::reporting_timezone;
+use crate::schema;
+use crate::AppError;
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+pub(crate) const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 pub type SqliteConnectionPool = Pool<ConnectionManager<SqliteConnection>>;
 
-pub fn run_migrations(conn: &mut SqliteConnection) {
-    conn.run_pending_migrations(MIGRATIONS)
-        .expect("Error running migrations");
+/// How long a connection waits for another to finish writing before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Applies pragmas to every pooled connection. Write-ahead logging lets the charts read while
+/// a download or the MQTT listener is writing, and only syncing at checkpoints makes the many
+/// small writes much cheaper while still being safe against an application crash.
+#[derive(Debug)]
+struct SqlitePragmaCustomizer {
+    /// Rejects any statement that would change the database
+    read_only: bool,
+}
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for SqlitePragmaCustomizer {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute(&format!(
+            r#"
+                PRAGMA journal_mode = WAL;
+                PRAGMA synchronous = NORMAL;
+                PRAGMA busy_timeout = {};
+                PRAGMA query_only = {};
+            "#,
+            BUSY_TIMEOUT.as_millis(),
+            self.read_only
+        ))
+        .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+fn build_connection_pool(
+    database_url: &str,
+    read_only: bool,
+) -> Result<SqliteConnectionPool, PoolError> {
+    Pool::builder()
+        .max_size(10)
+        .connection_customizer(Box::new(SqlitePragmaCustomizer { read_only }))
+        .build(ConnectionManager::<SqliteConnection>::new(database_url))
+}
+
+pub fn create_connection_pool(database_url: &str) -> Result<SqliteConnectionPool, PoolError> {
+    build_connection_pool(database_url, false)
+}
+
+/// Creates a pool whose connections can only read, for when the database couldn't be migrated.
+pub fn create_read_only_connection_pool(
+    database_url: &str,
+) -> Result<SqliteConnectionPool, PoolError> {
+    build_connection_pool(database_url, true)
+}
+
+/// Runs the pending migrations in a single transaction, so that if one fails the database is
+/// left as it was rather than part migrated.
+pub fn run_migrations(conn: &mut SqliteConnection) -> Result<(), AppError> {
+    run_migrations_from(conn, MIGRATIONS)
+}
+
+fn run_migrations_from<S: MigrationSource<Sqlite>>(
+    conn: &mut SqliteConnection,
+    source: S,
+) -> Result<(), AppError> {
+    // Each migration runs in a savepoint within this transaction
+    conn.transaction::<_, Box<dyn std::error::Error + Send + Sync>, _>(|conn| {
+        conn.run_pending_migrations(source)?;
+        Ok(())
+    })
+    .map_err(|e| AppError::CustomError(format!("Failed to run migrations: {}", e)))
+}
+
+/// Whether an existing database has migrations to run, i.e. isn't new and isn't up to date.
+pub fn has_pending_migrations(conn: &mut SqliteConnection) -> bool {
+    let has_applied = conn
+        .applied_migrations()
+        .is_ok_and(|applied| !applied.is_empty());
+
+    has_applied
+        && conn
+            .pending_migrations(MIGRATIONS)
+            .is_ok_and(|pending| !pending.is_empty())
 }
 
 pub fn revert_all_migrations(conn: &mut SqliteConnection) {
@@ -21,74 +106,555 @@ pub fn revert_all_migrations(conn: &mut SqliteConnection) {
         .expect("Error reverting migrations");
 }
 
+#[derive(QueryableByName)]
+struct TimestampRange {
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    start: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    end: Option<NaiveDateTime>,
+}
+
+/// Sets the date id of the rows of `table_name` matching `filter` to their date in `timezone`,
+/// returning the number of rows changed.
+fn update_london_date_ids(
+    conn: &mut SqliteConnection,
+    table_name: &str,
+    timezone: Tz,
+    filter: &str,
+) -> Result<usize, diesel::result::Error> {
+    let range = sql_query(format!(
+        "SELECT MIN(timestamp) AS start, MAX(timestamp) AS end FROM {table_name} WHERE {filter}"
+    ))
+    .get_result::<TimestampRange>(conn)?;
+
+    let (Some(start), Some(end)) = (range.start, range.end) else {
+        return Ok(0);
+    };
+
+    let london_timestamp = london_timestamp_sql(timezone, start, end + ChronoDuration::seconds(1));
+
+    sql_query(format!(
+        r#"
+            UPDATE {table_name}
+            SET london_date_id = CAST(strftime('%Y%m%d', {london_timestamp}) AS INTEGER)
+            WHERE {filter}
+        "#
+    ))
+    .execute(conn)
+}
+
+/// Sets the date id of any readings without one, e.g. from before the column was added.
 pub fn populate_missing_london_date_ids(
     conn: &mut SqliteConnection,
 ) -> Result<(), RepositoryError> {
-    const BATCH_SIZE: i64 = 5000;
+    let timezone = reporting_timezone();
 
-    {
-        use crate::schema::electricity_consumption::dsl::*;
+    for fuel in Fuel::ALL {
+        let populated_count = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let populated_count = update_london_date_ids(
+                conn,
+                fuel.consumption_table(),
+                timezone,
+                "london_date_id IS NULL",
+            )?;
 
-        loop {
-            let records = electricity_consumption
-                .filter(london_date_id.is_null())
-                .limit(BATCH_SIZE)
-                .load::<crate::data::consumption::ElectricityConsumptionRecord>(conn)?;
-
-            if records.is_empty() {
-                break;
+            // The rollups only include readings with a date id
+            if populated_count > 0 {
+                rebuild_rollups(conn, fuel)?;
             }
 
+            Ok(populated_count)
+        })?;
+
+        if populated_count > 0 {
             info!(
-                "Populating london_date_id for {} electricity consumptionrecords",
-                records.len()
+                "Populated london_date_id for {} {} consumption records",
+                populated_count, fuel
             );
+        }
+    }
 
-            conn.transaction::<_, diesel::result::Error, _>(|transaction_conn| {
-                for row in records {
-                    let date_id = utc_timestamp_to_london_date_id(&row.timestamp);
+    Ok(())
+}
+
+/// Reads the timezone that the date ids are in. There's none for a database from before it was
+/// recorded, until the app next opens it.
+pub fn load_reporting_timezone(conn: &mut SqliteConnection) -> Result<Option<Tz>, AppError> {
+    let timezone = schema::reporting_timezone::table
+        .select(schema::reporting_timezone::timezone)
+        .first::<String>(conn)
+        .optional()
+        .map_err(|e| {
+            AppError::CustomError(format!("Failed to read the reporting timezone: {}", e))
+        })?;
+
+    timezone
+        .map(|timezone| {
+            timezone.parse::<Tz>().map_err(|e| {
+                AppError::CustomError(format!("Unknown reporting timezone {}: {}", timezone, e))
+            })
+        })
+        .transpose()
+}
 
-                    diesel::update(electricity_consumption.find(row.electricity_consumption_id))
-                        .set(london_date_id.eq(date_id))
-                        .execute(transaction_conn)?;
-                }
+/// Records the timezone that the date ids are in. Only a new database, or one without any, can
+/// change it without [recalculate_london_date_ids].
+pub fn store_reporting_timezone(conn: &mut SqliteConnection, timezone: Tz) -> QueryResult<()> {
+    diesel::replace_into(schema::reporting_timezone::table)
+        .values((
+            schema::reporting_timezone::reporting_timezone_id.eq(1),
+            schema::reporting_timezone::timezone.eq(timezone.name()),
+        ))
+        .execute(conn)?;
 
-                Ok(())
-            })?;
+    Ok(())
+}
+
+/// The timezone to write date ids in. Writers read it within their own transaction rather than
+/// using [reporting_timezone], so that nothing is written in a timezone that the app or the
+/// command line tool has just changed from, after the dates were recalculated.
+pub fn reporting_timezone_for_writing(conn: &mut SqliteConnection) -> Result<Tz, RepositoryError> {
+    load_reporting_timezone(conn)
+        .map_err(|e| RepositoryError::ReportingTimezoneError(e.to_string()))?
+        .ok_or_else(|| RepositoryError::ReportingTimezoneError("none is stored".into()))
+}
+
+/// Recalculates every date id for a new reporting timezone, and the rollups built from them,
+/// apart from those of daily temperatures, and records the new timezone alongside them.
+/// Stored anomalies are for days in the old timezone, so they are deleted and need detecting
+/// again.
+pub fn recalculate_london_date_ids(
+    conn: &mut SqliteConnection,
+    timezone: Tz,
+) -> Result<(), RepositoryError> {
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        for fuel in Fuel::ALL {
+            update_london_date_ids(conn, fuel.consumption_table(), timezone, "1 = 1")?;
+            rebuild_rollups(conn, fuel)?;
         }
+
+        // Daily temperatures are for a date, which doesn't change with the timezone
+        update_london_date_ids(conn, "weather_temperature", timezone, "NOT is_daily")?;
+
+        sql_query("DELETE FROM consumption_anomaly").execute(conn)?;
+
+        store_reporting_timezone(conn, timezone)?;
+
+        Ok(())
+    })?;
+
+    info!("Recalculated london_date_id for {}", timezone);
+
+    Ok(())
+}
+
+/// A migrated database in a temporary file, deleted when dropped.
+#[cfg(test)]
+pub struct TestDatabase {
+    path: std::path::PathBuf,
+    pub pool: SqliteConnectionPool,
+}
+
+#[cfg(test)]
+impl TestDatabase {
+    pub fn new() -> Self {
+        let database = Self::empty();
+        let mut conn = database.pool.get().unwrap();
+
+        run_migrations(&mut conn).unwrap();
+        store_reporting_timezone(&mut conn, crate::dates::DEFAULT_REPORTING_TIMEZONE).unwrap();
+
+        drop(conn);
+
+        database
     }
 
-    {
-        use crate::schema::gas_consumption::dsl::*;
+    /// A database without any migrations run.
+    pub fn empty() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "smart-energy-explorer-test-{}.sqlite",
+            uuid::Uuid::new_v4()
+        ));
 
-        loop {
-            let records = gas_consumption
-                .filter(london_date_id.is_null())
-                .limit(BATCH_SIZE)
-                .load::<crate::data::consumption::GasConsumptionRecord>(conn)?;
+        let pool = create_connection_pool(path.to_str().unwrap()).unwrap();
 
-            if records.is_empty() {
-                break;
-            }
+        Self { path, pool }
+    }
+}
 
-            info!(
-                "Populating london_date_id for {} gas consumption records",
-                records.len()
+#[cfg(test)]
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", self.path.display(), suffix));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::dates::utc_timestamp_to_london_date_id;
+
+    #[derive(QueryableByName)]
+    struct DateIdRecord {
+        #[diesel(sql_type = Timestamp)]
+        timestamp: NaiveDateTime,
+        #[diesel(sql_type = Nullable<diesel::sql_types::Integer>)]
+        london_date_id: Option<i32>,
+    }
+
+    fn table_names(conn: &mut SqliteConnection) -> Vec<String> {
+        #[derive(QueryableByName)]
+        struct TableName {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            name: String,
+        }
+
+        sql_query(
+            r#"
+                SELECT name FROM sqlite_master
+                WHERE type = 'table'
+                    AND name NOT IN ('__diesel_schema_migrations', 'sqlite_sequence')
+                ORDER BY name
+            "#,
+        )
+        .load::<TableName>(conn)
+        .unwrap()
+        .into_iter()
+        .map(|table| table.name)
+        .collect()
+    }
+
+    fn electricity_wh_total(conn: &mut SqliteConnection) -> i64 {
+        diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "(SELECT CAST(COALESCE(SUM(energy_consumption_wh), 0) AS BIGINT) FROM electricity_consumption)",
+        ))
+        .get_result(conn)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_every_migration_runs_up_and_down() {
+        let database = TestDatabase::empty();
+        let mut conn = database.pool.get().unwrap();
+
+        // A fixture from the first version of the schema, to be carried through every migration
+        conn.run_next_migration(MIGRATIONS).unwrap();
+
+        conn.batch_execute(
+            r#"
+                INSERT INTO electricity_consumption (timestamp, energy_consumption_kwh) VALUES
+                    ('2024-08-20 10:00:00', 0.123),
+                    ('2024-08-20 10:30:00', 0.2);
+                INSERT INTO gas_consumption (timestamp, energy_consumption_m3) VALUES
+                    ('2024-08-20 10:00:00', 1.5);
+            "#,
+        )
+        .unwrap();
+
+        while !conn.pending_migrations(MIGRATIONS).unwrap().is_empty() {
+            let version = conn.run_next_migration(MIGRATIONS).unwrap();
+
+            conn.revert_last_migration(MIGRATIONS)
+                .unwrap_or_else(|e| panic!("Reverting {} failed: {}", version, e));
+
+            conn.run_next_migration(MIGRATIONS)
+                .unwrap_or_else(|e| panic!("Running {} again failed: {}", version, e));
+        }
+
+        assert_eq!(electricity_wh_total(&mut conn), 323);
+
+        while !conn.applied_migrations().unwrap().is_empty() {
+            conn.revert_last_migration(MIGRATIONS).unwrap();
+        }
+
+        assert!(table_names(&mut conn).is_empty());
+    }
+
+    #[test]
+    fn test_failed_migrations_leave_the_database_unchanged() {
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
+
+        let applied_count = conn.applied_migrations().unwrap().len();
+        let tables = table_names(&mut conn);
+
+        let migrations_directory = std::env::temp_dir().join(format!(
+            "smart-energy-explorer-test-migrations-{}",
+            uuid::Uuid::new_v4()
+        ));
+
+        for (name, up) in [
+            (
+                "2100-01-01-000000_create_example",
+                "CREATE TABLE example (id INTEGER);",
+            ),
+            (
+                "2100-01-02-000000_broken",
+                "ALTER TABLE missing ADD COLUMN id INTEGER;",
+            ),
+        ] {
+            let directory = migrations_directory.join(name);
+            std::fs::create_dir_all(&directory).unwrap();
+            std::fs::write(directory.join("up.sql"), up).unwrap();
+            std::fs::write(directory.join("down.sql"), "").unwrap();
+        }
+
+        let migrations =
+            diesel_migrations::FileBasedMigrations::from_path(&migrations_directory).unwrap();
+
+        assert!(run_migrations_from(&mut conn, migrations).is_err());
+        assert_eq!(conn.applied_migrations().unwrap().len(), applied_count);
+        assert_eq!(table_names(&mut conn), tables);
+
+        std::fs::remove_dir_all(&migrations_directory).unwrap();
+    }
+
+    #[test]
+    fn test_quality_flag_migration_backfills_existing_readings() {
+        use diesel::migration::Migration;
+
+        let database = TestDatabase::empty();
+        let mut conn = database.pool.get().unwrap();
+
+        while !conn.pending_migrations(MIGRATIONS).unwrap()[0]
+            .name()
+            .to_string()
+            .starts_with("2026-10-19-130000")
+        {
+            conn.run_next_migration(MIGRATIONS).unwrap();
+        }
+
+        conn.batch_execute(
+            r#"
+                INSERT INTO electricity_consumption (timestamp, energy_consumption_wh) VALUES
+                    ('2026-01-15 09:50:00', 100),
+                    ('2026-01-15 10:00:00', 100),
+                    ('2026-01-15 10:15:00', 100),
+                    ('2026-01-15 10:30:00', 16777215),
+                    ('2026-01-15 11:00:00', -1),
+                    ('2026-01-15 11:30:00', 25001);
+            "#,
+        )
+        .unwrap();
+
+        run_migrations(&mut conn).unwrap();
+
+        #[derive(QueryableByName)]
+        struct QualityFlagRecord {
+            #[diesel(sql_type = diesel::sql_types::Integer)]
+            quality_flag: i32,
+        }
+
+        let flags: Vec<i32> =
+            sql_query("SELECT quality_flag FROM electricity_consumption ORDER BY timestamp")
+                .load::<QualityFlagRecord>(&mut *conn)
+                .unwrap()
+                .into_iter()
+                .map(|x| x.quality_flag)
+                .collect();
+
+        // The stray reading at 09:50 doesn't stop the one at 10:00 being valid
+        assert_eq!(flags, vec![0, 0, 4, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_read_only_pool_rejects_writes() {
+        let database = TestDatabase::new();
+        let pool = create_read_only_connection_pool(database.path.to_str().unwrap()).unwrap();
+        let mut conn = pool.get().unwrap();
+
+        assert!(sql_query(
+            "INSERT INTO electricity_consumption (timestamp, energy_consumption_wh) VALUES ('2026-10-19 10:00:00', 1)",
+        )
+        .execute(&mut *conn)
+        .is_err());
+
+        assert_eq!(electricity_wh_total(&mut conn), 0);
+    }
+
+    #[test]
+    fn test_populate_missing_london_date_ids_around_clock_changes() {
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
+
+        let timestamps: Vec<NaiveDateTime> = [
+            (2026, 3, 28, 23, 30),
+            (2026, 3, 29, 0, 30),
+            (2026, 3, 29, 23, 0),
+            (2026, 7, 15, 22, 30),
+            (2026, 7, 15, 23, 0),
+            (2026, 10, 24, 23, 0),
+            (2026, 10, 25, 23, 30),
+            (2026, 12, 31, 23, 30),
+        ]
+        .iter()
+        .map(|(year, month, day, hour, minute)| {
+            NaiveDate::from_ymd_opt(*year, *month, *day)
+                .unwrap()
+                .and_hms_opt(*hour, *minute, 0)
+                .unwrap()
+        })
+        .collect();
+
+        for timestamp in &timestamps {
+            sql_query(
+                "INSERT INTO electricity_consumption (timestamp, energy_consumption_wh) VALUES (?, 100)",
+            )
+            .bind::<Timestamp, _>(*timestamp)
+            .execute(&mut *conn)
+            .unwrap();
+        }
+
+        populate_missing_london_date_ids(&mut conn).unwrap();
+
+        let records = sql_query("SELECT timestamp, london_date_id FROM electricity_consumption")
+            .load::<DateIdRecord>(&mut *conn)
+            .unwrap();
+
+        assert_eq!(records.len(), timestamps.len());
+
+        for record in records {
+            assert_eq!(
+                record.london_date_id,
+                Some(utc_timestamp_to_london_date_id(&record.timestamp)),
+                "{}",
+                record.timestamp
             );
+        }
+    }
+
+    #[test]
+    fn test_recalculate_london_date_ids_for_a_new_timezone() {
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
 
-            conn.transaction::<_, diesel::result::Error, _>(|transaction_conn| {
-                for row in records {
-                    let date_id = utc_timestamp_to_london_date_id(&row.timestamp);
+        let timezone = chrono_tz::America::New_York;
 
-                    diesel::update(gas_consumption.find(row.gas_consumption_id))
-                        .set(london_date_id.eq(date_id))
-                        .execute(transaction_conn)?;
-                }
+        // Either side of midnight in New York, in winter and in summer
+        let timestamps: Vec<NaiveDateTime> = [
+            (2026, 1, 15, 4, 30),
+            (2026, 1, 15, 5, 0),
+            (2026, 7, 15, 3, 30),
+            (2026, 7, 15, 4, 0),
+        ]
+        .iter()
+        .map(|(year, month, day, hour, minute)| {
+            NaiveDate::from_ymd_opt(*year, *month, *day)
+                .unwrap()
+                .and_hms_opt(*hour, *minute, 0)
+                .unwrap()
+        })
+        .collect();
 
-                Ok(())
-            })?;
+        for timestamp in &timestamps {
+            sql_query(
+                r#"
+                    INSERT INTO gas_consumption (timestamp, energy_consumption_wh, london_date_id)
+                    VALUES (?, 100, ?)
+                "#,
+            )
+            .bind::<Timestamp, _>(*timestamp)
+            .bind::<diesel::sql_types::Integer, _>(utc_timestamp_to_london_date_id(timestamp))
+            .execute(&mut *conn)
+            .unwrap();
         }
+
+        recalculate_london_date_ids(&mut conn, timezone).unwrap();
+
+        let date_ids: Vec<Option<i32>> =
+            sql_query("SELECT timestamp, london_date_id FROM gas_consumption ORDER BY timestamp")
+                .load::<DateIdRecord>(&mut *conn)
+                .unwrap()
+                .into_iter()
+                .map(|record| record.london_date_id)
+                .collect();
+
+        assert_eq!(
+            date_ids,
+            vec![
+                Some(20260114),
+                Some(20260115),
+                Some(20260714),
+                Some(20260715)
+            ]
+        );
+
+        let rollup_count: i64 = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+            "(SELECT COUNT(*) FROM gas_daily_rollup)",
+        ))
+        .get_result(&mut *conn)
+        .unwrap();
+
+        assert_eq!(rollup_count, 4);
+        assert_eq!(load_reporting_timezone(&mut conn).unwrap(), Some(timezone));
     }
 
-    Ok(())
+    #[test]
+    fn test_reporting_timezone_is_stored_in_the_database() {
+        let database = TestDatabase::empty();
+        let mut conn = database.pool.get().unwrap();
+
+        run_migrations(&mut conn).unwrap();
+
+        assert_eq!(load_reporting_timezone(&mut conn).unwrap(), None);
+        assert!(reporting_timezone_for_writing(&mut conn).is_err());
+
+        store_reporting_timezone(&mut conn, chrono_tz::Europe::London).unwrap();
+        store_reporting_timezone(&mut conn, chrono_tz::Australia::Sydney).unwrap();
+
+        assert_eq!(
+            load_reporting_timezone(&mut conn).unwrap(),
+            Some(chrono_tz::Australia::Sydney)
+        );
+
+        sql_query("UPDATE reporting_timezone SET timezone = 'Mars/Olympus_Mons'")
+            .execute(&mut *conn)
+            .unwrap();
+
+        assert!(load_reporting_timezone(&mut conn).is_err());
+    }
+
+    #[test]
+    fn test_recalculate_keeps_the_date_of_daily_temperatures() {
+        use crate::data::weather::{SqliteWeatherRepository, TemperatureValue, WeatherRepository};
+
+        let database = TestDatabase::new();
+        let mut conn = database.pool.get().unwrap();
+
+        let january_15th = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+
+        // A daily reading for January 15th in London, and an hourly one at 22:00 the day before
+        // in New York
+        SqliteWeatherRepository::new(database.pool.clone())
+            .insert(vec![
+                TemperatureValue {
+                    timestamp: january_15th.and_hms_opt(0, 0, 0).unwrap(),
+                    temperature_celsius: 4.0,
+                    is_daily: true,
+                },
+                TemperatureValue {
+                    timestamp: january_15th.and_hms_opt(3, 0, 0).unwrap(),
+                    temperature_celsius: 2.0,
+                    is_daily: false,
+                },
+            ])
+            .unwrap();
+
+        recalculate_london_date_ids(&mut conn, chrono_tz::America::New_York).unwrap();
+
+        let date_ids: Vec<Option<i32>> = sql_query(
+            "SELECT timestamp, london_date_id FROM weather_temperature ORDER BY timestamp",
+        )
+        .load::<DateIdRecord>(&mut *conn)
+        .unwrap()
+        .into_iter()
+        .map(|record| record.london_date_id)
+        .collect();
+
+        assert_eq!(date_ids, vec![Some(20260115), Some(20260114)]);
+    }
 }
@@ -1,39 +1,45 @@
-use std::{cmp, error::Error, future::Future, sync::Arc};
+use std::{
+    cmp,
+    error::Error,
+    future::Future,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
 
 use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
-use log::{debug, error, info};
-use serde::Serialize;
-use tauri::{async_runtime, AppHandle};
+use log::{error, info};
 
 use crate::{
+    analysis::anomaly::detect_and_store_anomalies,
     clients::data_provider::EnergyDataProvider,
     data::{
         consumption::{
             ConsumptionRepository, ElectricityConsumptionValue, GasConsumptionValue,
             SqliteElectricityConsumptionRepository, SqliteGasConsumptionRepository,
         },
-        energy_profile::{EnergyProfileRepository, SqliteEnergyProfileRepository},
+        energy_profile::{EnergyProfile, EnergyProfileRepository, SqliteEnergyProfileRepository},
         tariff::{
             NewElectricityTariffPlan, NewGasTariffPlan, SqliteElectricityTariffRepository,
             SqliteGasTariffRepository, TariffPlan, TariffRepository,
         },
-        RepositoryError,
+        Fuel, RepositoryError,
     },
+    dates::london_today,
     db::SqliteConnectionPool,
-    utils::{emit_event, get_or_create_energy_profile},
-    AppError, AppState,
+    AppError,
 };
 
-#[derive(Serialize, Clone)]
-struct DownloadUpdateEvent {
-    percentage: u32,
-    name: String,
-}
+/// Anomalies are detected again over this many days after each download, as the most recent
+/// days may have been incomplete the last time.
+const ANOMALY_DETECTION_DAYS: i64 = 14;
 
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct AppStatusUpdateEvent {
-    pub is_downloading: bool,
+/// Receives the progress of each download, as a percentage of the days to download. The app
+/// shows it in the status bar and the command line tool logs it.
+pub trait DownloadProgress {
+    fn update(&self, percentage: f64, download_name: &str) -> Result<(), AppError>;
+
+    /// Called once each fuel has synced, or failed to, with how long it took.
+    fn finished(&self, _fuel: Fuel, _duration: StdDuration, _succeeded: bool) {}
 }
 
 pub trait DataLoader<T> {
@@ -223,27 +229,8 @@ where
     }
 }
 
-struct DownloadUpdateEventEmitter<'a> {
-    app_handle: &'a AppHandle,
-}
-
-impl<'a> DownloadUpdateEventEmitter<'a> {
-    pub fn update(&self, percentage: f64, download_name: &str) -> Result<(), AppError> {
-        emit_event(
-            &self.app_handle,
-            "downloadUpdate",
-            DownloadUpdateEvent {
-                percentage: percentage.round() as u32,
-                name: download_name.into(),
-            },
-        )?;
-
-        Ok(())
-    }
-}
-
-pub async fn download_history<T, U>(
-    app_handle: AppHandle,
+pub async fn download_history<T, U, P>(
+    progress: &P,
     data_loader: T,
     until_date_time: NaiveDateTime,
     download_name: &str,
@@ -251,6 +238,7 @@ pub async fn download_history<T, U>(
 where
     T: DataLoader<U>,
     T::LoadError: Error + Send + Sync + 'static,
+    P: DownloadProgress,
 {
     let until_date = until_date_time.date();
 
@@ -264,10 +252,6 @@ where
 
     let total_days = today.signed_duration_since(until_date).num_days();
 
-    let download_update_event_emitter = DownloadUpdateEventEmitter {
-        app_handle: &app_handle,
-    };
-
     while start_of_period >= until_date && start_of_period < end_date {
         let records = data_loader
             .load(start_of_period, end_date)
@@ -299,100 +283,45 @@ where
 
         let percentage = 100.0 * (1.0 - (days_remaining as f64 / total_days as f64));
 
-        download_update_event_emitter.update(percentage, download_name)?;
+        progress.update(percentage, download_name)?;
     }
 
-    download_update_event_emitter.update(100f64, download_name)?;
+    progress.update(100f64, download_name)?;
 
     Ok(today)
 }
 
-struct DownloadGuard<'a> {
-    app_handle: &'a AppHandle,
-    downloading: &'a std::sync::Mutex<bool>,
-}
-
-impl<'a> Drop for DownloadGuard<'a> {
-    fn drop(&mut self) {
-        if let Ok(mut downloading) = self.downloading.lock() {
-            *downloading = false;
-        }
-
-        debug!("Emitting is_downloading = false event");
-
-        let event_publish_result = emit_event(
-            &self.app_handle,
-            "appStatusUpdate",
-            AppStatusUpdateEvent {
-                is_downloading: false,
-            },
-        );
-
-        if let Err(e) = event_publish_result {
-            error!(
-                "Failed to publish appStatusUpdate to indicate downloading stopped: {:?}",
-                e
-            );
-        }
-    }
-}
-
-pub async fn check_and_download_new_data<U>(
-    app_handle: AppHandle,
-    app_state: AppState,
+/// Downloads the consumption and tariffs for both fuels since they were last downloaded, then
+/// detects anomalies in the most recent days.
+pub async fn download_new_data<U, P>(
+    connection_pool: SqliteConnectionPool,
     data_provider: Arc<U>,
+    progress: &P,
+    anomaly_z_score: f64,
 ) -> Result<(), AppError>
 where
     U: EnergyDataProvider,
+    P: DownloadProgress,
 {
-    {
-        let mut downloading = app_state
-            .downloading
-            .lock()
-            .map_err(|e| AppError::CustomError(format!("Failed to acquire lock, error: {}", e)))?;
-
-        if *downloading {
-            return Ok(());
-        }
-
-        *downloading = true;
-    }
-
-    // Will notify downloading stopped and clean up downloading state on exit of this method
-    let _download_guard = DownloadGuard {
-        app_handle: &app_handle,
-        downloading: &app_state.downloading,
-    };
-
-    debug!("Emitting is_downloading = true event");
-
-    emit_event(
-        &app_handle,
-        "appStatusUpdate",
-        AppStatusUpdateEvent {
-            is_downloading: true,
-        },
-    )?;
-
     let electricity_consumption_data_loader = ElectricityConsumptionDataLoader {
         data_provider: data_provider.clone(),
-        connection_pool: app_state.db_pool.clone(),
+        connection_pool: connection_pool.clone(),
     };
 
     let electricity_tariff_data_loader = ElectricityTariffDataLoader {
         data_provider: data_provider.clone(),
-        connection_pool: app_state.db_pool.clone(),
+        connection_pool: connection_pool.clone(),
     };
 
-    let app_handle_clone = app_handle.clone();
+    let started = Instant::now();
 
-    check_for_new_data(
-        app_state.db_pool.clone(),
+    let result = check_for_new_data(
+        connection_pool.clone(),
         "electricity",
         "kWh",
         |until_date_time| async move {
             let date_one = download_history(
-                app_handle_clone.clone(),
+                progress,
                 electricity_consumption_data_loader,
                 until_date_time,
                 "electricity consumption",
@@ -400,7 +329,7 @@ where
             .await?;
 
             let date_two = download_history(
-                app_handle_clone,
+                progress,
                 electricity_tariff_data_loader,
                 until_date_time,
                 "electricity tariff",
@@ -410,27 +339,30 @@ where
             Ok(cmp::max(date_one, date_two))
         },
     )
-    .await?;
+    .await;
+
+    progress.finished(Fuel::Electricity, started.elapsed(), result.is_ok());
+    result?;
 
     let gas_consumption_data_loader = GasConsumptionDataLoader {
         data_provider: data_provider.clone(),
-        connection_pool: app_state.db_pool.clone(),
+        connection_pool: connection_pool.clone(),
     };
 
     let gas_tariff_data_loader = GasTariffDataLoader {
         data_provider: data_provider.clone(),
-        connection_pool: app_state.db_pool.clone(),
+        connection_pool: connection_pool.clone(),
     };
 
-    let app_handle_clone = app_handle.clone();
+    let started = Instant::now();
 
-    check_for_new_data(
-        app_state.db_pool.clone(),
+    let result = check_for_new_data(
+        connection_pool.clone(),
         "gas",
         "kWh",
         |until_date_time| async move {
             let date_one = download_history(
-                app_handle_clone.clone(),
+                progress,
                 gas_consumption_data_loader,
                 until_date_time,
                 "gas consumption",
@@ -438,7 +370,7 @@ where
             .await?;
 
             let date_two = download_history(
-                app_handle_clone,
+                progress,
                 gas_tariff_data_loader,
                 until_date_time,
                 "gas tariff",
@@ -448,11 +380,60 @@ where
             Ok(cmp::max(date_one, date_two))
         },
     )
-    .await?;
+    .await;
+
+    progress.finished(Fuel::Gas, started.elapsed(), result.is_ok());
+    result?;
+
+    detect_recent_anomalies(connection_pool, anomaly_z_score).await?;
+
+    Ok(())
+}
+
+async fn detect_recent_anomalies(
+    connection_pool: SqliteConnectionPool,
+    z_score: f64,
+) -> Result<(), AppError> {
+    // Today is never complete, so it isn't scored
+    let end = london_today();
+    let start = end - Duration::days(ANOMALY_DETECTION_DAYS);
+
+    for fuel in Fuel::ALL {
+        let connection_pool = connection_pool.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            detect_and_store_anomalies(connection_pool, fuel, start, end, z_score)
+        })
+        .await?;
+
+        match result {
+            Ok(anomaly_count) => info!("Found {} {} anomalies", anomaly_count, fuel),
+            Err(e) => error!("Failed to detect {} anomalies: {}", fuel, e),
+        }
+    }
 
     Ok(())
 }
 
+pub fn get_or_create_energy_profile(
+    connection_pool: SqliteConnectionPool,
+    name: &str,
+    base_unit: &str,
+) -> Result<EnergyProfile, AppError> {
+    let repository = SqliteEnergyProfileRepository::new(connection_pool);
+
+    repository.get_energy_profile(name).or_else(|get_error| {
+        repository
+            .create_energy_profile(name, base_unit)
+            .map_err(|create_error| {
+                AppError::CustomError(format!(
+                    "Failed to fetch profile {}, get error: {}, create error: {}",
+                    name, get_error, create_error
+                ))
+            })
+    })
+}
+
 async fn check_for_new_data<F, Fut>(
     connection_pool: SqliteConnectionPool,
     profile_name: &str,
@@ -497,27 +478,3 @@ where
 
     Ok(())
 }
-
-pub fn spawn_download_tasks<T>(
-    app_handle: AppHandle,
-    app_state: AppState,
-    data_provider: T,
-) -> Result<(), AppError>
-where
-    T: EnergyDataProvider + 'static,
-{
-    info!("Spawning download tasks");
-    let data_provider = Arc::new(data_provider);
-
-    async_runtime::spawn(async move {
-        match check_and_download_new_data(app_handle, app_state, data_provider).await {
-            Ok(_) => debug!("Data download tasks completed successfully"),
-            Err(e) => {
-                error!("Data download tasks panicked: {:?}", e);
-                // Handle the panic (e.g., restart the task, log the error, etc.)
-            }
-        }
-    });
-
-    Ok(())
-}
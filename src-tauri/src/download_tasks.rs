@@ -0,0 +1,166 @@
+use std::{sync::Arc, time::Duration};
+
+use log::{debug, error, info};
+use serde::Serialize;
+use tauri::{async_runtime, AppHandle, Manager};
+
+use crate::{
+    clients::data_provider::EnergyDataProvider,
+    data::Fuel,
+    download::{download_new_data, DownloadProgress},
+    utils::{emit_event, AnomalySettings},
+    AppError, AppState,
+};
+
+#[derive(Serialize, Clone)]
+struct DownloadUpdateEvent {
+    percentage: u32,
+    name: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AppStatusUpdateEvent {
+    pub is_downloading: bool,
+}
+
+struct DownloadUpdateEventEmitter<'a> {
+    app_handle: &'a AppHandle,
+}
+
+impl<'a> DownloadProgress for DownloadUpdateEventEmitter<'a> {
+    fn update(&self, percentage: f64, download_name: &str) -> Result<(), AppError> {
+        emit_event(
+            &self.app_handle,
+            "downloadUpdate",
+            DownloadUpdateEvent {
+                percentage: percentage.round() as u32,
+                name: download_name.into(),
+            },
+        )?;
+
+        Ok(())
+    }
+
+    fn finished(&self, fuel: Fuel, duration: Duration, succeeded: bool) {
+        let app_state = self.app_handle.state::<AppState>();
+
+        if let Ok(mut metrics) = app_state.metrics.lock() {
+            metrics.record_sync(fuel, duration, succeeded);
+        }
+    }
+}
+
+struct DownloadGuard<'a> {
+    app_handle: &'a AppHandle,
+    downloading: &'a std::sync::Mutex<bool>,
+}
+
+impl<'a> Drop for DownloadGuard<'a> {
+    fn drop(&mut self) {
+        if let Ok(mut downloading) = self.downloading.lock() {
+            *downloading = false;
+        }
+
+        debug!("Emitting is_downloading = false event");
+
+        let event_publish_result = emit_event(
+            &self.app_handle,
+            "appStatusUpdate",
+            AppStatusUpdateEvent {
+                is_downloading: false,
+            },
+        );
+
+        if let Err(e) = event_publish_result {
+            error!(
+                "Failed to publish appStatusUpdate to indicate downloading stopped: {:?}",
+                e
+            );
+        }
+    }
+}
+
+pub async fn check_and_download_new_data<U>(
+    app_handle: AppHandle,
+    app_state: AppState,
+    data_provider: Arc<U>,
+) -> Result<(), AppError>
+where
+    U: EnergyDataProvider,
+{
+    {
+        let mut downloading = app_state
+            .downloading
+            .lock()
+            .map_err(|e| AppError::CustomError(format!("Failed to acquire lock, error: {}", e)))?;
+
+        if *downloading {
+            return Ok(());
+        }
+
+        *downloading = true;
+    }
+
+    // Will notify downloading stopped and clean up downloading state on exit of this method
+    let _download_guard = DownloadGuard {
+        app_handle: &app_handle,
+        downloading: &app_state.downloading,
+    };
+
+    debug!("Emitting is_downloading = true event");
+
+    emit_event(
+        &app_handle,
+        "appStatusUpdate",
+        AppStatusUpdateEvent {
+            is_downloading: true,
+        },
+    )?;
+
+    let anomaly_z_score = {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| AppError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        AnomalySettings::from_app_settings(&app_settings)?.z_score
+    };
+
+    download_new_data(
+        app_state.db_pool.clone(),
+        data_provider,
+        &DownloadUpdateEventEmitter {
+            app_handle: &app_handle,
+        },
+        anomaly_z_score,
+    )
+    .await
+}
+
+pub fn spawn_download_tasks<T>(
+    app_handle: AppHandle,
+    app_state: AppState,
+    data_provider: T,
+) -> Result<(), AppError>
+where
+    T: EnergyDataProvider + 'static,
+{
+    info!("Spawning download tasks");
+    let data_provider = Arc::new(data_provider);
+
+    async_runtime::spawn(async move {
+        match check_and_download_new_data(app_handle, app_state, data_provider).await {
+            Ok(_) => debug!("Data download tasks completed successfully"),
+            Err(e) => {
+                error!("Data download tasks panicked: {:?}", e);
+                // Handle the panic (e.g., restart the task, log the error, etc.)
+            }
+        }
+    });
+
+    Ok(())
+}
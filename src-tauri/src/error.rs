@@ -0,0 +1,13 @@
+use crate::clients::glowmarkt::GlowmarktDataProviderError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Failed interaction with Glowmarkt API: {0}")]
+    GlowmarktApiError(#[from] GlowmarktDataProviderError),
+    #[error("Error: {0}")]
+    CustomError(String),
+    #[error("Mutex '{name}' is poisoned")]
+    MutexPoisonedError { name: String },
+    #[error("Background task execution failed: {0}")]
+    JoinError(#[from] tokio::task::JoinError),
+}
@@ -0,0 +1,606 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use parquet::{
+    basic::Compression,
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{
+        consumption::{
+            ConsumptionRepository, SqliteElectricityConsumptionRepository,
+            SqliteGasConsumptionRepository,
+        },
+        tariff::{SqliteElectricityTariffRepository, SqliteGasTariffRepository, TariffRepository},
+        Fuel, RepositoryError,
+    },
+    dates::{london_midnight_as_utc, reporting_timezone},
+    db::SqliteConnectionPool,
+    AppError,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Parquet,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportGranularity {
+    /// Each half-hourly reading
+    Raw,
+    Daily,
+    Monthly,
+}
+
+/// A row of an export: the consumption in a half hour, day or month, and what it cost. Field
+/// names are snake_case, the same in every format, for loading into pandas or a spreadsheet.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ExportRow {
+    pub fuel: Fuel,
+    pub period_start_utc: DateTime<Utc>,
+    /// The start of the period in the reporting timezone, with its offset
+    pub period_start_local: DateTime<FixedOffset>,
+    pub energy_consumption_wh: i64,
+    pub is_provisional: bool,
+    /// The unit price at the start of the period, except for months, when it may have changed
+    pub unit_price_pence: Option<f64>,
+    /// The daily standing charge, for days only
+    pub standing_charge_pence: Option<f64>,
+    /// The cost of the consumption, including standing charges for days and months
+    pub cost_pence: Option<f64>,
+}
+
+const CSV_HEADER: &str = "fuel,period_start_utc,period_start_local,energy_consumption_wh,is_provisional,unit_price_pence,standing_charge_pence,cost_pence";
+
+const PARQUET_SCHEMA: &str = r#"
+    message consumption_export {
+        REQUIRED BYTE_ARRAY fuel (UTF8);
+        REQUIRED INT64 period_start_utc (TIMESTAMP(MILLIS, true));
+        REQUIRED BYTE_ARRAY period_start_local (UTF8);
+        REQUIRED INT64 energy_consumption_wh;
+        REQUIRED BOOLEAN is_provisional;
+        OPTIONAL DOUBLE unit_price_pence;
+        OPTIONAL DOUBLE standing_charge_pence;
+        OPTIONAL DOUBLE cost_pence;
+    }
+"#;
+
+/// The unit prices and standing charges in effect over time.
+pub struct TariffTimeline {
+    standing_charges: BTreeMap<NaiveDateTime, f64>,
+    unit_prices: BTreeMap<NaiveDateTime, f64>,
+}
+
+impl TariffTimeline {
+    pub fn new(
+        standing_charges: impl IntoIterator<Item = (NaiveDateTime, f64)>,
+        unit_prices: impl IntoIterator<Item = (NaiveDateTime, f64)>,
+    ) -> Self {
+        Self {
+            standing_charges: standing_charges.into_iter().collect(),
+            unit_prices: unit_prices.into_iter().collect(),
+        }
+    }
+
+    pub fn standing_charge_on(&self, date: NaiveDate) -> Option<f64> {
+        self.standing_charges
+            .range(..=NaiveDateTime::from(date))
+            .next_back()
+            .map(|(_, v)| *v)
+    }
+
+    pub fn unit_price_at(&self, timestamp: NaiveDateTime) -> Option<f64> {
+        self.unit_prices
+            .range(..=timestamp)
+            .next_back()
+            .map(|(_, v)| *v)
+    }
+}
+
+fn local_timestamp(timestamp_utc: NaiveDateTime, timezone: Tz) -> DateTime<FixedOffset> {
+    timestamp_utc
+        .and_utc()
+        .with_timezone(&timezone)
+        .fixed_offset()
+}
+
+/// Rows for half-hourly readings of `(timestamp, Wh, is_provisional)`, costed at the unit
+/// price without the standing charge.
+pub fn raw_rows(
+    fuel: Fuel,
+    readings: &[(NaiveDateTime, i64, bool)],
+    tariffs: &TariffTimeline,
+    timezone: Tz,
+) -> Vec<ExportRow> {
+    readings
+        .iter()
+        .map(|(timestamp, wh, is_provisional)| {
+            let unit_price = tariffs.unit_price_at(*timestamp);
+
+            ExportRow {
+                fuel,
+                period_start_utc: timestamp.and_utc(),
+                period_start_local: local_timestamp(*timestamp, timezone),
+                energy_consumption_wh: *wh,
+                is_provisional: *is_provisional,
+                unit_price_pence: unit_price,
+                standing_charge_pence: None,
+                cost_pence: unit_price.map(|up| (*wh as f64) * up / 1000.0),
+            }
+        })
+        .collect()
+}
+
+/// Rows for daily totals of `(date, Wh, is_provisional)`, costed the same way as the cost
+/// history. `midnight_as_utc` gives the start of each day.
+pub fn daily_rows(
+    fuel: Fuel,
+    daily: &[(NaiveDate, i64, bool)],
+    tariffs: &TariffTimeline,
+    timezone: Tz,
+    midnight_as_utc: impl Fn(&NaiveDate) -> NaiveDateTime,
+) -> Vec<ExportRow> {
+    daily
+        .iter()
+        .map(|(date, wh, is_provisional)| {
+            let start = midnight_as_utc(date);
+            let standing_charge = tariffs.standing_charge_on(*date);
+            let unit_price = tariffs.unit_price_at(NaiveDateTime::from(*date));
+
+            ExportRow {
+                fuel,
+                period_start_utc: start.and_utc(),
+                period_start_local: local_timestamp(start, timezone),
+                energy_consumption_wh: *wh,
+                is_provisional: *is_provisional,
+                unit_price_pence: unit_price,
+                standing_charge_pence: standing_charge,
+                cost_pence: standing_charge
+                    .zip(unit_price)
+                    .map(|(sc, up)| sc + ((*wh as f64) * up / 1000.0)),
+            }
+        })
+        .collect()
+}
+
+/// Adds up daily rows, in date order, into a row for each month. A month's cost is missing if
+/// any of its days couldn't be costed.
+pub fn monthly_rows(daily: Vec<ExportRow>) -> Vec<ExportRow> {
+    let mut months: Vec<ExportRow> = vec![];
+
+    for day in daily {
+        let local_date = day.period_start_local.date_naive();
+
+        let same_month = months.last().is_some_and(|month| {
+            let month_date = month.period_start_local.date_naive();
+
+            month.fuel == day.fuel
+                && month_date.year() == local_date.year()
+                && month_date.month() == local_date.month()
+        });
+
+        match months.last_mut() {
+            Some(month) if same_month => {
+                month.energy_consumption_wh += day.energy_consumption_wh;
+                month.is_provisional |= day.is_provisional;
+                month.cost_pence = month.cost_pence.zip(day.cost_pence).map(|(a, b)| a + b);
+            }
+            _ => months.push(ExportRow {
+                unit_price_pence: None,
+                standing_charge_pence: None,
+                ..day
+            }),
+        }
+    }
+
+    months
+}
+
+/// Loads the stored standing charges and unit prices of a fuel.
+pub fn load_tariff_timeline(
+    connection_pool: SqliteConnectionPool,
+    fuel: Fuel,
+) -> Result<TariffTimeline, RepositoryError> {
+    let (standing_charges, unit_prices) = match fuel {
+        Fuel::Electricity => {
+            let repository = SqliteElectricityTariffRepository::new(connection_pool);
+
+            (
+                repository.get_standing_charge_history()?,
+                repository.get_unit_price_history()?,
+            )
+        }
+        Fuel::Gas => {
+            let repository = SqliteGasTariffRepository::new(connection_pool);
+
+            (
+                repository.get_standing_charge_history()?,
+                repository.get_unit_price_history()?,
+            )
+        }
+    };
+
+    Ok(TariffTimeline::new(
+        standing_charges
+            .iter()
+            .map(|x| (x.start_date, x.standing_charge_pence)),
+        unit_prices
+            .iter()
+            .map(|x| (x.price_effective_time, x.unit_price_pence)),
+    ))
+}
+
+/// Loads the rows of an export for a fuel. Invalid readings are left out, as they are from the
+/// charts.
+pub fn load_rows(
+    connection_pool: SqliteConnectionPool,
+    fuel: Fuel,
+    start: NaiveDate,
+    end: NaiveDate,
+    granularity: ExportGranularity,
+    tariffs: &TariffTimeline,
+) -> Result<Vec<ExportRow>, RepositoryError> {
+    let timezone = reporting_timezone();
+
+    if granularity == ExportGranularity::Raw {
+        let mut readings: Vec<_> = match fuel {
+            Fuel::Electricity => SqliteElectricityConsumptionRepository::new(connection_pool)
+                .get_raw(start, end)?
+                .into_iter()
+                .map(|x| (x.timestamp, x.energy_consumption_wh, x.is_provisional))
+                .collect(),
+            Fuel::Gas => SqliteGasConsumptionRepository::new(connection_pool)
+                .get_raw(start, end)?
+                .into_iter()
+                .map(|x| (x.timestamp, x.energy_consumption_wh, x.is_provisional))
+                .collect(),
+        };
+
+        readings.sort_by_key(|x| x.0);
+
+        return Ok(raw_rows(fuel, &readings, tariffs, timezone));
+    }
+
+    let mut daily = match fuel {
+        Fuel::Electricity => {
+            SqliteElectricityConsumptionRepository::new(connection_pool).get_daily(start, end)?
+        }
+        Fuel::Gas => SqliteGasConsumptionRepository::new(connection_pool).get_daily(start, end)?,
+    };
+
+    daily.sort_by_key(|x| x.0);
+
+    let rows = daily_rows(fuel, &daily, tariffs, timezone, london_midnight_as_utc);
+
+    Ok(match granularity {
+        ExportGranularity::Monthly => monthly_rows(rows),
+        _ => rows,
+    })
+}
+
+fn export_error(e: impl std::fmt::Display) -> AppError {
+    AppError::CustomError(format!("Failed to write export: {}", e))
+}
+
+fn format_optional(value: Option<f64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn write_csv(writer: &mut impl Write, rows: &[ExportRow]) -> Result<(), AppError> {
+    writeln!(writer, "{}", CSV_HEADER).map_err(export_error)?;
+
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            row.fuel,
+            row.period_start_utc
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            row.period_start_local
+                .to_rfc3339_opts(SecondsFormat::Secs, false),
+            row.energy_consumption_wh,
+            row.is_provisional,
+            format_optional(row.unit_price_pence),
+            format_optional(row.standing_charge_pence),
+            format_optional(row.cost_pence),
+        )
+        .map_err(export_error)?;
+    }
+
+    Ok(())
+}
+
+fn write_json_lines(writer: &mut impl Write, rows: &[ExportRow]) -> Result<(), AppError> {
+    for row in rows {
+        serde_json::to_writer(&mut *writer, row).map_err(export_error)?;
+        writeln!(writer).map_err(export_error)?;
+    }
+
+    Ok(())
+}
+
+/// Splits an optional column into its values and the definition levels that mark which rows
+/// have one.
+fn optional_column(values: impl Iterator<Item = Option<f64>>) -> (Vec<f64>, Vec<i16>) {
+    let mut present = vec![];
+    let mut definition_levels = vec![];
+
+    for value in values {
+        match value {
+            Some(v) => {
+                present.push(v);
+                definition_levels.push(1);
+            }
+            None => definition_levels.push(0),
+        }
+    }
+
+    (present, definition_levels)
+}
+
+fn write_parquet(file: File, rows: &[ExportRow]) -> Result<(), AppError> {
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA).map_err(export_error)?);
+    let properties = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+
+    let mut writer = SerializedFileWriter::new(file, schema, properties).map_err(export_error)?;
+    let mut row_group = writer.next_row_group().map_err(export_error)?;
+
+    let mut column_index = 0;
+
+    while let Some(mut column) = row_group.next_column().map_err(export_error)? {
+        match column_index {
+            0 | 2 => {
+                let values: Vec<ByteArray> = rows
+                    .iter()
+                    .map(|row| match column_index {
+                        0 => ByteArray::from(row.fuel.as_str()),
+                        _ => ByteArray::from(
+                            row.period_start_local
+                                .to_rfc3339_opts(SecondsFormat::Secs, false)
+                                .as_str(),
+                        ),
+                    })
+                    .collect();
+
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)
+                    .map_err(export_error)?;
+            }
+            1 | 3 => {
+                let values: Vec<i64> = rows
+                    .iter()
+                    .map(|row| match column_index {
+                        1 => row.period_start_utc.timestamp_millis(),
+                        _ => row.energy_consumption_wh,
+                    })
+                    .collect();
+
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)
+                    .map_err(export_error)?;
+            }
+            4 => {
+                let values: Vec<bool> = rows.iter().map(|row| row.is_provisional).collect();
+
+                column
+                    .typed::<BoolType>()
+                    .write_batch(&values, None, None)
+                    .map_err(export_error)?;
+            }
+            _ => {
+                let (values, definition_levels) =
+                    optional_column(rows.iter().map(|row| match column_index {
+                        5 => row.unit_price_pence,
+                        6 => row.standing_charge_pence,
+                        _ => row.cost_pence,
+                    }));
+
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(&values, Some(&definition_levels), None)
+                    .map_err(export_error)?;
+            }
+        }
+
+        column.close().map_err(export_error)?;
+        column_index += 1;
+    }
+
+    row_group.close().map_err(export_error)?;
+    writer.close().map_err(export_error)?;
+
+    Ok(())
+}
+
+/// Writes rows to a file in the chosen format, replacing it if it exists.
+pub fn write_export(path: &Path, format: ExportFormat, rows: &[ExportRow]) -> Result<(), AppError> {
+    let file = File::create(path).map_err(|e| {
+        AppError::CustomError(format!("Failed to create {}: {}", path.display(), e))
+    })?;
+
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = BufWriter::new(file);
+            write_csv(&mut writer, rows)?;
+            writer.flush().map_err(export_error)
+        }
+        ExportFormat::JsonLines => {
+            let mut writer = BufWriter::new(file);
+            write_json_lines(&mut writer, rows)?;
+            writer.flush().map_err(export_error)
+        }
+        ExportFormat::Parquet => write_parquet(file, rows),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono_tz::Europe::London;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn tariffs() -> TariffTimeline {
+        TariffTimeline::new(
+            [(NaiveDateTime::from(date(2026, 1, 1)), 50.0)],
+            [
+                (NaiveDateTime::from(date(2026, 1, 1)), 20.0),
+                (NaiveDateTime::from(date(2026, 7, 1)), 25.0),
+            ],
+        )
+    }
+
+    fn london_midnight(date: &NaiveDate) -> NaiveDateTime {
+        London
+            .from_local_datetime(&NaiveDateTime::from(*date))
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn daily() -> Vec<ExportRow> {
+        daily_rows(
+            Fuel::Electricity,
+            &[
+                (date(2026, 6, 30), 10_000, false),
+                (date(2026, 7, 1), 8_000, false),
+                (date(2026, 7, 2), 6_000, true),
+            ],
+            &tariffs(),
+            London,
+            london_midnight,
+        )
+    }
+
+    #[test]
+    fn test_daily_rows_are_costed_with_the_standing_charge() {
+        let rows = daily();
+
+        assert_eq!(rows[0].cost_pence, Some(250.0));
+        assert_eq!(rows[1].cost_pence, Some(250.0));
+        assert_eq!(rows[1].unit_price_pence, Some(25.0));
+        assert_eq!(rows[1].standing_charge_pence, Some(50.0));
+        assert_eq!(
+            rows[1].period_start_utc.naive_utc(),
+            date(2026, 6, 30).and_hms_opt(23, 0, 0).unwrap()
+        );
+        assert_eq!(
+            rows[1].period_start_local.to_rfc3339(),
+            "2026-07-01T00:00:00+01:00"
+        );
+    }
+
+    #[test]
+    fn test_raw_rows_are_costed_without_the_standing_charge() {
+        let timestamp = date(2026, 1, 15).and_hms_opt(12, 0, 0).unwrap();
+
+        let rows = raw_rows(Fuel::Gas, &[(timestamp, 500, false)], &tariffs(), London);
+
+        assert_eq!(rows[0].cost_pence, Some(10.0));
+        assert_eq!(rows[0].standing_charge_pence, None);
+    }
+
+    #[test]
+    fn test_monthly_rows_add_up_days_in_each_local_month() {
+        let months = monthly_rows(daily());
+
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].energy_consumption_wh, 10_000);
+        assert_eq!(months[1].energy_consumption_wh, 14_000);
+        assert_eq!(months[1].cost_pence, Some(250.0 + 200.0));
+        assert!(months[1].is_provisional);
+        assert_eq!(months[1].unit_price_pence, None);
+    }
+
+    #[test]
+    fn test_uncosted_days_leave_the_month_uncosted() {
+        let rows = daily_rows(
+            Fuel::Gas,
+            &[(date(2025, 12, 31), 1_000, false)],
+            &tariffs(),
+            London,
+            london_midnight,
+        );
+
+        assert_eq!(rows[0].cost_pence, None);
+        assert_eq!(monthly_rows(rows)[0].cost_pence, None);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let mut output = vec![];
+        write_csv(&mut output, &daily()[2..]).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "{}\nelectricity,2026-07-01T23:00:00Z,2026-07-02T00:00:00+01:00,6000,true,25,50,200\n",
+                CSV_HEADER
+            )
+        );
+    }
+
+    #[test]
+    fn test_json_lines_export() {
+        let mut output = vec![];
+        write_json_lines(&mut output, &daily()).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["fuel"], "electricity");
+        assert_eq!(lines[0]["period_start_local"], "2026-06-30T00:00:00+01:00");
+        assert_eq!(lines[0]["cost_pence"], 250.0);
+    }
+
+    #[test]
+    fn test_parquet_export() {
+        let path = std::env::temp_dir().join(format!(
+            "smart-energy-explorer-export-{}.parquet",
+            uuid::Uuid::new_v4()
+        ));
+
+        write_export(&path, ExportFormat::Parquet, &daily()).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .num_columns(),
+            8
+        );
+    }
+}
@@ -0,0 +1,426 @@
+//! The app itself. The modules that don't need a desktop session are public, so the command line
+//! tool can share them.
+
+use app_settings::{AppSettings, SETTINGS_FILE};
+use chrono_tz::Tz;
+use log::{debug, error, warn};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::Window;
+use tauri::{async_runtime, Manager};
+use tauri_plugin_log::{Target, TargetKind};
+use tauri_plugin_store::StoreExt;
+use tokio::sync::mpsc::Sender;
+use utils::{get_glowmarkt_data_provider, switch_splashscreen_to_main};
+
+use commands::alerts::*;
+use commands::analysis::*;
+use commands::api_server::*;
+use commands::app::*;
+use commands::backup::*;
+use commands::electricity::*;
+use commands::export::*;
+use commands::gas::*;
+use commands::glowmarkt::*;
+use commands::mqtt::*;
+use commands::profiles::*;
+use commands::quality::*;
+use commands::weather::*;
+
+use crate::api_server::restart_api_server;
+use crate::backup::apply_staged_restore;
+use crate::dates::{set_reporting_timezone, DEFAULT_REPORTING_TIMEZONE};
+use crate::db::{
+    load_reporting_timezone, populate_missing_london_date_ids, store_reporting_timezone,
+    SqliteConnectionPool,
+};
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::mqtt::start_mqtt_listener;
+use crate::startup::{migrate_with_snapshot, show_startup_error_dialog, StartupError};
+use crate::utils::MqttSettings;
+use crate::utils::{
+    ensure_mqtt_client_id, get_all_mqtt_settings, MqttAppSettings, MqttBrokerConfig,
+};
+use crate::utils::{BackupSettings, TimezoneSettings};
+
+mod alerts;
+pub mod analysis;
+mod api_server;
+mod app_settings;
+pub mod backup;
+pub mod clients;
+mod commands;
+pub mod data;
+pub mod dates;
+pub mod db;
+pub mod download;
+mod download_tasks;
+pub mod error;
+pub mod export;
+mod metrics;
+mod mqtt;
+mod mqtt_recording;
+mod provisional;
+mod retry;
+mod schema;
+pub mod serde_utils;
+mod startup;
+mod utils;
+mod weather;
+
+struct AppState {
+    db_pool: SqliteConnectionPool,
+    db_path: PathBuf,
+    downloading: Arc<Mutex<bool>>,
+    client_available: Arc<Mutex<bool>>,
+    app_settings: Arc<Mutex<AppSettings>>,
+    mqtt_settings: Arc<Mutex<Vec<MqttSettings>>>,
+    mqtt_message_sender: Arc<Sender<MqttMessage>>,
+    /// The local HTTP API, while it's running
+    api_server: Arc<Mutex<Option<async_runtime::JoinHandle<()>>>>,
+    metrics: Arc<Mutex<Metrics>>,
+    /// Set if the database couldn't be migrated, in which case it's open read-only
+    startup_error: Option<StartupError>,
+}
+
+impl Clone for AppState {
+    fn clone(&self) -> Self {
+        Self {
+            db_pool: self.db_pool.clone(),
+            db_path: self.db_path.clone(),
+            downloading: self.downloading.clone(),
+            client_available: self.client_available.clone(),
+            app_settings: self.app_settings.clone(),
+            mqtt_settings: self.mqtt_settings.clone(),
+            mqtt_message_sender: self.mqtt_message_sender.clone(),
+            api_server: self.api_server.clone(),
+            metrics: self.metrics.clone(),
+            startup_error: self.startup_error.clone(),
+        }
+    }
+}
+
+pub enum MqttMessage {
+    SettingsUpdated,
+    AlertRulesUpdated,
+}
+
+/// The timezone of the app's setting, which the dates in a database from before the timezone was
+/// recorded in it are in.
+fn timezone_from_app_settings(app_settings: &AppSettings) -> Result<Tz, AppError> {
+    match TimezoneSettings::from_app_settings(app_settings)?.parse() {
+        Ok(timezone) => Ok(timezone),
+        Err(e) => {
+            warn!("Using the default reporting timezone: {}", e);
+            Ok(DEFAULT_REPORTING_TIMEZONE)
+        }
+    }
+}
+
+fn set_close_handlers(window: &Window) {
+    window.on_window_event(|event| {
+        if let tauri::WindowEvent::CloseRequested { .. } = event {
+            std::process::exit(0);
+        }
+    });
+
+    ()
+}
+
+fn set_default_store() -> keyring_core::Result<()> {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    {
+        use apple_native_keyring_store::keychain::Store;
+        keyring_core::set_default_store(Store::new()?);
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        use windows_native_keyring_store::Store;
+        keyring_core::set_default_store(Store::new()?);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use zbus_secret_service_keyring_store::Store;
+        keyring_core::set_default_store(Store::new()?);
+    }
+
+    Ok(())
+}
+
+pub fn run() {
+    // Fix for Linux systems with WebKitGTK 2.38+ where DMA-BUF renderer is enabled by default, which can cause issues with rendering (showing a jagged distorted window) in some environments.
+    #[cfg(target_os = "linux")]
+    {
+        std::env::set_var("WEBKIT_DISABLE_DMABUF_RENDERER", "1");
+    }
+
+    set_default_store()
+        .map_err(|e| {
+            error!("Encountered error setting default keyring store: {}", e);
+        })
+        .expect("Failed to set default keyring store");
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_store::Builder::new().build())
+        .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            let app_data_dir = app
+                .path()
+                .app_data_dir()
+                .expect("Failed to resolve app data directory");
+
+            debug!("App data directory: {}", app_data_dir.display());
+
+            if !app_data_dir.exists() {
+                fs::create_dir_all(&app_data_dir)
+                    .expect("app data directory does not exist and cannot be created");
+            }
+
+            let db_path = app_data_dir.join("db.sqlite");
+
+            apply_staged_restore(&db_path)?;
+
+            let db_url = db_path.to_str().expect("db path needed");
+
+            let db_connection_pool = db::create_connection_pool(db_url)
+                .expect("Failed to create database connection pool");
+
+            let store = app.store(SETTINGS_FILE)?;
+
+            let app_settings = AppSettings::new(store);
+
+            let startup_error = {
+                let mut connection = db_connection_pool
+                    .get()
+                    .expect("Failed to get connection from pool");
+
+                let backup_settings = BackupSettings::from_app_settings(&app_settings)?;
+
+                match migrate_with_snapshot(&mut connection, &db_path, &backup_settings) {
+                    Ok(()) => {
+                        // Date ids are populated in the reporting timezone, so it's needed first
+                        let timezone = match load_reporting_timezone(&mut connection)? {
+                            Some(timezone) => timezone,
+                            None => {
+                                let timezone = timezone_from_app_settings(&app_settings)?;
+                                store_reporting_timezone(&mut connection, timezone)?;
+                                timezone
+                            }
+                        };
+
+                        set_reporting_timezone(timezone);
+
+                        populate_missing_london_date_ids(&mut connection)?;
+
+                        None
+                    }
+                    Err(startup_error) => {
+                        // The table may not exist yet, and nothing can be recorded
+                        let timezone = match load_reporting_timezone(&mut connection) {
+                            Ok(Some(timezone)) => timezone,
+                            _ => timezone_from_app_settings(&app_settings)?,
+                        };
+
+                        set_reporting_timezone(timezone);
+
+                        Some(startup_error)
+                    }
+                }
+            };
+
+            // The schema isn't what this version expects, so nothing may write to it
+            let db_connection_pool = match startup_error {
+                Some(_) => {
+                    drop(db_connection_pool);
+
+                    db::create_read_only_connection_pool(db_url)
+                        .expect("Failed to create database connection pool")
+                }
+                None => db_connection_pool,
+            };
+
+            ensure_mqtt_client_id(&app_settings)?;
+
+            let mqtt_app_settings = MqttAppSettings::from_app_settings(&app_settings)?;
+
+            let mqtt_brokers = MqttBrokerConfig::load_all(&app_settings)?;
+
+            let mqtt_settings = tauri::async_runtime::block_on(async {
+                get_all_mqtt_settings(mqtt_app_settings, mqtt_brokers).await
+            })
+            .expect("Failed to get MQTT settings");
+
+            let (tx, rx) = tokio::sync::mpsc::channel::<MqttMessage>(1);
+
+            let app_state = AppState {
+                db_pool: db_connection_pool,
+                db_path,
+                downloading: Arc::new(Mutex::new(false)),
+                client_available: Arc::new(Mutex::new(false)),
+                app_settings: Arc::new(Mutex::new(app_settings)),
+                mqtt_settings: Arc::new(Mutex::new(mqtt_settings)),
+                mqtt_message_sender: Arc::new(tx),
+                api_server: Arc::new(Mutex::new(None)),
+                metrics: Arc::new(Mutex::new(Metrics::default())),
+                startup_error,
+            };
+
+            app.manage(app_state.clone());
+
+            {
+                let app_settings = app_state.app_settings.lock().unwrap();
+
+                if let Some(true) = app_settings.get::<bool>("termsAccepted")? {
+                    switch_splashscreen_to_main(app.handle());
+                }
+            }
+
+            let window = app.get_window("main").unwrap();
+
+            set_close_handlers(&window);
+
+            let window = app.get_window("splashscreen").unwrap();
+
+            set_close_handlers(&window);
+
+            // The API only reads, so it's available even if the database is read-only
+            async_runtime::spawn({
+                let app_handle_clone = app.handle().clone();
+
+                async move {
+                    if let Err(e) = restart_api_server(&app_handle_clone).await {
+                        error!("Failed to start the API server: {}", e);
+                    }
+                }
+            });
+
+            if let Some(startup_error) = &app_state.startup_error {
+                show_startup_error_dialog(app.handle(), app_state.db_path.clone(), startup_error);
+
+                // Downloads and the MQTT listener write readings, so they're not started
+                return Ok(());
+            }
+
+            async_runtime::spawn({
+                let app_handle_clone = app.handle().clone();
+
+                async move { start_mqtt_listener(&app_handle_clone, rx).await }
+            });
+
+            async_runtime::spawn({
+                let app_state_clone = app_state.clone();
+                let app_handle_clone = app.handle().clone();
+
+                async move {
+                    if let Ok(Some(data_provider)) = get_glowmarkt_data_provider().await {
+                        {
+                            let mut client_available =
+                                app_state_clone.client_available.lock().unwrap();
+                            *client_available = true;
+                        }
+
+                        if let Err(e) = download_tasks::spawn_download_tasks(
+                            app_handle_clone,
+                            app_state_clone,
+                            data_provider,
+                        ) {
+                            error!("Failed to spawn download tasks: {}", e);
+                        }
+                    }
+                }
+            });
+
+            Ok(())
+        })
+        .plugin(tauri_plugin_sql::Builder::default().build())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_fs::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .targets([
+                    Target::new(TargetKind::Stdout),
+                    Target::new(TargetKind::Webview),
+                ])
+                .build(),
+        )
+        .invoke_handler(tauri::generate_handler![
+            add_mqtt_broker,
+            backup_database,
+            clear_all_data,
+            close_welcome_screen,
+            compare_consumption,
+            delete_alert_rule,
+            delete_data,
+            detect_anomalies,
+            export_consumption,
+            fetch_data,
+            get_alert_rules,
+            get_anomalies,
+            get_anomaly_settings,
+            get_api_server_settings,
+            get_api_token,
+            get_app_status,
+            get_app_version,
+            get_backup_settings,
+            get_consumption_heatmap,
+            get_daily_electricity_consumption,
+            get_daily_gas_consumption,
+            get_data_quality_summary,
+            get_electricity_baseload,
+            get_electricity_consumption_profile,
+            get_electricity_consumption_projection,
+            get_electricity_cost_history,
+            get_electricity_load_duration_curve,
+            get_electricity_tariff_history,
+            get_energy_profiles,
+            get_gas_consumption_profile,
+            get_gas_consumption_projection,
+            get_gas_cost_history,
+            get_gas_degree_day_regression,
+            get_gas_tariff_history,
+            get_glowmarkt_credentials,
+            get_mqtt_brokers,
+            get_mqtt_replay_settings,
+            get_mqtt_settings,
+            get_monthly_electricity_consumption,
+            get_monthly_gas_consumption,
+            get_raw_electricity_consumption,
+            get_raw_gas_consumption,
+            get_startup_error,
+            get_timezone_settings,
+            get_triggered_alerts,
+            get_weather_settings,
+            get_weekly_electricity_consumption,
+            get_weekly_gas_consumption,
+            get_yearly_electricity_consumption,
+            get_yearly_gas_consumption,
+            import_weather_csv,
+            import_weather_from_endpoint,
+            remove_mqtt_broker,
+            regenerate_api_token,
+            reset,
+            reset_mqtt_settings,
+            restore_database,
+            store_alert_rule,
+            store_anomaly_settings,
+            store_api_server_settings,
+            store_backup_settings,
+            store_glowmarkt_credentials,
+            store_mqtt_replay_settings,
+            store_mqtt_settings,
+            store_timezone_settings,
+            store_weather_settings,
+            test_glowmarkt_connection,
+            update_energy_profile_settings,
+            update_mqtt_broker
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,466 @@
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{BigInt, Timestamp},
+};
+
+use crate::{
+    data::{quality::QualityFlag, Fuel, RepositoryError},
+    dates::london_today,
+    db::SqliteConnectionPool,
+    export::{load_rows, load_tariff_timeline, ExportGranularity},
+    AppError,
+};
+
+/// The latest readings from a meter's MQTT feed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LiveReading {
+    pub power_watts: Option<f64>,
+    pub import_kwh: Option<f64>,
+    pub export_kwh: Option<f64>,
+    pub received_at: i64,
+}
+
+/// The outcome of syncing one fuel with Glowmarkt.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SyncStats {
+    pub last_duration: Duration,
+    pub successes: u64,
+    pub failures: u64,
+    pub last_success_at: Option<i64>,
+}
+
+/// Metrics recorded as they happen, as opposed to those read from the database when scraped.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Keyed by fuel and meter, i.e. MPAN or MPRN
+    live: BTreeMap<(Fuel, String), LiveReading>,
+    /// The meter each fuel's stored consumption is from, as last reported over MQTT
+    stored_meters: BTreeMap<Fuel, String>,
+    /// Whether each MQTT broker is connected, keyed by broker name
+    mqtt_connected: BTreeMap<String, bool>,
+    syncs: BTreeMap<Fuel, SyncStats>,
+}
+
+/// The stored consumption of a fuel, read when the metrics are scraped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredFuelMetrics {
+    pub fuel: Fuel,
+    pub latest_reading: Option<(NaiveDateTime, i64)>,
+    pub today_wh: i64,
+    pub today_cost_pence: Option<f64>,
+}
+
+#[derive(QueryableByName)]
+struct LatestReading {
+    #[diesel(sql_type = Timestamp)]
+    timestamp: NaiveDateTime,
+    #[diesel(sql_type = BigInt)]
+    energy_consumption_wh: i64,
+}
+
+impl Metrics {
+    pub fn record_live_reading(&mut self, fuel: Fuel, meter: &str, reading: LiveReading) {
+        self.live.insert((fuel, meter.to_string()), reading);
+    }
+
+    pub fn record_stored_meter(&mut self, fuel: Fuel, meter: &str) {
+        self.stored_meters.insert(fuel, meter.to_string());
+    }
+
+    /// Labels a fuel's stored consumption with its meter, once MQTT has reported it, as
+    /// neither the energy profiles nor the Glowmarkt resources have the MPAN or MPRN.
+    fn stored_labels(&self, fuel: Fuel) -> String {
+        match self.stored_meters.get(&fuel) {
+            Some(meter) => meter_labels(fuel, meter),
+            None => fuel_labels(fuel),
+        }
+    }
+
+    pub fn set_mqtt_connected(&mut self, broker: &str, connected: bool) {
+        self.mqtt_connected.insert(broker.to_string(), connected);
+    }
+
+    /// Forgets a broker that has been removed or replaced by a replay.
+    pub fn remove_mqtt_broker(&mut self, broker: &str) {
+        self.mqtt_connected.remove(broker);
+    }
+
+    pub fn record_sync(&mut self, fuel: Fuel, duration: Duration, succeeded: bool) {
+        let stats = self.syncs.entry(fuel).or_default();
+
+        stats.last_duration = duration;
+
+        if succeeded {
+            stats.successes += 1;
+            stats.last_success_at = Some(Utc::now().timestamp());
+        } else {
+            stats.failures += 1;
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self, stored: &[StoredFuelMetrics]) -> String {
+        let mut output = String::new();
+
+        family(
+            &mut output,
+            "smart_energy_power_watts",
+            "gauge",
+            "Live power draw from the meter's MQTT feed",
+            self.live.iter().filter_map(|((fuel, meter), reading)| {
+                Some((meter_labels(*fuel, meter), reading.power_watts?))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_import_kwh_total",
+            "counter",
+            "Cumulative energy imported, from the meter's MQTT feed",
+            self.live.iter().filter_map(|((fuel, meter), reading)| {
+                Some((meter_labels(*fuel, meter), reading.import_kwh?))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_export_kwh_total",
+            "counter",
+            "Cumulative energy exported, from the meter's MQTT feed",
+            self.live.iter().filter_map(|((fuel, meter), reading)| {
+                Some((meter_labels(*fuel, meter), reading.export_kwh?))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_live_reading_timestamp_seconds",
+            "gauge",
+            "When the latest MQTT reading was received",
+            self.live.iter().map(|((fuel, meter), reading)| {
+                (meter_labels(*fuel, meter), reading.received_at as f64)
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_latest_reading_wh",
+            "gauge",
+            "The latest stored half-hourly consumption",
+            stored.iter().filter_map(|fuel_metrics| {
+                let (_, wh) = fuel_metrics.latest_reading?;
+                Some((self.stored_labels(fuel_metrics.fuel), wh as f64))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_latest_reading_timestamp_seconds",
+            "gauge",
+            "The start of the latest stored half hour",
+            stored.iter().filter_map(|fuel_metrics| {
+                let (timestamp, _) = fuel_metrics.latest_reading?;
+                Some((
+                    self.stored_labels(fuel_metrics.fuel),
+                    timestamp.and_utc().timestamp() as f64,
+                ))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_today_kwh",
+            "gauge",
+            "Consumption so far today in the reporting timezone",
+            stored.iter().map(|fuel_metrics| {
+                (
+                    self.stored_labels(fuel_metrics.fuel),
+                    fuel_metrics.today_wh as f64 / 1000.0,
+                )
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_today_cost_pence",
+            "gauge",
+            "The cost of today's consumption, including the standing charge",
+            stored.iter().filter_map(|fuel_metrics| {
+                Some((
+                    self.stored_labels(fuel_metrics.fuel),
+                    fuel_metrics.today_cost_pence?,
+                ))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_sync_duration_seconds",
+            "gauge",
+            "How long the last sync with Glowmarkt took",
+            self.syncs
+                .iter()
+                .map(|(fuel, stats)| (fuel_labels(*fuel), stats.last_duration.as_secs_f64())),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_syncs_total",
+            "counter",
+            "Successful syncs with Glowmarkt since the app started",
+            self.syncs
+                .iter()
+                .map(|(fuel, stats)| (fuel_labels(*fuel), stats.successes as f64)),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_sync_failures_total",
+            "counter",
+            "Failed syncs with Glowmarkt since the app started",
+            self.syncs
+                .iter()
+                .map(|(fuel, stats)| (fuel_labels(*fuel), stats.failures as f64)),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_last_successful_sync_timestamp_seconds",
+            "gauge",
+            "When the last successful sync with Glowmarkt finished",
+            self.syncs.iter().filter_map(|(fuel, stats)| {
+                Some((fuel_labels(*fuel), stats.last_success_at? as f64))
+            }),
+        );
+
+        family(
+            &mut output,
+            "smart_energy_mqtt_connected",
+            "gauge",
+            "Whether the app is connected to the MQTT broker",
+            self.mqtt_connected.iter().map(|(broker, connected)| {
+                (
+                    format!("broker=\"{}\"", escape_label(broker)),
+                    if *connected { 1.0 } else { 0.0 },
+                )
+            }),
+        );
+
+        output
+    }
+}
+
+fn fuel_labels(fuel: Fuel) -> String {
+    format!("fuel=\"{}\"", fuel)
+}
+
+fn meter_labels(fuel: Fuel, meter: &str) -> String {
+    format!("fuel=\"{}\",meter=\"{}\"", fuel, escape_label(meter))
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Writes a metric family, leaving it out altogether if it has no samples yet.
+fn family(
+    output: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl Iterator<Item = (String, f64)>,
+) {
+    let mut samples = samples.peekable();
+
+    if samples.peek().is_none() {
+        return;
+    }
+
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+
+    for (labels, value) in samples {
+        let _ = writeln!(output, "{}{{{}}} {}", name, labels, value);
+    }
+}
+
+/// Reads the latest stored reading and today's consumption and cost of each fuel.
+pub fn load_stored_metrics(
+    connection_pool: SqliteConnectionPool,
+) -> Result<Vec<StoredFuelMetrics>, AppError> {
+    let today = london_today();
+    let tomorrow = today.succ_opt().unwrap_or(today);
+
+    Fuel::ALL
+        .into_iter()
+        .map(|fuel| {
+            let repository_error = |e: RepositoryError| AppError::CustomError(e.to_string());
+
+            let mut conn = connection_pool
+                .get()
+                .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+            let latest_reading = sql_query(format!(
+                "SELECT timestamp, energy_consumption_wh FROM {} WHERE quality_flag = {} \
+                ORDER BY timestamp DESC LIMIT 1",
+                fuel.consumption_table(),
+                QualityFlag::Valid as i32
+            ))
+            .get_result::<LatestReading>(&mut conn)
+            .optional()
+            .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+            drop(conn);
+
+            let tariffs =
+                load_tariff_timeline(connection_pool.clone(), fuel).map_err(repository_error)?;
+
+            let today_rows = load_rows(
+                connection_pool.clone(),
+                fuel,
+                today,
+                tomorrow,
+                ExportGranularity::Daily,
+                &tariffs,
+            )
+            .map_err(repository_error)?;
+
+            Ok(StoredFuelMetrics {
+                fuel,
+                latest_reading: latest_reading
+                    .map(|reading| (reading.timestamp, reading.energy_consumption_wh)),
+                today_wh: today_rows.iter().map(|row| row.energy_consumption_wh).sum(),
+                today_cost_pence: today_rows
+                    .iter()
+                    .filter_map(|row| row.cost_pence)
+                    .reduce(|a, b| a + b),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::db::TestDatabase;
+
+    #[test]
+    fn test_render_is_empty_before_anything_is_recorded() {
+        assert_eq!(Metrics::default().render(&[]), "");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut metrics = Metrics::default();
+
+        metrics.record_live_reading(
+            Fuel::Electricity,
+            "1234567890123",
+            LiveReading {
+                power_watts: Some(350.0),
+                import_kwh: Some(12345.678),
+                export_kwh: Some(0.5),
+                received_at: 1_760_000_000,
+            },
+        );
+        metrics.record_stored_meter(Fuel::Electricity, "1234567890123");
+        metrics.set_mqtt_connected("default", true);
+        metrics.set_mqtt_connected("solar", false);
+        metrics.record_sync(Fuel::Gas, Duration::from_millis(1500), false);
+
+        let stored = [StoredFuelMetrics {
+            fuel: Fuel::Electricity,
+            latest_reading: Some((
+                NaiveDate::from_ymd_opt(2025, 10, 9)
+                    .unwrap()
+                    .and_hms_opt(23, 30, 0)
+                    .unwrap(),
+                250,
+            )),
+            today_wh: 4500,
+            today_cost_pence: Some(162.5),
+        }];
+
+        let output = metrics.render(&stored);
+
+        for line in [
+            "# TYPE smart_energy_power_watts gauge",
+            "smart_energy_power_watts{fuel=\"electricity\",meter=\"1234567890123\"} 350",
+            "smart_energy_import_kwh_total{fuel=\"electricity\",meter=\"1234567890123\"} 12345.678",
+            "smart_energy_export_kwh_total{fuel=\"electricity\",meter=\"1234567890123\"} 0.5",
+            "smart_energy_latest_reading_wh{fuel=\"electricity\",meter=\"1234567890123\"} 250",
+            "smart_energy_latest_reading_timestamp_seconds{fuel=\"electricity\",meter=\"1234567890123\"} 1760052600",
+            "smart_energy_today_kwh{fuel=\"electricity\",meter=\"1234567890123\"} 4.5",
+            "smart_energy_today_cost_pence{fuel=\"electricity\",meter=\"1234567890123\"} 162.5",
+            "smart_energy_sync_duration_seconds{fuel=\"gas\"} 1.5",
+            "smart_energy_syncs_total{fuel=\"gas\"} 0",
+            "smart_energy_sync_failures_total{fuel=\"gas\"} 1",
+            "smart_energy_mqtt_connected{broker=\"default\"} 1",
+            "smart_energy_mqtt_connected{broker=\"solar\"} 0",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing {}", line);
+        }
+
+        // Nothing has synced successfully yet
+        assert!(!output.contains("smart_energy_last_successful_sync_timestamp_seconds"));
+    }
+
+    #[test]
+    fn test_stored_metrics_have_no_meter_label_until_mqtt_reports_it() {
+        let stored = [StoredFuelMetrics {
+            fuel: Fuel::Gas,
+            latest_reading: None,
+            today_wh: 1500,
+            today_cost_pence: None,
+        }];
+
+        assert!(Metrics::default()
+            .render(&stored)
+            .contains("smart_energy_today_kwh{fuel=\"gas\"} 1.5"));
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        let mut metrics = Metrics::default();
+
+        metrics.set_mqtt_connected("loft \"heat\\pump\"", true);
+
+        assert!(metrics
+            .render(&[])
+            .contains(r#"smart_energy_mqtt_connected{broker="loft \"heat\\pump\""} 1"#));
+    }
+
+    #[test]
+    fn test_load_stored_metrics() {
+        let database = TestDatabase::new();
+
+        let metrics = load_stored_metrics(database.pool.clone()).unwrap();
+
+        assert_eq!(
+            metrics,
+            vec![
+                StoredFuelMetrics {
+                    fuel: Fuel::Electricity,
+                    latest_reading: None,
+                    today_wh: 0,
+                    today_cost_pence: None,
+                },
+                StoredFuelMetrics {
+                    fuel: Fuel::Gas,
+                    latest_reading: None,
+                    today_wh: 0,
+                    today_cost_pence: None,
+                },
+            ]
+        );
+    }
+}
@@ -1,15 +1,34 @@
-use std::{pin::Pin, time::Duration};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::Duration,
+};
 
-use log::{error, info};
+use chrono::{NaiveDateTime, Utc};
+use log::{debug, error, info, warn};
 use paho_mqtt::{self as mqtt, AsyncClient, AsyncReceiver, DisconnectOptionsBuilder, Message};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
-use tokio::sync::mpsc;
+use tauri_plugin_notification::NotificationExt;
+use tokio::{sync::mpsc, task::JoinHandle};
 use tokio_stream::StreamExt;
-use uuid::Uuid;
 
 use crate::{
-    utils::{emit_event, MqttSettings},
+    alerts::{power_to_watts, PowerAlertEngine},
+    data::{
+        alert::{AlertRepository, AlertRule, SqliteAlertRepository},
+        consumption::{
+            ConsumptionRepository, ElectricityConsumptionValue, GasConsumptionValue,
+            SqliteElectricityConsumptionRepository, SqliteGasConsumptionRepository,
+        },
+        Fuel,
+    },
+    dates::reporting_timezone,
+    metrics::LiveReading,
+    mqtt_recording::{load_recording, replay_delay, MqttRecorder},
+    provisional::{parse_reading_timestamp, ProvisionalConsumptionTracker},
+    utils::{emit_event, MqttPayloadParser, MqttReplaySettings, MqttSettings, MqttSource},
     AppState, MqttMessage,
 };
 
@@ -118,167 +137,911 @@ pub struct GasUpdate {
 }
 
 async fn create_mqtt_client(
-    client_id: String,
     settings: &MqttSettings,
-) -> Result<AsyncClient, paho_mqtt::Error> {
-    let qos = 1;
+) -> Result<(AsyncClient, Pin<Box<AsyncReceiver<Option<Message>>>>), paho_mqtt::Error> {
+    let qos = settings.qos;
 
     let client_options = mqtt::CreateOptionsBuilder::new()
         .server_uri(settings.hostname.clone())
-        .client_id(client_id)
+        .client_id(settings.client_id.clone())
         .finalize();
 
-    let client = mqtt::AsyncClient::new(client_options)?;
+    let mut client = mqtt::AsyncClient::new(client_options)?;
+
+    // Create the stream before connecting, so that messages the broker has queued for a
+    // persistent session are not missed when they are delivered straight after connecting
+    let stream = Box::pin(client.get_stream(None));
 
-    let connection_options = mqtt::ConnectOptionsBuilder::new()
-        .clean_session(true)
+    let mut connection_options_builder = mqtt::ConnectOptionsBuilder::new();
+
+    connection_options_builder
+        .clean_session(!settings.persistent_session)
+        .keep_alive_interval(Duration::from_secs(settings.keep_alive_seconds))
         .user_name(settings.username.clone())
         .password(settings.password.clone())
         .connect_timeout(Duration::from_secs(5))
-        .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30))
-        .finalize();
+        .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30));
 
-    client.connect(connection_options).await?;
+    if !settings.status_topic.is_empty() {
+        connection_options_builder.will_message(Message::new_retained(
+            settings.status_topic.clone(),
+            STATUS_OFFLINE,
+            qos,
+        ));
+    }
+
+    let response = client
+        .connect(connection_options_builder.finalize())
+        .await?;
+
+    if let Some(connect_response) = response.connect_response() {
+        info!(
+            "Connected to MQTT broker {} (session present: {})",
+            connect_response.server_uri, connect_response.session_present
+        );
+    }
 
-    if settings.topic.len() > 0 {
+    if !settings.topic.is_empty() {
         client.subscribe(settings.topic.clone(), qos).await?;
     }
 
-    if settings.gas_topic.len() > 0 {
+    if !settings.gas_topic.is_empty() {
         client.subscribe(settings.gas_topic.clone(), qos).await?;
     }
 
-    Ok(client)
+    if !settings.status_topic.is_empty() {
+        client
+            .publish(Message::new_retained(
+                settings.status_topic.clone(),
+                STATUS_ONLINE,
+                qos,
+            ))
+            .await?;
+    }
+
+    // Without a persistent session the broker forgets the subscriptions when the connection
+    // drops, so they're renewed, along with the online status, whenever it reconnects
+    client.set_connected_callback({
+        let settings = settings.clone();
+
+        move |client| {
+            info!("Reconnected to MQTT broker '{}'", settings.name);
+
+            for topic in [&settings.topic, &settings.gas_topic] {
+                if !topic.is_empty() {
+                    client.subscribe(topic.clone(), qos);
+                }
+            }
+
+            if !settings.status_topic.is_empty() {
+                client.publish(Message::new_retained(
+                    settings.status_topic.clone(),
+                    STATUS_ONLINE,
+                    qos,
+                ));
+            }
+        }
+    });
+
+    Ok((client, stream))
 }
 
-enum MqttClientState {
-    Disconnected,
-    Connected(AsyncClient, Pin<Box<AsyncReceiver<Option<Message>>>>),
+const STATUS_ONLINE: &str = "online";
+
+const STATUS_OFFLINE: &str = "offline";
+
+/// Parses meter payloads and passes them on to the frontend, the provisional consumption
+/// trackers and the alert engine. Payloads from a replayed recording are handled in the same
+/// way, except that nothing is written to the database.
+struct MeterMessageHandler {
+    broker_name: String,
+    parser: MqttPayloadParser,
+    /// Whether the broker relays the meters the app stores. Only its readings are stored as
+    /// provisional consumption and evaluated for alerts, as the consumption tables hold one
+    /// meter per fuel.
+    glow_source: bool,
+    electricity_tracker: ProvisionalConsumptionTracker,
+    gas_tracker: ProvisionalConsumptionTracker,
+    alert_engine: PowerAlertEngine,
 }
 
-pub async fn start_mqtt_listener(
-    app_handle: &AppHandle,
-    mut mqtt_message_receiver: mpsc::Receiver<MqttMessage>,
-) {
-    info!("Starting MQTT listener thread.");
+/// A message from a broker using the generic JSON parser, passed to the frontend as is.
+#[derive(Serialize, Clone, Debug)]
+struct MqttJsonMessage {
+    broker: String,
+    topic: String,
+    payload: serde_json::Value,
+}
 
-    let uuid_string = Uuid::new_v4().to_string();
-    let client_id = format!("smart-energy-explorer-{}", uuid_string);
-    info!("Generated Client ID: {}", client_id);
+impl MeterMessageHandler {
+    async fn new(
+        app_handle: &AppHandle,
+        broker_name: &str,
+        parser: MqttPayloadParser,
+        glow_source: bool,
+    ) -> Self {
+        let mut handler = Self {
+            broker_name: broker_name.to_string(),
+            parser,
+            glow_source,
+            electricity_tracker: ProvisionalConsumptionTracker::new(),
+            gas_tracker: ProvisionalConsumptionTracker::new(),
+            alert_engine: PowerAlertEngine::new(),
+        };
 
-    let mut mqtt_client_state = MqttClientState::Disconnected;
+        handler.reload_alert_rules(app_handle).await;
 
-    loop {
-        let (client, stream) = match &mut mqtt_client_state {
-            MqttClientState::Connected(c, s) => (c, s),
-            MqttClientState::Disconnected => {
-                let settings = {
-                    let app_state = app_handle.state::<AppState>();
-                    let settings = app_state.mqtt_settings.lock().unwrap();
-                    settings.clone()
-                };
-
-                if let Some(settings) = settings {
-                    if settings.is_complete() {
-                        info!("MQTT settings are complete but client is not yet created. Creating MQTT client...");
-                        match create_mqtt_client(client_id.clone(), &settings).await {
-                            Ok(mut client) => {
-                                let stream = Box::pin(client.get_stream(None));
-                                info!("MQTT client and stream created");
-                                mqtt_client_state = MqttClientState::Connected(client, stream);
-                                continue;
-                            }
-                            Err(e) => {
-                                error!("Failed to create client: {}", e);
+        handler
+    }
+
+    async fn reload_alert_rules(&mut self, app_handle: &AppHandle) {
+        if self.glow_source {
+            self.alert_engine
+                .set_rules(load_alert_rules(app_handle).await);
+        }
+    }
+
+    async fn handle_message(&mut self, app_handle: &AppHandle, message: MqttMessage) {
+        match message {
+            MqttMessage::SettingsUpdated => (),
+            MqttMessage::AlertRulesUpdated => self.reload_alert_rules(app_handle).await,
+        }
+    }
+
+    async fn handle_payload(
+        &mut self,
+        app_handle: &AppHandle,
+        topic: &str,
+        payload: &str,
+        persist: bool,
+    ) {
+        match self.parser {
+            MqttPayloadParser::Glow => {
+                self.handle_meter_payload(app_handle, payload, persist)
+                    .await
+            }
+            MqttPayloadParser::Json => self.handle_json_payload(app_handle, topic, payload),
+        }
+    }
+
+    fn handle_json_payload(&self, app_handle: &AppHandle, topic: &str, payload: &str) {
+        match serde_json::from_str::<serde_json::Value>(payload) {
+            Ok(payload) => {
+                if let Err(err) = emit_event(
+                    app_handle,
+                    "mqttUpdate",
+                    MqttJsonMessage {
+                        broker: self.broker_name.clone(),
+                        topic: topic.to_string(),
+                        payload,
+                    },
+                ) {
+                    error!("Unexpected error emitting mqttUpdate event: {}", err);
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Failed to deserialize payload from broker '{}': {}",
+                    self.broker_name, e
+                );
+            }
+        }
+    }
+
+    async fn handle_meter_payload(&mut self, app_handle: &AppHandle, payload: &str, persist: bool) {
+        match serde_json::from_str::<MeterPayload>(payload) {
+            Ok(payload) => {
+                info!("Deserialized data: {:?}", payload);
+
+                match payload {
+                    MeterPayload::ElectricityMeter(data) => {
+                        let Some(timestamp) = parse_reading_timestamp(&data.timestamp) else {
+                            warn!(
+                                "Skipping electricity meter message with an invalid timestamp: {}",
+                                data.timestamp
+                            );
+                            return;
+                        };
+
+                        if persist {
+                            if self.glow_source {
+                                store_provisional_electricity(
+                                    app_handle,
+                                    &mut self.electricity_tracker,
+                                    timestamp,
+                                    &data,
+                                )
+                                .await;
                             }
+
+                            record_live_metrics(
+                                app_handle,
+                                Fuel::Electricity,
+                                &data.energy.import.mpan,
+                                LiveReading {
+                                    power_watts: power_to_watts(
+                                        data.power.value,
+                                        &data.power.units,
+                                    ),
+                                    import_kwh: kwh(
+                                        data.energy.import.cumulative,
+                                        &data.energy.import.units,
+                                    ),
+                                    export_kwh: kwh(
+                                        data.energy.export.cumulative,
+                                        &data.energy.export.units,
+                                    ),
+                                    received_at: Utc::now().timestamp(),
+                                },
+                                self.glow_source,
+                            );
+                        }
+
+                        if self.glow_source {
+                            evaluate_power_alerts(
+                                app_handle,
+                                &mut self.alert_engine,
+                                timestamp,
+                                &data,
+                                persist,
+                            )
+                            .await;
+                        }
+
+                        if let Err(err) = emit_event(
+                            app_handle,
+                            "electricityUpdate",
+                            ElectricityMeterMessage {
+                                electricitymeter: data,
+                            },
+                        ) {
+                            error!("Unexpected error emitting electricityUpdate event: {}", err);
+                        }
+                    }
+                    MeterPayload::GasMeter(data) => {
+                        let Some(timestamp) = parse_reading_timestamp(&data.timestamp) else {
+                            warn!(
+                                "Skipping gas meter message with an invalid timestamp: {}",
+                                data.timestamp
+                            );
+                            return;
                         };
-                    } else {
-                        info!("MQTT settings are not complete");
+
+                        if persist {
+                            if self.glow_source {
+                                store_provisional_gas(
+                                    app_handle,
+                                    &mut self.gas_tracker,
+                                    timestamp,
+                                    &data,
+                                )
+                                .await;
+                            }
+
+                            record_live_metrics(
+                                app_handle,
+                                Fuel::Gas,
+                                &data.energy.import.mprn,
+                                LiveReading {
+                                    import_kwh: kwh(
+                                        data.energy.import.cumulative,
+                                        &data.energy.import.units,
+                                    ),
+                                    received_at: Utc::now().timestamp(),
+                                    ..LiveReading::default()
+                                },
+                                self.glow_source,
+                            );
+                        }
+
+                        if let Err(err) =
+                            emit_event(app_handle, "gasUpdate", GasMeterMessage { gasmeter: data })
+                        {
+                            error!("Unexpected error emitting gasUpdate event: {}", err);
+                        }
                     }
-                } else {
-                    info!("MQTT settings are not set")
                 }
+            }
+            Err(e) => {
+                error!("Failed to deserialize payload: {}", e);
+            }
+        }
+    }
+}
 
-                // Sleep to avoid tight loop, but settings updates should be handled immediately
-                tokio::select! {
-                  Some(app_message) = mqtt_message_receiver.recv() => {
-                      match app_message {
-                          MqttMessage::SettingsUpdated => {
-                              info!("MQTT settings updated");
-                          }
-                      }
-                  },
-                  _ = tokio::time::sleep(Duration::from_secs(10)) => {}
-                }
+/// Returns a cumulative reading if it's in kWh.
+fn kwh(value: f64, units: &str) -> Option<f64> {
+    units.eq_ignore_ascii_case("kWh").then_some(value)
+}
 
-                continue;
+/// Records a meter's latest readings for the metrics endpoint. Replayed readings aren't
+/// recorded, as they aren't live. The Glow source's meters are the ones the stored consumption
+/// is from, so they label its metrics too.
+fn record_live_metrics(
+    app_handle: &AppHandle,
+    fuel: Fuel,
+    meter: &str,
+    reading: LiveReading,
+    glow_source: bool,
+) {
+    if let Ok(mut metrics) = app_handle.state::<AppState>().metrics.lock() {
+        metrics.record_live_reading(fuel, meter, reading);
+
+        if glow_source {
+            metrics.record_stored_meter(fuel, meter);
+        }
+    }
+}
+
+fn set_mqtt_connected(app_handle: &AppHandle, broker: &str, connected: Option<bool>) {
+    if let Ok(mut metrics) = app_handle.state::<AppState>().metrics.lock() {
+        match connected {
+            Some(connected) => metrics.set_mqtt_connected(broker, connected),
+            None => metrics.remove_mqtt_broker(broker),
+        }
+    }
+}
+
+fn get_replay_settings(app_handle: &AppHandle) -> Option<MqttReplaySettings> {
+    let app_state = app_handle.state::<AppState>();
+    let app_settings = app_state.app_settings.lock().ok()?;
+
+    MqttReplaySettings::from_app_settings(&app_settings)
+        .map_err(|e| error!("Failed to read MQTT replay settings: {}", e))
+        .ok()
+}
+
+fn create_recorder(record_file: &str) -> Option<MqttRecorder> {
+    if record_file.is_empty() {
+        return None;
+    }
+
+    match MqttRecorder::create(Path::new(record_file)) {
+        Ok(recorder) => {
+            info!("Recording MQTT messages to {}", record_file);
+            Some(recorder)
+        }
+        Err(e) => {
+            error!("Failed to start recording MQTT messages: {}", e);
+            None
+        }
+    }
+}
+
+/// The key under which the replay listener is supervised. Broker listeners use the broker name.
+const REPLAY_LISTENER_KEY: &str = "replay";
+
+/// How often the supervisor checks that the listeners match the settings and are still running.
+const SUPERVISOR_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, PartialEq)]
+enum ListenerConfig {
+    Broker {
+        settings: MqttSettings,
+        record_file: String,
+    },
+    Replay {
+        replay_file: String,
+    },
+}
+
+struct SupervisedListener {
+    config: ListenerConfig,
+    sender: mpsc::Sender<MqttMessage>,
+    handle: JoinHandle<()>,
+}
+
+/// Works out which listeners should be running. Replaying a recording replaces all of the
+/// broker connections, so that replayed and live readings are never mixed.
+fn get_listener_configs(app_handle: &AppHandle) -> HashMap<String, ListenerConfig> {
+    let replay_settings = get_replay_settings(app_handle);
+
+    if let Some(MqttReplaySettings {
+        source: MqttSource::Replay,
+        replay_file,
+        ..
+    }) = &replay_settings
+    {
+        return HashMap::from([(
+            REPLAY_LISTENER_KEY.to_string(),
+            ListenerConfig::Replay {
+                replay_file: replay_file.clone(),
+            },
+        )]);
+    }
+
+    let record_file = replay_settings.map(|s| s.record_file).unwrap_or_default();
+
+    let app_state = app_handle.state::<AppState>();
+    let all_settings = app_state.mqtt_settings.lock().unwrap().clone();
+
+    all_settings
+        .into_iter()
+        .filter(|settings| {
+            if !settings.is_complete() {
+                info!(
+                    "MQTT settings for broker '{}' are not complete",
+                    settings.name
+                );
             }
-        };
+            settings.is_complete()
+        })
+        .map(|settings| {
+            (
+                settings.name.clone(),
+                ListenerConfig::Broker {
+                    settings,
+                    record_file: record_file.clone(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Stops listeners whose settings have changed or that have stopped unexpectedly, and starts
+/// any that are missing.
+async fn reconcile_listeners(
+    app_handle: &AppHandle,
+    listeners: &mut HashMap<String, SupervisedListener>,
+) {
+    let mut configs = get_listener_configs(app_handle);
 
-        if !client.is_connected() {
-            info!("The MQTT client is no longer connected. Resetting client and stream.");
-            mqtt_client_state = MqttClientState::Disconnected;
+    let stale_keys: Vec<String> = listeners
+        .iter()
+        .filter(|(key, listener)| {
+            configs.get(*key) != Some(&listener.config) || listener.handle.is_finished()
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    for key in stale_keys {
+        if let Some(listener) = listeners.remove(&key) {
+            if listener.handle.is_finished() {
+                error!("MQTT listener '{}' stopped unexpectedly", key);
+            } else {
+                info!("Stopping MQTT listener '{}'", key);
+            }
+
+            // Closing the channel asks the listener to disconnect. Wait for it, so that a
+            // restarted listener doesn't connect with the same client ID alongside it.
+            drop(listener.sender);
+
+            if tokio::time::timeout(SUPERVISOR_INTERVAL, listener.handle)
+                .await
+                .is_err()
+            {
+                error!("MQTT listener '{}' did not stop in time", key);
+            }
+        }
+    }
+
+    for (key, config) in configs.drain() {
+        if listeners.contains_key(&key) {
             continue;
         }
 
+        info!("Starting MQTT listener '{}'", key);
+
+        let (sender, receiver) = mpsc::channel(1);
+        let app_handle = app_handle.clone();
+
+        let handle = match config.clone() {
+            ListenerConfig::Broker {
+                settings,
+                record_file,
+            } => tokio::spawn(run_broker_listener(
+                app_handle,
+                settings,
+                record_file,
+                receiver,
+            )),
+            ListenerConfig::Replay { replay_file } => {
+                tokio::spawn(run_replay_listener(app_handle, replay_file, receiver))
+            }
+        };
+
+        listeners.insert(
+            key,
+            SupervisedListener {
+                config,
+                sender,
+                handle,
+            },
+        );
+    }
+}
+
+/// Runs one listener per configured broker, or a single replay listener, restarting them when
+/// the settings change or a listener stops.
+pub async fn start_mqtt_listener(
+    app_handle: &AppHandle,
+    mut mqtt_message_receiver: mpsc::Receiver<MqttMessage>,
+) {
+    info!("Starting MQTT listener supervisor.");
+
+    let mut listeners: HashMap<String, SupervisedListener> = HashMap::new();
+
+    loop {
+        reconcile_listeners(app_handle, &mut listeners).await;
+
         tokio::select! {
             Some(app_message) = mqtt_message_receiver.recv() => {
                 match app_message {
                     MqttMessage::SettingsUpdated => {
                         info!("MQTT settings updated");
-                        if let MqttClientState::Connected(client, _) = &mqtt_client_state {
-                          info!("Disconnecting existing MQTT client due to settings update...");
-                          if client.is_connected() {
-                            disconnect_client(client).await;
-                          }
+                    }
+                    MqttMessage::AlertRulesUpdated => {
+                        for listener in listeners.values() {
+                            // A listener that has stopped is restarted on the next pass
+                            let _ = listener.sender.send(MqttMessage::AlertRulesUpdated).await;
                         }
-                        mqtt_client_state = MqttClientState::Disconnected;
                     }
                 }
             },
-            message = stream.next() => {
-                match message {
-                    Some(Some(msg)) => {
-                        match serde_json::from_str::<MeterPayload>(&msg.payload_str()) {
-                            Ok(payload) => {
-                                info!("Deserialized data: {:?}", payload);
-                                // Now you can work with the structured 'data' object
-
-                                match payload {
-                                    MeterPayload::ElectricityMeter(data) => {
-                                      if let Err(err) = emit_event(app_handle, "electricityUpdate", ElectricityMeterMessage { electricitymeter: data }) {
-                                          error!("Unexpected error emitting electricityUpdate event: {}", err);
-                                      }
-                                    },
-                                    MeterPayload::GasMeter(data) => {
-                                      if let Err(err) = emit_event(app_handle, "gasUpdate", GasMeterMessage { gasmeter: data }) {
-                                          error!("Unexpected error emitting gasUpdate event: {}", err);
-                                      }
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("Failed to deserialize payload: {}", e);
+            _ = tokio::time::sleep(SUPERVISOR_INTERVAL) => {}
+        }
+    }
+}
+
+async fn run_broker_listener(
+    app_handle: AppHandle,
+    settings: MqttSettings,
+    record_file: String,
+    mut mqtt_message_receiver: mpsc::Receiver<MqttMessage>,
+) {
+    let mut handler = MeterMessageHandler::new(
+        &app_handle,
+        &settings.name,
+        settings.parser,
+        settings.glow_source,
+    )
+    .await;
+
+    loop {
+        let (client, mut stream) = match create_mqtt_client(&settings).await {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!(
+                    "Failed to create client for MQTT broker '{}': {}",
+                    settings.name, e
+                );
+
+                set_mqtt_connected(&app_handle, &settings.name, Some(false));
+
+                // Wait before retrying, but stop straight away if asked to
+                tokio::select! {
+                    app_message = mqtt_message_receiver.recv() => match app_message {
+                        Some(app_message) => handler.handle_message(&app_handle, app_message).await,
+                        None => {
+                            set_mqtt_connected(&app_handle, &settings.name, None);
+                            return;
+                        }
+                    },
+                    _ = tokio::time::sleep(SUPERVISOR_INTERVAL) => {}
+                }
+
+                continue;
+            }
+        };
+
+        info!(
+            "MQTT client and stream created for broker '{}' with client ID {}",
+            settings.name, settings.client_id
+        );
+
+        set_mqtt_connected(&app_handle, &settings.name, Some(true));
+
+        let mut recorder = create_recorder(&record_file);
+
+        // Automatic reconnect handles dropped connections, so the client is only recreated if the
+        // stream ends
+        loop {
+            tokio::select! {
+                app_message = mqtt_message_receiver.recv() => {
+                    match app_message {
+                        Some(app_message) => handler.handle_message(&app_handle, app_message).await,
+                        None => {
+                            info!("Disconnecting from MQTT broker '{}'...", settings.name);
+                            if client.is_connected() {
+                                disconnect_client(&client, &settings).await;
                             }
+                            set_mqtt_connected(&app_handle, &settings.name, None);
+                            return;
                         }
                     }
-                    Some(None) => {
-                        info!("Unexpected message from MQTT stream receiever: None");
+                },
+                message = stream.next() => {
+                    match message {
+                        Some(Some(msg)) => {
+                            // Reconnecting is automatic, so a message means it's connected again
+                            set_mqtt_connected(&app_handle, &settings.name, Some(true));
+
+                            let payload = msg.payload_str();
+
+                            if let Some(r) = recorder.as_mut() {
+                                if let Err(e) = r.record(msg.topic(), &payload) {
+                                    error!("Failed to record MQTT message, stopping recording: {}", e);
+                                    recorder = None;
+                                }
+                            }
+
+                            handler.handle_payload(&app_handle, msg.topic(), &payload, true).await;
+                        }
+                        Some(None) => {
+                            // The client reconnects with the same client ID, and the broker then
+                            // delivers anything it queued while we were disconnected
+                            info!("Connection to MQTT broker '{}' lost, reconnecting", settings.name);
+                            set_mqtt_connected(&app_handle, &settings.name, Some(false));
+                        }
+                        None => {
+                            info!("The MQTT stream for broker '{}' ended. Resetting client and stream.", settings.name);
+                            if client.is_connected() {
+                                disconnect_client(&client, &settings).await;
+                            }
+                            set_mqtt_connected(&app_handle, &settings.name, Some(false));
+                            break;
+                        },
                     }
+                },
+            }
+        }
+    }
+}
+
+async fn run_replay_listener(
+    app_handle: AppHandle,
+    replay_file: String,
+    mut mqtt_message_receiver: mpsc::Receiver<MqttMessage>,
+) {
+    // A recording stands in for the Glow source, but is never persisted
+    let mut handler = MeterMessageHandler::new(
+        &app_handle,
+        REPLAY_LISTENER_KEY,
+        MqttPayloadParser::Glow,
+        true,
+    )
+    .await;
+
+    let path = PathBuf::from(&replay_file);
+
+    let messages = match tokio::task::spawn_blocking(move || load_recording(&path)).await {
+        Ok(Ok(messages)) if !messages.is_empty() => {
+            info!(
+                "Replaying {} MQTT messages from {}",
+                messages.len(),
+                replay_file
+            );
+            messages
+        }
+        result => {
+            match result {
+                Ok(Ok(_)) => info!("MQTT recording {} is empty", replay_file),
+                Ok(Err(e)) => error!("Failed to load MQTT recording: {}", e),
+                Err(e) => error!("Failed to load MQTT recording: {}", e),
+            }
+
+            // Stay idle until stopped, rather than being restarted over and over
+            while mqtt_message_receiver.recv().await.is_some() {}
+            return;
+        }
+    };
+
+    for position in 0..messages.len() {
+        let replay_speed = get_replay_settings(&app_handle)
+            .map(|s| s.replay_speed)
+            .unwrap_or(1.0);
+
+        let delay = if position == 0 {
+            Duration::ZERO
+        } else {
+            replay_delay(&messages[position - 1], &messages[position], replay_speed)
+        };
+
+        tokio::select! {
+            app_message = mqtt_message_receiver.recv() => {
+                match app_message {
+                    Some(app_message) => handler.handle_message(&app_handle, app_message).await,
                     None => {
-                        info!("Notified that the MQTT client connection has been lost. Resetting client and stream.");
-                        if client.is_connected() {
-                            disconnect_client(&client).await;
-                        }
-                        mqtt_client_state = MqttClientState::Disconnected;
-                    },
+                        info!("Stopping MQTT replay");
+                        return;
+                    }
                 }
             },
+            _ = tokio::time::sleep(delay) => {
+                let message = &messages[position];
+
+                handler.handle_payload(&app_handle, &message.topic, &message.payload, false).await;
+            }
+        }
+    }
+
+    // Replaying the recording again would take its timestamps back to the start, so it's played
+    // once and the listener stays idle until stopped, rather than being restarted
+    info!("Finished replaying MQTT recording {}", replay_file);
+
+    while let Some(app_message) = mqtt_message_receiver.recv().await {
+        handler.handle_message(&app_handle, app_message).await;
+    }
+}
+
+async fn store_provisional_electricity(
+    app_handle: &AppHandle,
+    tracker: &mut ProvisionalConsumptionTracker,
+    timestamp: NaiveDateTime,
+    data: &ElectricityMeter,
+) {
+    if !data.energy.import.units.eq_ignore_ascii_case("kWh") {
+        debug!(
+            "Ignoring electricity cumulative reading in unsupported units: {}",
+            data.energy.import.units
+        );
+        return;
+    }
+
+    let values: Vec<_> = tracker
+        .record(timestamp, data.energy.import.cumulative)
+        .into_iter()
+        .map(|(timestamp, value)| ElectricityConsumptionValue { timestamp, value })
+        .collect();
+
+    if values.is_empty() {
+        return;
+    }
+
+    let connection_pool = app_handle.state::<AppState>().db_pool.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        SqliteElectricityConsumptionRepository::new(connection_pool).insert_provisional(values)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => error!("Failed to store provisional electricity consumption: {}", e),
+        Err(e) => error!("Failed to store provisional electricity consumption: {}", e),
+    }
+}
+
+async fn store_provisional_gas(
+    app_handle: &AppHandle,
+    tracker: &mut ProvisionalConsumptionTracker,
+    timestamp: NaiveDateTime,
+    data: &GasMeter,
+) {
+    if !data.energy.import.units.eq_ignore_ascii_case("kWh") {
+        debug!(
+            "Ignoring gas cumulative reading in unsupported units: {}",
+            data.energy.import.units
+        );
+        return;
+    }
+
+    let values: Vec<_> = tracker
+        .record(timestamp, data.energy.import.cumulative)
+        .into_iter()
+        .map(|(timestamp, value)| GasConsumptionValue { timestamp, value })
+        .collect();
+
+    if values.is_empty() {
+        return;
+    }
+
+    let connection_pool = app_handle.state::<AppState>().db_pool.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        SqliteGasConsumptionRepository::new(connection_pool).insert_provisional(values)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => error!("Failed to store provisional gas consumption: {}", e),
+        Err(e) => error!("Failed to store provisional gas consumption: {}", e),
+    }
+}
+
+async fn load_alert_rules(app_handle: &AppHandle) -> Vec<AlertRule> {
+    let connection_pool = app_handle.state::<AppState>().db_pool.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        SqliteAlertRepository::new(connection_pool).get_rules()
+    })
+    .await;
+
+    match result {
+        Ok(Ok(rules)) => {
+            info!("Loaded {} alert rules", rules.len());
+            rules
+        }
+        Ok(Err(e)) => {
+            error!("Failed to load alert rules: {}", e);
+            vec![]
+        }
+        Err(e) => {
+            error!("Failed to load alert rules: {}", e);
+            vec![]
         }
     }
 }
 
-async fn disconnect_client(client: &AsyncClient) {
+async fn evaluate_power_alerts(
+    app_handle: &AppHandle,
+    alert_engine: &mut PowerAlertEngine,
+    timestamp: NaiveDateTime,
+    data: &ElectricityMeter,
+    persist: bool,
+) {
+    let Some(power_watts) = power_to_watts(data.power.value, &data.power.units) else {
+        debug!(
+            "Ignoring power reading in unsupported units: {}",
+            data.power.units
+        );
+        return;
+    };
+
+    let events = alert_engine.evaluate(timestamp, power_watts);
+
+    for event in events {
+        info!(
+            "Alert '{}' triggered at {} W",
+            event.rule_name, event.power_watts
+        );
+
+        if persist {
+            let connection_pool = app_handle.state::<AppState>().db_pool.clone();
+            let new_alert = (&event).into();
+
+            let result = tokio::task::spawn_blocking(move || {
+                SqliteAlertRepository::new(connection_pool).insert_triggered_alert(new_alert)
+            })
+            .await;
+
+            match result {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => error!("Failed to store triggered alert: {}", e),
+                Err(e) => error!("Failed to store triggered alert: {}", e),
+            }
+        }
+
+        if persist && event.notify {
+            let notification_result = app_handle
+                .notification()
+                .builder()
+                .title(format!("Smart Energy Explorer: {}", event.rule_name))
+                .body(format!(
+                    "Power has been above {:.0} W since {}, currently {:.0} W",
+                    event.threshold_watts,
+                    event
+                        .exceeded_since
+                        .and_utc()
+                        .with_timezone(&reporting_timezone())
+                        .format("%H:%M"),
+                    event.power_watts
+                ))
+                .show();
+
+            if let Err(e) = notification_result {
+                error!("Failed to show alert notification: {}", e);
+            }
+        }
+
+        if let Err(err) = emit_event(app_handle, "alert", event) {
+            error!("Unexpected error emitting alert event: {}", err);
+        }
+    }
+}
+
+async fn disconnect_client(client: &AsyncClient, settings: &MqttSettings) {
+    if !settings.status_topic.is_empty() {
+        // The last will is not published on a clean disconnect, so publish it ourselves
+        let offline_message =
+            Message::new_retained(settings.status_topic.clone(), STATUS_OFFLINE, settings.qos);
+
+        if let Err(e) = client.publish(offline_message).await {
+            error!("Failed to publish MQTT offline status: {:?}", e);
+        }
+    }
+
     let opts = DisconnectOptionsBuilder::new()
         .timeout(Duration::from_secs(5))
         .finalize();
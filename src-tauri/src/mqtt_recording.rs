@@ -0,0 +1,173 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::AppError;
+
+/// The longest pause between two replayed messages, so that gaps in a recording (e.g. while the
+/// broker was unavailable) don't stall a replay.
+const MAX_REPLAY_DELAY: Duration = Duration::from_secs(60);
+
+/// A raw MQTT message as written to, and read from, a JSON Lines recording.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedMessage {
+    pub received_at: DateTime<Utc>,
+    pub topic: String,
+    pub payload: String,
+}
+
+pub struct MqttRecorder {
+    writer: LineWriter<File>,
+}
+
+impl MqttRecorder {
+    /// Opens a recording, appending to it if it already exists.
+    pub fn create(path: &Path) -> Result<Self, AppError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                AppError::CustomError(format!(
+                    "Failed to open MQTT recording {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+
+        Ok(Self {
+            writer: LineWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, topic: &str, payload: &str) -> Result<(), AppError> {
+        let message = RecordedMessage {
+            received_at: Utc::now(),
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+        };
+
+        let line = serde_json::to_string(&message).map_err(|e| {
+            AppError::CustomError(format!("Failed to serialize MQTT message: {}", e))
+        })?;
+
+        writeln!(self.writer, "{}", line)
+            .map_err(|e| AppError::CustomError(format!("Failed to write MQTT recording: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// Reads a recording, skipping any lines that cannot be parsed.
+pub fn load_recording(path: &Path) -> Result<Vec<RecordedMessage>, AppError> {
+    let file = File::open(path).map_err(|e| {
+        AppError::CustomError(format!(
+            "Failed to open MQTT recording {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let mut messages = vec![];
+
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line
+            .map_err(|e| AppError::CustomError(format!("Failed to read MQTT recording: {}", e)))?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<RecordedMessage>(&line) {
+            Ok(message) => messages.push(message),
+            Err(e) => warn!("Skipping line {} of MQTT recording: {}", line_number + 1, e),
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Returns how long to wait before replaying `next` after `previous`, at the given speed
+/// multiplier. A speed of zero or less replays without any delay.
+pub fn replay_delay(previous: &RecordedMessage, next: &RecordedMessage, speed: f64) -> Duration {
+    if speed <= 0.0 {
+        return Duration::ZERO;
+    }
+
+    let elapsed = (next.received_at - previous.received_at)
+        .to_std()
+        .unwrap_or(Duration::ZERO);
+
+    elapsed.div_f64(speed).min(MAX_REPLAY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn message(second: u32) -> RecordedMessage {
+        RecordedMessage {
+            received_at: Utc.with_ymd_and_hms(2026, 10, 19, 10, 0, second).unwrap(),
+            topic: "glow/electricitymeter".to_string(),
+            payload: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_recording_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "smart-energy-explorer-recording-{}.jsonl",
+            uuid::Uuid::new_v4()
+        ));
+
+        {
+            let mut recorder = MqttRecorder::create(&path).unwrap();
+            recorder
+                .record("glow/electricitymeter", "{\"a\":1}")
+                .unwrap();
+            recorder.record("glow/gasmeter", "{\"b\":2}").unwrap();
+        }
+
+        let messages = load_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].topic, "glow/electricitymeter");
+        assert_eq!(messages[1].payload, "{\"b\":2}");
+    }
+
+    #[test]
+    fn test_replay_delay_at_real_speed() {
+        assert_eq!(
+            replay_delay(&message(0), &message(10), 1.0),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn test_replay_delay_accelerated() {
+        assert_eq!(
+            replay_delay(&message(0), &message(10), 10.0),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_replay_delay_without_pauses() {
+        assert_eq!(replay_delay(&message(0), &message(10), 0.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_replay_delay_out_of_order() {
+        assert_eq!(replay_delay(&message(10), &message(0), 1.0), Duration::ZERO);
+    }
+}
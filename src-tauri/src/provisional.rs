@@ -0,0 +1,222 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Timelike};
+use rust_decimal::{prelude::FromPrimitive, Decimal};
+
+const HALF_HOUR_MINUTES: i64 = 30;
+
+/// Derives provisional half-hourly consumption from the cumulative meter readings published
+/// over MQTT. Each half-hour is credited with the increase in the cumulative reading between
+/// the first reading seen in that half-hour and the first reading seen in the next one.
+///
+/// A half-hour is only credited if its baseline covers all of it, i.e. the baseline was read
+/// in its first minute or was the reading that closed the previous half-hour. The half-hour
+/// the tracker starts in, or restarts in after a gap or a meter reset, is otherwise partial.
+pub struct ProvisionalConsumptionTracker {
+    current: Option<CurrentSlot>,
+}
+
+#[derive(Clone, Copy)]
+struct CurrentSlot {
+    start: NaiveDateTime,
+    baseline_kwh: f64,
+    covers_slot: bool,
+}
+
+impl ProvisionalConsumptionTracker {
+    pub fn new() -> Self {
+        Self { current: None }
+    }
+
+    /// Records a cumulative reading in kWh and returns the half-hours (UTC start timestamp and
+    /// kWh) whose provisional value changed as a result.
+    pub fn record(
+        &mut self,
+        timestamp: NaiveDateTime,
+        cumulative_kwh: f64,
+    ) -> Vec<(NaiveDateTime, Decimal)> {
+        let slot = half_hour_slot_start(&timestamp);
+
+        let restarted = CurrentSlot {
+            start: slot,
+            baseline_kwh: cumulative_kwh,
+            covers_slot: timestamp - slot < Duration::minutes(1),
+        };
+
+        let Some(current) = self.current else {
+            self.current = Some(restarted);
+            return vec![];
+        };
+
+        if slot < current.start {
+            // Ignore readings that arrive out of order
+            return vec![];
+        }
+
+        if cumulative_kwh < current.baseline_kwh {
+            // The meter has been reset or replaced, so start again from this reading
+            self.current = Some(restarted);
+            return vec![];
+        }
+
+        let delta = to_decimal(cumulative_kwh - current.baseline_kwh);
+
+        if slot == current.start {
+            return if current.covers_slot {
+                vec![(current.start, delta)]
+            } else {
+                vec![]
+            };
+        }
+
+        // Readings were missed for at least one whole half-hour if this isn't the next one, so
+        // there is no way of knowing how the consumption was spread across them
+        let is_next_slot = slot - current.start == Duration::minutes(HALF_HOUR_MINUTES);
+
+        self.current = Some(CurrentSlot {
+            covers_slot: is_next_slot || restarted.covers_slot,
+            ..restarted
+        });
+
+        if is_next_slot && current.covers_slot {
+            vec![(current.start, delta)]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Parses the RFC 3339 timestamp of a meter reading, returning `None` if it's malformed.
+pub fn parse_reading_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.naive_utc())
+}
+
+fn half_hour_slot_start(timestamp: &NaiveDateTime) -> NaiveDateTime {
+    let minute = if timestamp.minute() < 30 { 0 } else { 30 };
+
+    timestamp
+        .date()
+        .and_hms_opt(timestamp.hour(), minute, 0)
+        .unwrap()
+}
+
+fn to_decimal(kwh: f64) -> Decimal {
+    Decimal::from_f64(kwh)
+        .expect("f64 should fit into Decimal")
+        .round_dp(3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_first_reading_only_sets_baseline() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        assert!(tracker.record(at(10, 0), 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_reading_in_same_slot_updates_slot() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        tracker.record(at(10, 0), 100.0);
+        let result = tracker.record(at(10, 20), 100.25);
+
+        assert_eq!(result, vec![(at(10, 0), Decimal::new(250, 3))]);
+    }
+
+    #[test]
+    fn test_reading_in_next_slot_closes_previous_slot() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        tracker.record(at(10, 0), 100.0);
+        tracker.record(at(10, 20), 100.25);
+        let result = tracker.record(at(10, 31), 100.5);
+
+        assert_eq!(result, vec![(at(10, 0), Decimal::new(500, 3))]);
+
+        let result = tracker.record(at(10, 40), 100.75);
+
+        assert_eq!(result, vec![(at(10, 30), Decimal::new(250, 3))]);
+    }
+
+    #[test]
+    fn test_slot_started_part_way_through_is_not_credited() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        tracker.record(at(10, 5), 100.0);
+        assert!(tracker.record(at(10, 20), 100.25).is_empty());
+        assert!(tracker.record(at(10, 31), 100.5).is_empty());
+
+        let result = tracker.record(at(10, 40), 100.75);
+
+        assert_eq!(result, vec![(at(10, 30), Decimal::new(250, 3))]);
+    }
+
+    #[test]
+    fn test_gap_in_readings_is_not_attributed() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        tracker.record(at(10, 0), 100.0);
+        let result = tracker.record(at(11, 10), 101.0);
+
+        assert!(result.is_empty());
+
+        // The slot resumed part way through isn't credited either
+        assert!(tracker.record(at(11, 20), 101.5).is_empty());
+        assert!(tracker.record(at(11, 30), 101.75).is_empty());
+
+        let result = tracker.record(at(11, 40), 102.0);
+
+        assert_eq!(result, vec![(at(11, 30), Decimal::new(250, 3))]);
+    }
+
+    #[test]
+    fn test_gap_ending_at_start_of_slot_is_credited() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        tracker.record(at(10, 0), 100.0);
+        assert!(tracker.record(at(11, 0), 101.0).is_empty());
+
+        let result = tracker.record(at(11, 20), 101.5);
+
+        assert_eq!(result, vec![(at(11, 0), Decimal::new(500, 3))]);
+    }
+
+    #[test]
+    fn test_meter_reset_restarts_baseline() {
+        let mut tracker = ProvisionalConsumptionTracker::new();
+
+        tracker.record(at(10, 0), 100.0);
+        assert!(tracker.record(at(10, 10), 2.0).is_empty());
+        assert!(tracker.record(at(10, 15), 2.5).is_empty());
+        assert!(tracker.record(at(10, 30), 3.0).is_empty());
+
+        let result = tracker.record(at(10, 45), 3.5);
+
+        assert_eq!(result, vec![(at(10, 30), Decimal::new(500, 3))]);
+    }
+
+    #[test]
+    fn test_parse_reading_timestamp() {
+        assert_eq!(
+            parse_reading_timestamp("2026-10-19T10:05:00Z"),
+            Some(at(10, 5))
+        );
+        assert_eq!(
+            parse_reading_timestamp("2026-10-19T11:05:00+01:00"),
+            Some(at(10, 5))
+        );
+        assert_eq!(parse_reading_timestamp("not a timestamp"), None);
+    }
+}
@@ -1,11 +1,56 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    alert_rule (alert_rule_id) {
+        alert_rule_id -> Integer,
+        name -> Text,
+        threshold_watts -> Double,
+        duration_seconds -> Integer,
+        window_start -> Nullable<Time>,
+        window_end -> Nullable<Time>,
+        hysteresis_watts -> Double,
+        is_enabled -> Bool,
+        notify -> Bool,
+    }
+}
+
+diesel::table! {
+    consumption_anomaly (consumption_anomaly_id) {
+        consumption_anomaly_id -> Integer,
+        fuel -> Text,
+        granularity -> Text,
+        period_start -> Timestamp,
+        london_date_id -> Integer,
+        expected_wh -> Double,
+        actual_wh -> BigInt,
+        z_score -> Double,
+        detected_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     electricity_consumption (electricity_consumption_id) {
         electricity_consumption_id -> Integer,
         timestamp -> Timestamp,
         energy_consumption_wh -> BigInt,
         london_date_id -> Nullable<Integer>,
+        is_provisional -> Bool,
+        quality_flag -> Integer,
+    }
+}
+
+diesel::table! {
+    electricity_daily_rollup (london_date_id) {
+        london_date_id -> Integer,
+        energy_consumption_wh -> BigInt,
+        is_provisional -> Bool,
+    }
+}
+
+diesel::table! {
+    electricity_monthly_rollup (london_month_id) {
+        london_month_id -> Integer,
+        energy_consumption_wh -> BigInt,
     }
 }
 
@@ -51,6 +96,23 @@ diesel::table! {
         timestamp -> Timestamp,
         energy_consumption_wh -> BigInt,
         london_date_id -> Nullable<Integer>,
+        is_provisional -> Bool,
+        quality_flag -> Integer,
+    }
+}
+
+diesel::table! {
+    gas_daily_rollup (london_date_id) {
+        london_date_id -> Integer,
+        energy_consumption_wh -> BigInt,
+        is_provisional -> Bool,
+    }
+}
+
+diesel::table! {
+    gas_monthly_rollup (london_month_id) {
+        london_month_id -> Integer,
+        energy_consumption_wh -> BigInt,
     }
 }
 
@@ -79,14 +141,52 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    reporting_timezone (reporting_timezone_id) {
+        reporting_timezone_id -> Integer,
+        timezone -> Text,
+    }
+}
+
+diesel::table! {
+    triggered_alert (triggered_alert_id) {
+        triggered_alert_id -> Integer,
+        alert_rule_id -> Integer,
+        rule_name -> Text,
+        exceeded_since -> Timestamp,
+        triggered_at -> Timestamp,
+        power_watts -> Double,
+        threshold_watts -> Double,
+    }
+}
+
+diesel::table! {
+    weather_temperature (weather_temperature_id) {
+        weather_temperature_id -> Integer,
+        timestamp -> Timestamp,
+        temperature_celsius -> Double,
+        london_date_id -> Integer,
+        is_daily -> Bool,
+    }
+}
+
 diesel::allow_tables_to_appear_in_same_query!(
+    alert_rule,
+    consumption_anomaly,
     electricity_consumption,
+    electricity_daily_rollup,
+    electricity_monthly_rollup,
     electricity_standing_charge,
     electricity_tariff_plan,
     electricity_unit_price,
     energy_profile,
     gas_consumption,
+    gas_daily_rollup,
+    gas_monthly_rollup,
     gas_standing_charge,
     gas_tariff_plan,
     gas_unit_price,
+    reporting_timezone,
+    triggered_alert,
+    weather_temperature,
 );
@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use diesel::sqlite::SqliteConnection;
+use log::{error, info};
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+use crate::{
+    backup::{automatic_backup_directory, create_automatic_backup, stage_snapshot_restore},
+    db,
+    utils::BackupSettings,
+};
+
+/// Why the database couldn't be migrated when the app started. The migrations run in a single
+/// transaction, so the database is left as it was, and the app carries on with it open
+/// read-only.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StartupError {
+    pub message: String,
+    /// The backup taken just before migrating, if there was anything to back up
+    pub snapshot_path: Option<String>,
+}
+
+/// Backs up an existing database that has migrations to run, then runs them. The snapshot is
+/// taken even if automatic backups are turned off, so that there's always one to go back to.
+pub fn migrate_with_snapshot(
+    conn: &mut SqliteConnection,
+    db_path: &Path,
+    backup_settings: &BackupSettings,
+) -> Result<(), StartupError> {
+    let snapshot = if db::has_pending_migrations(conn) {
+        let snapshot = create_automatic_backup(
+            conn,
+            &automatic_backup_directory(db_path),
+            "migration",
+            backup_settings.automatic_backup_count.max(1),
+        )
+        .map_err(|e| StartupError {
+            message: format!("Couldn't back up the database before migrating it. {}", e),
+            snapshot_path: None,
+        })?;
+
+        Some(snapshot)
+    } else {
+        None
+    };
+
+    db::run_migrations(conn).map_err(|e| {
+        error!("Database migration failed: {}", e);
+
+        StartupError {
+            message: e.to_string(),
+            snapshot_path: snapshot.map(|snapshot| snapshot.path),
+        }
+    })
+}
+
+/// Tells the user that the database couldn't be migrated, and offers to restore the snapshot
+/// taken before migrating or to carry on read-only.
+pub fn show_startup_error_dialog(
+    app_handle: &AppHandle,
+    db_path: PathBuf,
+    startup_error: &StartupError,
+) {
+    let message = format!(
+        "Smart Energy Explorer couldn't update its database, so it has been opened read-only. \
+        Your data hasn't been changed.\n\n{}",
+        startup_error.message
+    );
+
+    let dialog = app_handle
+        .dialog()
+        .message(message)
+        .title("Database update failed")
+        .kind(MessageDialogKind::Error);
+
+    let Some(snapshot_path) = startup_error.snapshot_path.clone() else {
+        dialog.show(|_| ());
+        return;
+    };
+
+    let app_handle_clone = app_handle.clone();
+
+    dialog
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Restore snapshot".into(),
+            "Open read-only".into(),
+        ))
+        .show(move |restore| {
+            if !restore {
+                info!("Continuing with the database open read-only");
+                return;
+            }
+
+            restore_snapshot(&app_handle_clone, &PathBuf::from(snapshot_path), &db_path);
+        });
+}
+
+/// Stages the snapshot taken before migrating to replace the database, and restarts the app. If
+/// it can't be staged, the app carries on read-only.
+fn restore_snapshot(app_handle: &AppHandle, snapshot_path: &Path, db_path: &Path) {
+    match stage_snapshot_restore(snapshot_path, db_path) {
+        Ok(snapshot) => {
+            info!("Restarting to restore the snapshot {}", snapshot.path);
+
+            app_handle.restart();
+        }
+        Err(e) => {
+            error!("Failed to restore the snapshot: {}", e);
+
+            app_handle
+                .dialog()
+                .message(format!(
+                    "Couldn't restore {}, so the database is still open read-only.\n\n{}",
+                    snapshot_path.display(),
+                    e
+                ))
+                .title("Restore failed")
+                .kind(MessageDialogKind::Error)
+                .show(|_| ());
+        }
+    }
+}
@@ -1,15 +1,18 @@
-use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeZone, Utc};
-use chrono_tz::Europe::London;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
 use keyring_core::Entry;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter, Manager};
+use uuid::Uuid;
 
 use crate::{
+    analysis::{anomaly::DEFAULT_ANOMALY_Z_SCORE, degree_days::DEFAULT_BASE_TEMPERATURE_CELSIUS},
+    api_server::DEFAULT_API_SERVER_PORT,
     app_settings::AppSettings,
+    backup::DEFAULT_AUTOMATIC_BACKUP_COUNT,
     clients::glowmarkt::GlowmarktDataProvider,
     commands::{ApiError, APP_SERVICE_NAME},
-    data::energy_profile::{EnergyProfile, EnergyProfileRepository, SqliteEnergyProfileRepository},
-    db::SqliteConnectionPool,
+    dates::DEFAULT_REPORTING_TIMEZONE,
     AppError, AppState, MqttMessage,
 };
 
@@ -17,38 +20,6 @@ pub fn parse_iso_string_to_naive_date(iso_date_str: &str) -> Result<NaiveDate, A
     NaiveDate::parse_from_str(&iso_date_str[..10], "%Y-%m-%d").map_err(ApiError::ChronoParseError)
 }
 
-pub fn london_midnight_as_utc(date: &NaiveDate) -> NaiveDateTime {
-    let london_midnight_local = London
-        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
-        .unwrap();
-
-    let london_midnight_utc = london_midnight_local.with_timezone(&Utc);
-
-    london_midnight_utc.naive_utc()
-}
-
-pub fn utc_timestamp_to_london_date_id(timestamp_utc: &NaiveDateTime) -> i32 {
-    let london_time = timestamp_utc.and_utc().with_timezone(&London);
-
-    london_time
-        .format("%Y%m%d")
-        .to_string()
-        .parse::<i32>()
-        .unwrap()
-}
-
-pub fn naive_date_to_london_date_id(date: &NaiveDate) -> i32 {
-    (date.year() * 10000) + (date.month() as i32 * 100) + (date.day() as i32)
-}
-
-pub fn london_date_id_to_naive_date(date_id: i32) -> NaiveDate {
-    let year = date_id / 10000;
-    let month = ((date_id % 10000) / 100) as u32;
-    let day = (date_id % 100) as u32;
-
-    NaiveDate::from_ymd_opt(year, month, day).expect("Invalid date_id in the database")
-}
-
 pub fn emit_event<T>(app_handle: &AppHandle, event: &str, payload: T) -> Result<(), AppError>
 where
     T: Serialize + Clone,
@@ -60,25 +31,6 @@ where
     Ok(())
 }
 
-pub fn get_or_create_energy_profile(
-    connection_pool: SqliteConnectionPool,
-    name: &str,
-    base_unit: &str,
-) -> Result<EnergyProfile, AppError> {
-    let repository = SqliteEnergyProfileRepository::new(connection_pool);
-
-    repository.get_energy_profile(name).or_else(|get_error| {
-        repository
-            .create_energy_profile(name, base_unit)
-            .map_err(|create_error| {
-                AppError::CustomError(format!(
-                    "Failed to fetch profile {}, get error: {}, create error: {}",
-                    name, get_error, create_error
-                ))
-            })
-    })
-}
-
 pub async fn get_glowmarkt_data_provider() -> Result<Option<GlowmarktDataProvider>, AppError> {
     let credentials_result =
         tokio::task::spawn_blocking(|| get_glowmarkt_credentials_opt()).await?;
@@ -251,14 +203,44 @@ pub fn save_mqtt_credentials(credentials: &MqttCredentials) -> Result<(), AppErr
     Ok(())
 }
 
-#[derive(Clone, serde::Serialize)]
+pub const DEFAULT_MQTT_QOS: i32 = 1;
+
+pub const DEFAULT_MQTT_KEEP_ALIVE_SECONDS: u64 = 60;
+
+/// The broker configured through the original single-broker settings. Its settings and
+/// credentials are stored under the original keys.
+pub const DEFAULT_MQTT_BROKER_NAME: &str = "default";
+
+/// How the payloads received from a broker are interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MqttPayloadParser {
+    /// Glow IHD electricity and gas meter messages
+    #[default]
+    Glow,
+    /// Any JSON payload, passed to the frontend as is
+    Json,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MqttSettings {
+    pub name: String,
+    pub parser: MqttPayloadParser,
     pub hostname: String,
     pub topic: String,
     pub gas_topic: String,
     pub username: String,
     pub password: String,
+    pub client_id: String,
+    pub qos: i32,
+    pub persistent_session: bool,
+    pub keep_alive_seconds: u64,
+    /// Topic to publish a retained "online" status to, with "offline" as the last will.
+    pub status_topic: String,
+    /// Whether this broker relays the Glow IHD for the meters the app stores. Only its readings
+    /// are stored as provisional consumption and evaluated for alerts.
+    pub glow_source: bool,
 }
 
 impl MqttSettings {
@@ -275,6 +257,12 @@ pub struct MqttAppSettings {
     pub hostname: Option<String>,
     pub topic: Option<String>,
     pub gas_topic: Option<String>,
+    pub client_id: String,
+    pub qos: i32,
+    pub persistent_session: bool,
+    pub keep_alive_seconds: u64,
+    pub status_topic: String,
+    pub glow_source: bool,
 }
 
 impl MqttAppSettings {
@@ -285,12 +273,287 @@ impl MqttAppSettings {
 
         let gas_topic = app_settings.get::<String>("mqttGasTopic")?;
 
+        // Generated at startup by ensure_mqtt_client_id
+        let client_id = app_settings
+            .get::<String>("mqttClientId")?
+            .unwrap_or_default();
+
+        let qos = app_settings
+            .get::<i32>("mqttQos")?
+            .unwrap_or(DEFAULT_MQTT_QOS)
+            .clamp(0, 2);
+
+        let persistent_session = app_settings
+            .get::<bool>("mqttPersistentSession")?
+            .unwrap_or(true);
+
+        let keep_alive_seconds = app_settings
+            .get::<u64>("mqttKeepAliveSeconds")?
+            .unwrap_or(DEFAULT_MQTT_KEEP_ALIVE_SECONDS);
+
+        let status_topic = app_settings
+            .get::<String>("mqttStatusTopic")?
+            .unwrap_or_default();
+
+        // The default broker was the only Glow source before there was a choice
+        let glow_source = app_settings.get::<bool>("mqttGlowSource")?.unwrap_or(true);
+
         Ok(MqttAppSettings {
             hostname,
             topic,
             gas_topic,
+            client_id,
+            qos,
+            persistent_session,
+            keep_alive_seconds,
+            status_topic,
+            glow_source,
+        })
+    }
+}
+
+pub fn new_mqtt_client_id() -> String {
+    format!("smart-energy-explorer-{}", Uuid::new_v4())
+}
+
+/// Stores a client ID for the default broker if it doesn't have one. The ID is kept stable
+/// across launches so that the broker can hold a persistent session for us, rather than
+/// collecting a stale session on every launch.
+pub fn ensure_mqtt_client_id(app_settings: &AppSettings) -> Result<(), AppError> {
+    let has_client_id = app_settings
+        .get::<String>("mqttClientId")?
+        .is_some_and(|client_id| !client_id.is_empty());
+
+    if !has_client_id {
+        app_settings.safe_set("mqttClientId", new_mqtt_client_id())?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MqttSource {
+    Broker,
+    Replay,
+}
+
+/// Settings for recording the raw messages received from the broker, and for replaying a
+/// recording in place of the broker.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MqttReplaySettings {
+    pub source: MqttSource,
+    pub replay_file: String,
+    pub replay_speed: f64,
+    pub record_file: String,
+}
+
+impl MqttReplaySettings {
+    pub fn from_app_settings(app_settings: &AppSettings) -> Result<Self, AppError> {
+        Ok(MqttReplaySettings {
+            source: app_settings
+                .get::<MqttSource>("mqttSource")?
+                .unwrap_or(MqttSource::Broker),
+            replay_file: app_settings
+                .get::<String>("mqttReplayFile")?
+                .unwrap_or_default(),
+            replay_speed: app_settings.get::<f64>("mqttReplaySpeed")?.unwrap_or(1.0),
+            record_file: app_settings
+                .get::<String>("mqttRecordFile")?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn save(&self, app_settings: &AppSettings) -> Result<(), AppError> {
+        let source = match self.source {
+            MqttSource::Broker => "broker",
+            MqttSource::Replay => "replay",
+        };
+
+        app_settings.safe_set("mqttSource", source)?;
+
+        app_settings.safe_set("mqttReplayFile", self.replay_file.trim())?;
+
+        app_settings.safe_set("mqttReplaySpeed", self.replay_speed)?;
+
+        app_settings.safe_set("mqttRecordFile", self.record_file.trim())?;
+
+        Ok(())
+    }
+}
+
+/// Settings for detecting unusual consumption.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnomalySettings {
+    /// How many standard deviations from the baseline a day or half hour must be to be flagged
+    pub z_score: f64,
+}
+
+impl AnomalySettings {
+    pub fn from_app_settings(app_settings: &AppSettings) -> Result<Self, AppError> {
+        Ok(AnomalySettings {
+            z_score: app_settings
+                .get::<f64>("anomalyZScore")?
+                .unwrap_or(DEFAULT_ANOMALY_Z_SCORE),
         })
     }
+
+    pub fn save(&self, app_settings: &AppSettings) -> Result<(), AppError> {
+        app_settings.safe_set("anomalyZScore", self.z_score)?;
+
+        Ok(())
+    }
+}
+
+/// Settings for importing outdoor temperatures and normalising gas consumption.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeatherSettings {
+    /// The outdoor temperature above which no heating is needed
+    pub base_temperature_celsius: f64,
+    /// An HTTP endpoint, e.g. a local weather station, serving temperatures as CSV or JSON
+    pub source_url: String,
+}
+
+impl WeatherSettings {
+    pub fn from_app_settings(app_settings: &AppSettings) -> Result<Self, AppError> {
+        Ok(WeatherSettings {
+            base_temperature_celsius: app_settings
+                .get::<f64>("weatherBaseTemperature")?
+                .unwrap_or(DEFAULT_BASE_TEMPERATURE_CELSIUS),
+            source_url: app_settings
+                .get::<String>("weatherSourceUrl")?
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn save(&self, app_settings: &AppSettings) -> Result<(), AppError> {
+        app_settings.safe_set("weatherBaseTemperature", self.base_temperature_celsius)?;
+
+        app_settings.safe_set("weatherSourceUrl", self.source_url.trim())?;
+
+        Ok(())
+    }
+}
+
+/// Settings for the backups taken automatically before migrations and operations that delete
+/// data.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupSettings {
+    pub automatic_backups: bool,
+    /// How many automatic backups to keep, deleting the oldest
+    pub automatic_backup_count: u32,
+}
+
+impl BackupSettings {
+    pub fn from_app_settings(app_settings: &AppSettings) -> Result<Self, AppError> {
+        Ok(BackupSettings {
+            automatic_backups: app_settings
+                .get::<bool>("automaticBackups")?
+                .unwrap_or(false),
+            automatic_backup_count: app_settings
+                .get::<u32>("automaticBackupCount")?
+                .unwrap_or(DEFAULT_AUTOMATIC_BACKUP_COUNT),
+        })
+    }
+
+    pub fn save(&self, app_settings: &AppSettings) -> Result<(), AppError> {
+        app_settings.safe_set("automaticBackups", self.automatic_backups)?;
+
+        app_settings.safe_set("automaticBackupCount", self.automatic_backup_count)?;
+
+        Ok(())
+    }
+}
+
+/// The timezone that consumption and costs are reported in, as an IANA name such as
+/// "Europe/London". It's stored in the database, alongside the dates it applies to; the app's
+/// setting is only read for a database from before that.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimezoneSettings {
+    pub timezone: String,
+}
+
+impl TimezoneSettings {
+    pub fn from_app_settings(app_settings: &AppSettings) -> Result<Self, AppError> {
+        Ok(TimezoneSettings {
+            timezone: app_settings
+                .get::<String>("reportingTimezone")?
+                .unwrap_or_else(|| DEFAULT_REPORTING_TIMEZONE.name().to_string()),
+        })
+    }
+
+    pub fn parse(&self) -> Result<Tz, AppError> {
+        self.timezone
+            .trim()
+            .parse::<Tz>()
+            .map_err(|e| AppError::CustomError(format!("Unknown timezone: {}", e)))
+    }
+}
+
+/// Settings for the local HTTP API and Prometheus metrics, which are both off by default.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiServerSettings {
+    pub enabled: bool,
+    /// Whether to serve `/metrics`, which is independent of the JSON API
+    #[serde(default)]
+    pub metrics_enabled: bool,
+    /// The port on localhost to listen on
+    pub port: u16,
+}
+
+impl ApiServerSettings {
+    pub fn from_app_settings(app_settings: &AppSettings) -> Result<Self, AppError> {
+        Ok(ApiServerSettings {
+            enabled: app_settings
+                .get::<bool>("apiServerEnabled")?
+                .unwrap_or(false),
+            metrics_enabled: app_settings.get::<bool>("metricsEnabled")?.unwrap_or(false),
+            port: app_settings
+                .get::<u16>("apiServerPort")?
+                .unwrap_or(DEFAULT_API_SERVER_PORT),
+        })
+    }
+
+    pub fn save(&self, app_settings: &AppSettings) -> Result<(), AppError> {
+        app_settings.safe_set("apiServerEnabled", self.enabled)?;
+
+        app_settings.safe_set("metricsEnabled", self.metrics_enabled)?;
+
+        app_settings.safe_set("apiServerPort", self.port)?;
+
+        Ok(())
+    }
+}
+
+/// Replaces the API token with a new random one, so that any clients using the old one are
+/// locked out.
+pub fn regenerate_api_token() -> Result<String, AppError> {
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    let token_entry = Entry::new(APP_SERVICE_NAME, "api_token")
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    token_entry.set_password(&token).map_err(|e| {
+        AppError::CustomError(format!("Failed to save API token to keychain: {}", e))
+    })?;
+
+    Ok(token)
+}
+
+pub fn get_or_create_api_token() -> Result<String, AppError> {
+    let token_entry = Entry::new(APP_SERVICE_NAME, "api_token")
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    match get_entry_password(&token_entry)? {
+        Some(token) => Ok(token),
+        None => regenerate_api_token(),
+    }
 }
 
 pub async fn get_mqtt_settings_opt(
@@ -300,11 +563,19 @@ pub async fn get_mqtt_settings_opt(
 
     if let Some(credentials) = credentials_result? {
         return Ok(Some(MqttSettings {
+            name: DEFAULT_MQTT_BROKER_NAME.to_string(),
+            parser: MqttPayloadParser::Glow,
             hostname: mqtt_app_settings.hostname.unwrap_or("".to_string()),
             topic: mqtt_app_settings.topic.unwrap_or("".to_string()),
             gas_topic: mqtt_app_settings.gas_topic.unwrap_or("".to_string()),
             username: credentials.username,
             password: credentials.password,
+            client_id: mqtt_app_settings.client_id,
+            qos: mqtt_app_settings.qos,
+            persistent_session: mqtt_app_settings.persistent_session,
+            keep_alive_seconds: mqtt_app_settings.keep_alive_seconds,
+            status_topic: mqtt_app_settings.status_topic,
+            glow_source: mqtt_app_settings.glow_source,
         }));
     }
 
@@ -313,14 +584,203 @@ pub async fn get_mqtt_settings_opt(
     }
 
     Ok(Some(MqttSettings {
+        name: DEFAULT_MQTT_BROKER_NAME.to_string(),
+        parser: MqttPayloadParser::Glow,
         hostname: mqtt_app_settings.hostname.unwrap_or("".to_string()),
         topic: mqtt_app_settings.topic.unwrap_or("".to_string()),
         gas_topic: mqtt_app_settings.gas_topic.unwrap_or("".to_string()),
         username: "".to_string(),
         password: "".to_string(),
+        client_id: mqtt_app_settings.client_id,
+        qos: mqtt_app_settings.qos,
+        persistent_session: mqtt_app_settings.persistent_session,
+        keep_alive_seconds: mqtt_app_settings.keep_alive_seconds,
+        status_topic: mqtt_app_settings.status_topic,
+        glow_source: mqtt_app_settings.glow_source,
     }))
 }
 
+/// An additional named broker. Its credentials are stored in the keyring, keyed by its name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MqttBrokerConfig {
+    pub name: String,
+    pub parser: MqttPayloadParser,
+    pub hostname: String,
+    pub topic: String,
+    pub gas_topic: String,
+    pub client_id: String,
+    pub qos: i32,
+    pub persistent_session: bool,
+    pub keep_alive_seconds: u64,
+    pub status_topic: String,
+    #[serde(default)]
+    pub glow_source: bool,
+}
+
+impl MqttBrokerConfig {
+    pub fn load_all(app_settings: &AppSettings) -> Result<Vec<Self>, AppError> {
+        Ok(app_settings
+            .get::<Vec<MqttBrokerConfig>>("mqttBrokers")?
+            .unwrap_or_default())
+    }
+
+    pub fn save_all(
+        app_settings: &AppSettings,
+        brokers: &[MqttBrokerConfig],
+    ) -> Result<(), AppError> {
+        let json_value = serde_json::to_value(brokers).map_err(|e| {
+            AppError::CustomError(format!("Failed to serialize MQTT brokers: {}", e))
+        })?;
+
+        app_settings.safe_set("mqttBrokers", json_value)
+    }
+}
+
+/// Makes the named broker the only Glow source, as the app stores one meter per fuel and two
+/// sources would overwrite each other's half hours.
+pub fn set_glow_source_broker(app_settings: &AppSettings, name: &str) -> Result<(), AppError> {
+    app_settings.safe_set("mqttGlowSource", name == DEFAULT_MQTT_BROKER_NAME)?;
+
+    let mut brokers = MqttBrokerConfig::load_all(app_settings)?;
+
+    for broker in brokers.iter_mut() {
+        broker.glow_source = broker.name == name;
+    }
+
+    MqttBrokerConfig::save_all(app_settings, &brokers)
+}
+
+fn mqtt_broker_credentials_key(name: &str) -> String {
+    format!("mqtt_credentials_{}", name)
+}
+
+pub fn get_mqtt_broker_credentials_opt(name: &str) -> Result<Option<MqttCredentials>, AppError> {
+    if name == DEFAULT_MQTT_BROKER_NAME {
+        return get_mqtt_credentials_opt();
+    }
+
+    let (_, credentials_entry) = get_keyring_entry_value(&mqtt_broker_credentials_key(name))?;
+
+    credentials_entry
+        .map(|credentials_json| {
+            serde_json::from_str(&credentials_json).map_err(|e| {
+                AppError::CustomError(format!("Failed to deserialize mqtt credentials: {}", e))
+            })
+        })
+        .transpose()
+}
+
+pub fn save_mqtt_broker_credentials(
+    name: &str,
+    credentials: &MqttCredentials,
+) -> Result<(), AppError> {
+    if name == DEFAULT_MQTT_BROKER_NAME {
+        return save_mqtt_credentials(credentials);
+    }
+
+    let credentials_json = serde_json::to_string(credentials).map_err(|e| {
+        AppError::CustomError(format!("Failed to serialize mqtt credentials: {}", e))
+    })?;
+
+    let credentials_entry = Entry::new(APP_SERVICE_NAME, &mqtt_broker_credentials_key(name))
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    credentials_entry
+        .set_password(&credentials_json)
+        .map_err(|e| {
+            AppError::CustomError(format!(
+                "Failed to save mqtt credentials to keychain: {}",
+                e
+            ))
+        })?;
+
+    Ok(())
+}
+
+pub fn delete_mqtt_broker_credentials(name: &str) -> Result<(), AppError> {
+    delete_credential(&mqtt_broker_credentials_key(name))
+}
+
+/// Returns the settings of every configured broker, starting with the default broker.
+pub async fn get_all_mqtt_settings(
+    mqtt_app_settings: MqttAppSettings,
+    brokers: Vec<MqttBrokerConfig>,
+) -> Result<Vec<MqttSettings>, AppError> {
+    let mut all_settings: Vec<MqttSettings> = get_mqtt_settings_opt(mqtt_app_settings)
+        .await?
+        .into_iter()
+        .collect();
+
+    for broker in brokers {
+        let name = broker.name.clone();
+        let credentials =
+            tokio::task::spawn_blocking(move || get_mqtt_broker_credentials_opt(&name)).await??;
+
+        let (username, password) = credentials
+            .map(|c| (c.username, c.password))
+            .unwrap_or_default();
+
+        all_settings.push(MqttSettings {
+            name: broker.name,
+            parser: broker.parser,
+            hostname: broker.hostname,
+            topic: broker.topic,
+            gas_topic: broker.gas_topic,
+            username,
+            password,
+            client_id: broker.client_id,
+            qos: broker.qos,
+            persistent_session: broker.persistent_session,
+            keep_alive_seconds: broker.keep_alive_seconds,
+            status_topic: broker.status_topic,
+            glow_source: broker.glow_source,
+        });
+    }
+
+    Ok(all_settings)
+}
+
+/// Reloads the settings of every broker into the app state, and restarts the MQTT listeners.
+pub async fn refresh_mqtt_settings(app_state: &AppState) -> Result<(), AppError> {
+    let (mqtt_app_settings, brokers) = {
+        let app_settings =
+            app_state
+                .app_settings
+                .lock()
+                .map_err(|_| AppError::MutexPoisonedError {
+                    name: "app_settings".into(),
+                })?;
+
+        (
+            MqttAppSettings::from_app_settings(&app_settings)?,
+            MqttBrokerConfig::load_all(&app_settings)?,
+        )
+    };
+
+    let all_settings = get_all_mqtt_settings(mqtt_app_settings, brokers).await?;
+
+    {
+        let mut mqtt_settings =
+            app_state
+                .mqtt_settings
+                .lock()
+                .map_err(|_| AppError::MutexPoisonedError {
+                    name: "mqtt_settings".into(),
+                })?;
+
+        *mqtt_settings = all_settings;
+    }
+
+    app_state
+        .mqtt_message_sender
+        .send(MqttMessage::SettingsUpdated)
+        .await
+        .map_err(|e| AppError::CustomError(e.to_string()))?;
+
+    Ok(())
+}
+
 fn get_entry_password(entry: &Entry) -> Result<Option<String>, AppError> {
     match entry.get_password() {
         Ok(password) => Ok(Some(password)),
@@ -373,6 +833,8 @@ pub async fn reset_mqtt_settings(app_handle: &AppHandle) -> Result<(), AppError>
         app_settings.safe_set("mqttTopic", "")?;
 
         app_settings.safe_set("mqttGasTopic", "")?;
+
+        app_settings.safe_set("mqttStatusTopic", "")?;
     }
 
     tokio::task::spawn_blocking(|| {
@@ -386,40 +848,28 @@ pub async fn reset_mqtt_settings(app_handle: &AppHandle) -> Result<(), AppError>
     })
     .await??;
 
-    {
-        let mut mqtt_settings =
-            app_state
-                .mqtt_settings
-                .lock()
-                .map_err(|_| AppError::MutexPoisonedError {
-                    name: "mqtt_settings".into(),
-                })?;
-
-        *mqtt_settings = None;
-    }
-
-    app_state
-        .mqtt_message_sender
-        .send(MqttMessage::SettingsUpdated)
-        .await
-        .map_err(|e| AppError::CustomError(e.to_string()))?;
-
-    Ok(())
+    refresh_mqtt_settings(&app_state).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::NaiveDate;
-    use chrono::NaiveTime;
 
     fn complete_settings() -> MqttSettings {
         MqttSettings {
+            name: DEFAULT_MQTT_BROKER_NAME.to_string(),
+            parser: MqttPayloadParser::Glow,
             hostname: "localhost".to_string(),
             topic: "test/topic".to_string(),
             gas_topic: "test/gas".to_string(),
             username: "user".to_string(),
             password: "password".to_string(),
+            client_id: "smart-energy-explorer-test".to_string(),
+            qos: DEFAULT_MQTT_QOS,
+            persistent_session: true,
+            keep_alive_seconds: DEFAULT_MQTT_KEEP_ALIVE_SECONDS,
+            status_topic: "".to_string(),
+            glow_source: true,
         }
     }
 
@@ -498,60 +948,4 @@ mod tests {
         };
         assert!(!settings.is_complete());
     }
-
-    #[test]
-    fn test_london_midnight_during_gmt_winter() {
-        let winter_date = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
-
-        let result = london_midnight_as_utc(&winter_date);
-
-        let expected = winter_date.and_hms_opt(0, 0, 0).unwrap();
-        assert_eq!(result, expected);
-    }
-
-    #[test]
-    fn test_london_midnight_during_bst_summer() {
-        let summer_date = NaiveDate::from_ymd_opt(2026, 6, 26).unwrap();
-
-        let result = london_midnight_as_utc(&summer_date);
-
-        let expected_date = NaiveDate::from_ymd_opt(2026, 6, 25).unwrap();
-        let expected_time = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
-        let expected = expected_date.and_time(expected_time);
-
-        assert_eq!(result, expected);
-    }
-
-    #[test]
-    fn test_london_midnight_on_clocks_forward_transition_day() {
-        let transition_date = NaiveDate::from_ymd_opt(2026, 3, 29).unwrap();
-
-        let result = london_midnight_as_utc(&transition_date);
-
-        let expected = transition_date.and_hms_opt(0, 0, 0).unwrap();
-        assert_eq!(result, expected);
-    }
-
-    #[test]
-    fn test_london_midnight_on_clocks_back_transition_day() {
-        let transition_sunday = NaiveDate::from_ymd_opt(2026, 10, 25).unwrap();
-
-        let result = london_midnight_as_utc(&transition_sunday);
-
-        let expected_date = NaiveDate::from_ymd_opt(2026, 10, 24).unwrap();
-        let expected_time = NaiveTime::from_hms_opt(23, 0, 0).unwrap();
-        let expected = expected_date.and_time(expected_time);
-
-        assert_eq!(result, expected);
-    }
-
-    #[test]
-    fn test_london_midnight_day_after_clocks_back() {
-        let post_transition_monday = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap();
-
-        let result = london_midnight_as_utc(&post_transition_monday);
-
-        let expected = post_transition_monday.and_hms_opt(0, 0, 0).unwrap();
-        assert_eq!(result, expected);
-    }
 }
@@ -0,0 +1,200 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone};
+use serde_json::Value;
+
+use crate::{
+    data::weather::TemperatureValue,
+    dates::{london_midnight_as_utc, reporting_timezone},
+    AppError,
+};
+
+/// Column names, or JSON keys, recognised for the time of a reading.
+const TIME_FIELDS: [&str; 4] = ["timestamp", "datetime", "time", "date"];
+
+/// Column names, or JSON keys, recognised for the temperature in degrees Celsius.
+const TEMPERATURE_FIELDS: [&str; 5] = [
+    "temperature",
+    "temperature_celsius",
+    "temp",
+    "tavg",
+    "mean_temperature",
+];
+
+/// Parses the time of a reading. A date on its own is a daily reading for that day in the
+/// reporting timezone. A time without an offset is taken to be in the reporting timezone.
+/// Returns the UTC timestamp and whether the reading is daily.
+fn parse_reading_time(value: &str) -> Option<(NaiveDateTime, bool)> {
+    let value = value.trim().trim_matches('"');
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some((london_midnight_as_utc(&date), true));
+    }
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some((timestamp.naive_utc(), false));
+    }
+
+    [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .and_then(|local| reporting_timezone().from_local_datetime(&local).earliest())
+    .map(|timestamp| (timestamp.naive_utc(), false))
+}
+
+fn find_field(fields: &[String], names: &[&str]) -> Option<usize> {
+    fields
+        .iter()
+        .position(|field| names.contains(&field.to_ascii_lowercase().as_str()))
+}
+
+/// Parses comma separated temperatures with a header row naming the time and temperature
+/// columns. Rows that cannot be parsed are skipped.
+pub fn parse_temperature_csv(text: &str) -> Result<Vec<TemperatureValue>, AppError> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+
+    let header: Vec<String> = lines
+        .next()
+        .ok_or_else(|| AppError::CustomError("The temperature CSV is empty".into()))?
+        .split(',')
+        .map(|field| field.trim().trim_matches('"').to_string())
+        .collect();
+
+    let (time_index, temperature_index) = find_field(&header, &TIME_FIELDS)
+        .zip(find_field(&header, &TEMPERATURE_FIELDS))
+        .ok_or_else(|| {
+            AppError::CustomError(
+                "The temperature CSV needs a time or date column and a temperature column".into(),
+            )
+        })?;
+
+    Ok(lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+
+            let (timestamp, is_daily) = parse_reading_time(fields.get(time_index)?)?;
+            let temperature_celsius = fields
+                .get(temperature_index)?
+                .trim()
+                .trim_matches('"')
+                .parse::<f64>()
+                .ok()?;
+
+            Some(TemperatureValue {
+                timestamp,
+                temperature_celsius,
+                is_daily,
+            })
+        })
+        .collect())
+}
+
+/// Parses a JSON array of objects with the same fields as the CSV columns.
+pub fn parse_temperature_json(text: &str) -> Result<Vec<TemperatureValue>, AppError> {
+    let value: Value = serde_json::from_str(text)
+        .map_err(|e| AppError::CustomError(format!("Invalid temperature JSON: {}", e)))?;
+
+    let readings = value.as_array().ok_or_else(|| {
+        AppError::CustomError("The temperature JSON must be an array of readings".into())
+    })?;
+
+    let field = |reading: &Value, names: &[&str]| -> Option<Value> {
+        let object = reading.as_object()?;
+
+        object
+            .iter()
+            .find(|(key, _)| names.contains(&key.to_ascii_lowercase().as_str()))
+            .map(|(_, value)| value.clone())
+    };
+
+    Ok(readings
+        .iter()
+        .filter_map(|reading| {
+            let (timestamp, is_daily) =
+                parse_reading_time(field(reading, &TIME_FIELDS)?.as_str()?)?;
+            let temperature_celsius = field(reading, &TEMPERATURE_FIELDS)?.as_f64()?;
+
+            Some(TemperatureValue {
+                timestamp,
+                temperature_celsius,
+                is_daily,
+            })
+        })
+        .collect())
+}
+
+/// Fetches temperatures from an HTTP endpoint, e.g. a local weather station, serving either
+/// format.
+pub async fn fetch_temperatures(url: &str) -> Result<Vec<TemperatureValue>, AppError> {
+    let text = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| AppError::CustomError(format!("Failed to fetch temperatures: {}", e)))?
+        .text()
+        .await
+        .map_err(|e| AppError::CustomError(format!("Failed to read temperatures: {}", e)))?;
+
+    if text.trim_start().starts_with('[') {
+        parse_temperature_json(&text)
+    } else {
+        parse_temperature_csv(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_daily_csv() {
+        let csv = "date,tavg\n2026-01-15,4.5\n2026-07-15,18.0\nbad,1\n";
+
+        let readings = parse_temperature_csv(csv).unwrap();
+
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].timestamp, utc(2026, 1, 15, 0));
+        assert_eq!(readings[0].temperature_celsius, 4.5);
+        assert!(readings[0].is_daily);
+        // London midnight in British Summer Time
+        assert_eq!(readings[1].timestamp, utc(2026, 7, 14, 23));
+    }
+
+    #[test]
+    fn test_parse_hourly_csv() {
+        let csv = "\"Time\",\"Humidity\",\"Temperature\"\n\"2026-07-15T12:00:00Z\",60,\"21.5\"\n2026-07-15 14:00,55,22\n";
+
+        let readings = parse_temperature_csv(csv).unwrap();
+
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].timestamp, utc(2026, 7, 15, 12));
+        assert_eq!(readings[0].temperature_celsius, 21.5);
+        assert!(!readings[0].is_daily);
+        assert_eq!(readings[1].timestamp, utc(2026, 7, 15, 13));
+    }
+
+    #[test]
+    fn test_parse_csv_without_temperature_column() {
+        assert!(parse_temperature_csv("date,humidity\n2026-01-15,80\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let json = r#"[{"timestamp": "2026-01-15T06:00:00+00:00", "temperature": -1.5}, {"timestamp": "2026-01-15T07:00:00+00:00"}]"#;
+
+        let readings = parse_temperature_json(json).unwrap();
+
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].timestamp, utc(2026, 1, 15, 6));
+        assert_eq!(readings[0].temperature_celsius, -1.5);
+    }
+}
@@ -8,6 +8,7 @@ import { NavigationEnd, Router, RouterOutlet } from '@angular/router';
 import { Observable, filter, map, startWith } from 'rxjs';
 
 import { NavigationBarComponent } from './components/navigation-bar/navigation-bar.component';
+import { ReadOnlyBannerComponent } from './components/read-only-banner/read-only-banner.component';
 import { StatusBarComponent } from './components/status-bar/status-bar.component';
 import { ThemeService } from './services/theme/theme.service';
 
@@ -17,6 +18,7 @@ import { ThemeService } from './services/theme/theme.service';
     RouterOutlet,
     CommonModule,
     NavigationBarComponent,
+    ReadOnlyBannerComponent,
     StatusBarComponent,
     MatIconModule,
     MatButtonModule,
@@ -0,0 +1,52 @@
+import { beforeEach, describe, expect, it, vi } from 'vitest';
+
+import { ComponentFixture, TestBed } from '@angular/core/testing';
+
+import { invoke } from '@tauri-apps/api/core';
+
+import { ReadOnlyBannerComponent } from './read-only-banner.component';
+
+vi.mock('@tauri-apps/api/core', () => ({ invoke: vi.fn() }));
+
+describe('ReadOnlyBannerComponent', () => {
+  let fixture: ComponentFixture<ReadOnlyBannerComponent>;
+
+  beforeEach(async () => {
+    vi.clearAllMocks();
+
+    await TestBed.configureTestingModule({
+      imports: [ReadOnlyBannerComponent],
+    }).compileComponents();
+
+    fixture = TestBed.createComponent(ReadOnlyBannerComponent);
+  });
+
+  it('should show nothing when the database was updated', async () => {
+    (invoke as any).mockResolvedValue(null);
+
+    fixture.detectChanges();
+    await fixture.whenStable();
+    fixture.detectChanges();
+
+    expect(invoke).toHaveBeenCalledWith('get_startup_error', {});
+    expect(fixture.nativeElement.querySelector('[role="alert"]')).toBeNull();
+  });
+
+  it('should show the startup error and snapshot', async () => {
+    (invoke as any).mockResolvedValue({
+      message: 'Failed to run migrations',
+      snapshotPath: '/backups/migration.sqlite',
+    });
+
+    fixture.detectChanges();
+    await fixture.whenStable();
+    fixture.detectChanges();
+
+    const banner: HTMLElement =
+      fixture.nativeElement.querySelector('[role="alert"]');
+
+    expect(banner.textContent).toContain('open read-only');
+    expect(banner.textContent).toContain('Failed to run migrations');
+    expect(banner.textContent).toContain('/backups/migration.sqlite');
+  });
+});
@@ -0,0 +1,28 @@
+import { ChangeDetectionStrategy, Component, OnInit, signal } from '@angular/core';
+import { MatIconModule } from '@angular/material/icon';
+
+import { invoke } from '@tauri-apps/api/core';
+
+/** Why the database couldn't be updated when the app started. */
+export interface StartupError {
+  message: string;
+  snapshotPath: string | null;
+}
+
+/** Shown while the database is open read-only because it couldn't be updated. */
+@Component({
+  selector: 'app-read-only-banner',
+  imports: [MatIconModule],
+  templateUrl: './read-only-banner.component.html',
+  styleUrl: './read-only-banner.component.scss',
+  changeDetection: ChangeDetectionStrategy.OnPush,
+})
+export class ReadOnlyBannerComponent implements OnInit {
+  protected startupError = signal<StartupError | null>(null);
+
+  public async ngOnInit(): Promise<void> {
+    this.startupError.set(
+      await invoke<StartupError | null>('get_startup_error', {}),
+    );
+  }
+}